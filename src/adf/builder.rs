@@ -0,0 +1,335 @@
+use super::model::*;
+use crate::client::Result;
+
+/// Shorthand constructors for the common nodes.
+impl Node {
+    pub fn text(text: &str) -> Self {
+        Node::Text {
+            text: text.to_string(),
+            marks: Vec::new(),
+        }
+    }
+
+    pub fn marked(text: &str, marks: Vec<Mark>) -> Self {
+        Node::Text {
+            text: text.to_string(),
+            marks,
+        }
+    }
+
+    pub fn paragraph(content: Vec<Node>) -> Self {
+        Node::Paragraph { content }
+    }
+
+    pub fn heading(level: u8, content: Vec<Node>) -> Self {
+        Node::Heading {
+            attrs: HeadingAttrs { level },
+            content,
+        }
+    }
+
+    pub fn bullet_list(items: Vec<Node>) -> Self {
+        Node::BulletList { content: items }
+    }
+
+    pub fn ordered_list(order: u64, items: Vec<Node>) -> Self {
+        Node::OrderedList {
+            attrs: if order == 1 {
+                None
+            } else {
+                Some(OrderedListAttrs { order })
+            },
+            content: items,
+        }
+    }
+
+    pub fn list_item(content: Vec<Node>) -> Self {
+        Node::ListItem { content }
+    }
+
+    pub fn code_block(language: Option<&str>, code: &str) -> Self {
+        Node::CodeBlock {
+            attrs: language.map(|l| CodeBlockAttrs {
+                language: Some(l.to_string()),
+            }),
+            content: if code.is_empty() {
+                Vec::new()
+            } else {
+                vec![Node::text(code)]
+            },
+        }
+    }
+
+    pub fn panel(panel_type: PanelType, content: Vec<Node>) -> Self {
+        Node::Panel {
+            attrs: PanelAttrs { panel_type },
+            content,
+        }
+    }
+
+    pub fn table(rows: Vec<Node>) -> Self {
+        Node::Table {
+            attrs: None,
+            content: rows,
+        }
+    }
+
+    pub fn table_row(cells: Vec<Node>) -> Self {
+        Node::TableRow { content: cells }
+    }
+
+    pub fn table_header(content: Vec<Node>) -> Self {
+        Node::TableHeader {
+            attrs: None,
+            content,
+        }
+    }
+
+    pub fn table_cell(content: Vec<Node>) -> Self {
+        Node::TableCell {
+            attrs: None,
+            content,
+        }
+    }
+}
+
+/// Builds a document from top level nodes, checking the structure against
+/// the ADF schema on `build()`.
+///
+/// ```
+/// use confluence::adf::{DocumentBuilder, Mark, Node};
+///
+/// let doc = DocumentBuilder::new()
+///     .heading(1, "Release notes")
+///     .push(Node::paragraph(vec![
+///         Node::text("Now with "),
+///         Node::marked("ADF", vec![Mark::Strong]),
+///     ]))
+///     .build()
+///     .unwrap();
+/// assert_eq!(doc.content.len(), 2);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DocumentBuilder {
+    content: Vec<Node>,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(mut self, node: Node) -> Self {
+        self.content.push(node);
+        self
+    }
+
+    pub fn paragraph(self, text: &str) -> Self {
+        self.push(Node::paragraph(vec![Node::text(text)]))
+    }
+
+    pub fn heading(self, level: u8, text: &str) -> Self {
+        self.push(Node::heading(level, vec![Node::text(text)]))
+    }
+
+    pub fn code_block(self, language: Option<&str>, code: &str) -> Self {
+        self.push(Node::code_block(language, code))
+    }
+
+    pub fn rule(self) -> Self {
+        self.push(Node::Rule)
+    }
+
+    pub fn build(self) -> Result<Document> {
+        let doc = Document::new(self.content);
+        validate(&doc)?;
+        Ok(doc)
+    }
+}
+
+/// Checks the parts of the ADF schema Confluence rejects most often: which
+/// nodes may contain which, heading levels, empty text and mark combinations.
+pub fn validate(doc: &Document) -> Result<()> {
+    if doc.version != 1 || doc.doc_type != "doc" {
+        return Err("ADF: expected a version 1 document of type doc".into());
+    }
+    for node in &doc.content {
+        if node.is_inline() {
+            return Err(
+                format!("ADF: {} is not allowed at the top level", node.type_name()).into(),
+            );
+        }
+        validate_node(node, "doc")?;
+    }
+    Ok(())
+}
+
+fn validate_node(node: &Node, path: &str) -> Result<()> {
+    let path = format!("{}/{}", path, node.type_name());
+    let children = node.content();
+    let only = |allowed: &[&str]| -> Result<()> {
+        match children.iter().find(|c| !allowed.contains(&c.type_name())) {
+            Some(c) => Err(format!("ADF: {} is not allowed in {}", c.type_name(), path).into()),
+            None => Ok(()),
+        }
+    };
+    let blocks = || -> Result<()> {
+        match children.iter().find(|c| c.is_inline()) {
+            Some(c) => {
+                Err(format!("ADF: inline {} is not allowed in {}", c.type_name(), path).into())
+            }
+            None => Ok(()),
+        }
+    };
+    let inlines = || -> Result<()> {
+        match children.iter().find(|c| !c.is_inline()) {
+            Some(c) => {
+                Err(format!("ADF: block {} is not allowed in {}", c.type_name(), path).into())
+            }
+            None => Ok(()),
+        }
+    };
+    let non_empty = || -> Result<()> {
+        if children.is_empty() {
+            Err(format!("ADF: {} must not be empty", path).into())
+        } else {
+            Ok(())
+        }
+    };
+
+    match node {
+        Node::Text { text, marks } => {
+            if text.is_empty() {
+                return Err(format!("ADF: empty text in {}", path).into());
+            }
+            let code = marks.contains(&Mark::Code);
+            let other = marks
+                .iter()
+                .any(|m| !matches!(m, Mark::Code | Mark::Link { .. }));
+            if code && other {
+                return Err(format!("ADF: code mark combined with formatting in {}", path).into());
+            }
+        }
+        Node::Paragraph { .. } => inlines()?,
+        Node::Heading { attrs, .. } => {
+            if attrs.level < 1 || attrs.level > 6 {
+                return Err(format!("ADF: heading level {} out of range", attrs.level).into());
+            }
+            inlines()?;
+        }
+        Node::BulletList { .. } | Node::OrderedList { .. } => {
+            non_empty()?;
+            only(&["listItem"])?;
+        }
+        Node::ListItem { content } => {
+            non_empty()?;
+            blocks()?;
+            if !matches!(
+                content.first(),
+                Some(Node::Paragraph { .. })
+                    | Some(Node::CodeBlock { .. })
+                    | Some(Node::MediaSingle { .. })
+            ) {
+                return Err(format!("ADF: {} must start with a paragraph", path).into());
+            }
+        }
+        Node::CodeBlock { content, .. } => {
+            only(&["text"])?;
+            if content
+                .iter()
+                .any(|c| matches!(c, Node::Text { marks, .. } if !marks.is_empty()))
+            {
+                return Err(format!("ADF: marks are not allowed in {}", path).into());
+            }
+        }
+        Node::Blockquote { .. } | Node::Panel { .. } | Node::Expand { .. } => {
+            non_empty()?;
+            blocks()?;
+        }
+        Node::Table { .. } => {
+            non_empty()?;
+            only(&["tableRow"])?;
+        }
+        Node::TableRow { .. } => {
+            non_empty()?;
+            only(&["tableHeader", "tableCell"])?;
+        }
+        Node::TableHeader { .. } | Node::TableCell { .. } => {
+            non_empty()?;
+            blocks()?;
+        }
+        Node::MediaSingle { .. } => only(&["media"])?,
+        Node::TaskList { .. } => {
+            non_empty()?;
+            only(&["taskItem", "taskList"])?;
+        }
+        Node::TaskItem { .. } => inlines()?,
+        Node::BodiedExtension { .. } => blocks()?,
+        _ => {}
+    }
+    for child in children {
+        validate_node(child, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_round_trip_keeps_unknown_nodes() {
+        let json = r#"{"version":1,"type":"doc","content":[{"type":"heading","attrs":{"level":2},"content":[{"type":"text","text":"Hi","marks":[{"type":"strong"},{"type":"link","attrs":{"href":"https://example.com"}}]}]},{"type":"decisionList","attrs":{"localId":"x"},"content":[]},{"type":"panel","attrs":{"panelType":"info"},"content":[{"type":"paragraph","content":[{"type":"text","text":"note"}]}]}]}"#;
+        let doc: Document = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            doc.content[0],
+            Node::heading(
+                2,
+                vec![Node::marked(
+                    "Hi",
+                    vec![
+                        Mark::Strong,
+                        Mark::Link {
+                            attrs: LinkAttrs {
+                                href: "https://example.com".to_string(),
+                                title: None
+                            }
+                        }
+                    ]
+                )]
+            )
+        );
+        assert_eq!(doc.content[1].type_name(), "decisionList");
+        assert_eq!(
+            serde_json::to_value(&doc).unwrap(),
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+        assert!(validate(&doc).is_ok());
+    }
+
+    #[test]
+    fn builder_rejects_invalid_structure() {
+        assert!(DocumentBuilder::new().heading(7, "x").build().is_err());
+        assert!(DocumentBuilder::new()
+            .push(Node::text("loose"))
+            .build()
+            .is_err());
+        assert!(DocumentBuilder::new()
+            .push(Node::bullet_list(vec![Node::paragraph(vec![])]))
+            .build()
+            .is_err());
+        assert!(DocumentBuilder::new()
+            .push(Node::paragraph(vec![Node::marked(
+                "x",
+                vec![Mark::Code, Mark::Strong]
+            )]))
+            .build()
+            .is_err());
+        assert!(DocumentBuilder::new()
+            .push(Node::bullet_list(vec![Node::list_item(vec![
+                Node::paragraph(vec![Node::text("ok")])
+            ])]))
+            .build()
+            .is_ok());
+    }
+}
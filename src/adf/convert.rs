@@ -0,0 +1,773 @@
+use serde_json::{json, Map, Value};
+
+use super::model::*;
+use crate::client::Result;
+use crate::storage::builders::{
+    AdmonitionKind, AdmonitionMacro, CodeMacro, ExpandMacro, StatusColour, StatusMacro,
+};
+use crate::storage::{self, Element, MacroBody, ResourceIdentifier};
+use crate::util::{civil_from_days, days_from_civil};
+
+const MACRO_EXTENSION_TYPE: &str = "com.atlassian.confluence.macro.core";
+
+/// Converts an ADF document into storage format.
+///
+/// Media nodes of type `file` become attachment images named after the
+/// media's `alt` text, since storage format refers to attachments by file
+/// name. Extensions become the structured macro named by their extension key.
+/// Node types without a storage format equivalent are dropped.
+pub fn to_storage(doc: &Document) -> String {
+    let mut writer = StorageWriter { task_id: 0 };
+    writer
+        .blocks(&doc.content)
+        .iter()
+        .map(|n| n.to_string())
+        .collect()
+}
+
+/// Converts storage format into an ADF document.
+///
+/// Links to pages keep their text but lose their target as ADF links need an
+/// absolute URL. Macros without an ADF equivalent become extensions carrying
+/// their parameters.
+pub fn from_storage(storage: &str) -> Result<Document> {
+    let doc = storage::parse(storage)?;
+    Ok(Document::new(blocks(&doc.nodes)))
+}
+
+struct StorageWriter {
+    task_id: u64,
+}
+
+impl StorageWriter {
+    fn blocks(&mut self, nodes: &[Node]) -> Vec<storage::Node> {
+        nodes.iter().flat_map(|n| self.node(n)).collect()
+    }
+
+    /// List items and table cells holding a single paragraph are written
+    /// without the paragraph, as Confluence does.
+    fn unwrapped(&mut self, nodes: &[Node]) -> Vec<storage::Node> {
+        match nodes {
+            [Node::Paragraph { content }] => self.blocks(content),
+            _ => self.blocks(nodes),
+        }
+    }
+
+    fn element(&mut self, name: &str, content: &[Node]) -> storage::Node {
+        let mut e = Element::new(name);
+        e.children = self.blocks(content);
+        e.into()
+    }
+
+    fn node(&mut self, node: &Node) -> Vec<storage::Node> {
+        let node = match node {
+            Node::Paragraph { content } => self.element("p", content),
+            Node::Text { text, marks } => text_with_marks(text, marks),
+            Node::Heading { attrs, content } => {
+                self.element(&format!("h{}", attrs.level.clamp(1, 6)), content)
+            }
+            Node::BulletList { content } => self.element("ul", content),
+            Node::OrderedList { attrs, content } => {
+                let mut e = Element::new("ol");
+                if let Some(a) = attrs.as_ref().filter(|a| a.order != 1) {
+                    e.set_attribute("start", &a.order.to_string());
+                }
+                e.children = self.blocks(content);
+                e.into()
+            }
+            Node::ListItem { content } => {
+                let mut e = Element::new("li");
+                e.children = self.unwrapped(content);
+                e.into()
+            }
+            Node::CodeBlock { attrs, content } => {
+                let code: String = content.iter().map(|n| n.text_content()).collect();
+                let mut m = CodeMacro::new(&code);
+                if let Some(language) = attrs.as_ref().and_then(|a| a.language.as_ref()) {
+                    m = m.language(language);
+                }
+                m.into()
+            }
+            Node::Blockquote { content } => self.element("blockquote", content),
+            Node::Rule => Element::new("hr").into(),
+            Node::HardBreak => Element::new("br").into(),
+            Node::Panel { attrs, content } => {
+                let kind = match attrs.panel_type {
+                    PanelType::Info | PanelType::Note => AdmonitionKind::Info,
+                    PanelType::Success => AdmonitionKind::Tip,
+                    PanelType::Warning => AdmonitionKind::Note,
+                    PanelType::Error => AdmonitionKind::Warning,
+                };
+                AdmonitionMacro::new(kind, self.blocks(content)).into()
+            }
+            Node::Table { content, .. } => {
+                let mut tbody = Element::new("tbody");
+                tbody.children = self.blocks(content);
+                Element::new("table").with_child(tbody).into()
+            }
+            Node::TableRow { content } => self.element("tr", content),
+            Node::TableHeader { content, .. } | Node::TableCell { content, .. } => {
+                let name = if let Node::TableHeader { .. } = node {
+                    "th"
+                } else {
+                    "td"
+                };
+                let mut e = Element::new(name);
+                e.children = self.unwrapped(content);
+                e.into()
+            }
+            Node::MediaSingle { content, .. } => {
+                return content.iter().flat_map(|n| self.node(n)).collect();
+            }
+            Node::Media { attrs } => {
+                let resource = match (&attrs.media_type, &attrs.url) {
+                    (MediaType::External, Some(url)) => {
+                        ResourceIdentifier::Url { value: url.clone() }
+                    }
+                    _ => ResourceIdentifier::Attachment {
+                        filename: attrs
+                            .alt
+                            .clone()
+                            .or_else(|| attrs.id.clone())
+                            .unwrap_or_default(),
+                        container: None,
+                    },
+                };
+                let mut image = Element::new("ac:image");
+                if let Some(width) = attrs.width {
+                    image.set_attribute("ac:width", &width.to_string());
+                }
+                if let Some(height) = attrs.height {
+                    image.set_attribute("ac:height", &height.to_string());
+                }
+                image
+                    .with_child(storage::Resource::from_identifier(&resource))
+                    .into()
+            }
+            Node::InlineCard { attrs } => Element::new("a")
+                .with_attribute("href", &attrs.url)
+                .with_text(&attrs.url)
+                .into(),
+            Node::Mention { attrs } => Element::new("ac:link")
+                .with_child(storage::Resource::from_identifier(
+                    &ResourceIdentifier::User {
+                        user_key: None,
+                        account_id: Some(attrs.id.clone()),
+                        username: None,
+                    },
+                ))
+                .into(),
+            Node::Emoji { attrs } => Element::empty("ac:emoticon")
+                .with_attribute("ac:name", attrs.short_name.trim_matches(':'))
+                .into(),
+            Node::Status { attrs } => {
+                let colour = match attrs.color.as_str() {
+                    "red" => StatusColour::Red,
+                    "yellow" => StatusColour::Yellow,
+                    "green" => StatusColour::Green,
+                    "blue" => StatusColour::Blue,
+                    "purple" => StatusColour::Purple,
+                    _ => StatusColour::Grey,
+                };
+                StatusMacro::new(&attrs.text, colour).into()
+            }
+            Node::Date { attrs } => {
+                let millis: i64 = attrs.timestamp.parse().unwrap_or(0);
+                let date = date_from_days(millis.div_euclid(86_400_000));
+                Element::empty("time")
+                    .with_attribute("datetime", &date)
+                    .into()
+            }
+            Node::Expand { attrs, content } => {
+                let mut m = ExpandMacro::new(self.blocks(content));
+                if let Some(title) = attrs.as_ref().and_then(|a| a.title.as_ref()) {
+                    m = m.title(title);
+                }
+                m.into()
+            }
+            Node::TaskList { content, .. } => self.element("ac:task-list", content),
+            Node::TaskItem { attrs, content } => {
+                self.task_id += 1;
+                let status = match attrs.state {
+                    TaskState::Done => "complete",
+                    TaskState::Todo => "incomplete",
+                };
+                let mut body = Element::new("ac:task-body");
+                body.children = self.blocks(content);
+                Element::new("ac:task")
+                    .with_child(Element::new("ac:task-id").with_text(&self.task_id.to_string()))
+                    .with_child(Element::new("ac:task-status").with_text(status))
+                    .with_child(body)
+                    .into()
+            }
+            Node::Extension { attrs } | Node::InlineExtension { attrs } => {
+                extension_macro(attrs).into()
+            }
+            Node::BodiedExtension { attrs, content } => {
+                let mut m = extension_macro(attrs);
+                m.set_rich_body(self.blocks(content));
+                m.into()
+            }
+            Node::Unknown(_) => return Vec::new(),
+        };
+        vec![node]
+    }
+}
+
+fn text_with_marks(text: &str, marks: &[Mark]) -> storage::Node {
+    let mut node = storage::Node::text(text);
+    for mark in marks.iter().rev() {
+        let e = match mark {
+            Mark::Strong => Element::new("strong"),
+            Mark::Em => Element::new("em"),
+            Mark::Code => Element::new("code"),
+            Mark::Strike => {
+                Element::new("span").with_attribute("style", "text-decoration: line-through;")
+            }
+            Mark::Underline => Element::new("u"),
+            Mark::Link { attrs } => Element::new("a").with_attribute("href", &attrs.href),
+            Mark::Subsup { attrs } => Element::new(match attrs.subsup_type {
+                SubsupType::Sub => "sub",
+                SubsupType::Sup => "sup",
+            }),
+            Mark::TextColor { attrs } => {
+                Element::new("span").with_attribute("style", &format!("color: {};", attrs.color))
+            }
+        };
+        node = e.with_child(node).into();
+    }
+    node
+}
+
+fn extension_macro(attrs: &ExtensionAttrs) -> storage::Macro {
+    let mut m = storage::Macro::named(&attrs.extension_key);
+    let params = attrs
+        .parameters
+        .as_ref()
+        .and_then(|p| p.get("macroParams"))
+        .and_then(|p| p.as_object());
+    if let Some(params) = params {
+        for (name, value) in params {
+            let value = match value.get("value") {
+                Some(Value::String(s)) => s.clone(),
+                Some(v) => v.to_string(),
+                None => continue,
+            };
+            m.set_parameter(name, &value);
+        }
+    }
+    m
+}
+
+/// Converts sibling storage nodes into ADF blocks, wrapping runs of inline
+/// content into paragraphs.
+fn blocks(nodes: &[storage::Node]) -> Vec<Node> {
+    let mut out = Vec::new();
+    let mut inline = Vec::new();
+    for node in nodes {
+        match block(node) {
+            Some(mut b) => {
+                flush(&mut inline, &mut out);
+                out.append(&mut b);
+            }
+            None => inline.extend(inlines(node, &[])),
+        }
+    }
+    flush(&mut inline, &mut out);
+    out
+}
+
+fn flush(inline: &mut Vec<Node>, out: &mut Vec<Node>) {
+    let content = tidy(std::mem::take(inline));
+    if !content.is_empty() {
+        out.push(Node::paragraph(content));
+    }
+}
+
+/// Blocks that must not be empty get an empty paragraph.
+fn non_empty(mut nodes: Vec<Node>) -> Vec<Node> {
+    if nodes.is_empty() {
+        nodes.push(Node::paragraph(Vec::new()));
+    }
+    nodes
+}
+
+/// Converts a block level storage node; `None` for inline nodes.
+fn block(node: &storage::Node) -> Option<Vec<Node>> {
+    let b = match node {
+        storage::Node::Macro(m) => {
+            let name = m.name().unwrap_or_default();
+            let rich = || match m.body() {
+                Some(MacroBody::Rich(nodes)) => blocks(nodes),
+                _ => Vec::new(),
+            };
+            match name.as_str() {
+                "status" | "anchor" => return None,
+                "code" | "noformat" => {
+                    let code = match m.body() {
+                        Some(MacroBody::Plain(text)) => text,
+                        _ => String::new(),
+                    };
+                    Node::code_block(m.parameter("language").as_deref(), &code)
+                }
+                "info" | "tip" | "note" | "warning" => {
+                    let panel_type = match name.as_str() {
+                        "info" => PanelType::Info,
+                        "tip" => PanelType::Success,
+                        "note" => PanelType::Warning,
+                        _ => PanelType::Error,
+                    };
+                    Node::panel(panel_type, non_empty(rich()))
+                }
+                "expand" => Node::Expand {
+                    attrs: m
+                        .parameter("title")
+                        .map(|title| ExpandAttrs { title: Some(title) }),
+                    content: non_empty(rich()),
+                },
+                _ => {
+                    let attrs = extension_attrs(m);
+                    match m.body() {
+                        Some(MacroBody::Rich(nodes)) => Node::BodiedExtension {
+                            attrs,
+                            content: non_empty(blocks(nodes)),
+                        },
+                        _ => Node::Extension { attrs },
+                    }
+                }
+            }
+        }
+        storage::Node::Layout(_)
+        | storage::Node::LayoutSection(_)
+        | storage::Node::LayoutCell(_) => {
+            return Some(blocks(&node.as_element().unwrap().children))
+        }
+        storage::Node::TaskList(list) => Node::TaskList {
+            attrs: LocalIdAttrs {
+                local_id: String::new(),
+            },
+            content: list
+                .tasks()
+                .map(|task| Node::TaskItem {
+                    attrs: TaskItemAttrs {
+                        local_id: task.id().unwrap_or_default(),
+                        state: match task.status() {
+                            storage::TaskStatus::Complete => TaskState::Done,
+                            storage::TaskStatus::Incomplete => TaskState::Todo,
+                        },
+                    },
+                    content: task
+                        .body()
+                        .map(|b| tidy(b.children.iter().flat_map(|c| inlines(c, &[])).collect()))
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        },
+        storage::Node::Image(image) => {
+            let attrs = match image.resource().map(|r| r.identifier()) {
+                Some(ResourceIdentifier::Url { value }) => MediaAttrs {
+                    media_type: MediaType::External,
+                    id: None,
+                    collection: None,
+                    url: Some(value),
+                    alt: image.alt(),
+                    width: None,
+                    height: None,
+                },
+                Some(ResourceIdentifier::Attachment { filename, .. }) => MediaAttrs {
+                    media_type: MediaType::File,
+                    id: None,
+                    collection: Some(String::new()),
+                    url: None,
+                    alt: Some(filename),
+                    width: image.width().and_then(|w| w.parse().ok()),
+                    height: image.height().and_then(|h| h.parse().ok()),
+                },
+                _ => return Some(Vec::new()),
+            };
+            Node::MediaSingle {
+                attrs: None,
+                content: vec![Node::Media { attrs }],
+            }
+        }
+        storage::Node::Element(e) => match e.name.as_str() {
+            "p" => Node::paragraph(tidy(
+                e.children.iter().flat_map(|c| inlines(c, &[])).collect(),
+            )),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Node::heading(
+                e.name[1..].parse().unwrap_or(1),
+                tidy(e.children.iter().flat_map(|c| inlines(c, &[])).collect()),
+            ),
+            "ul" | "ol" => {
+                let items = e
+                    .child_elements()
+                    .filter(|li| li.name == "li")
+                    .map(|li| {
+                        let mut content = blocks(&li.children);
+                        if !matches!(content.first(), Some(Node::Paragraph { .. })) {
+                            content.insert(0, Node::paragraph(Vec::new()));
+                        }
+                        Node::list_item(content)
+                    })
+                    .collect();
+                if e.name == "ul" {
+                    Node::bullet_list(items)
+                } else {
+                    let start = e
+                        .attribute("start")
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(1);
+                    Node::ordered_list(start, items)
+                }
+            }
+            "blockquote" => Node::Blockquote {
+                content: non_empty(blocks(&e.children)),
+            },
+            "pre" => Node::code_block(None, &e.text()),
+            "hr" => Node::Rule,
+            "table" => {
+                let rows = e
+                    .child_elements()
+                    .flat_map(|c| match c.name.as_str() {
+                        "tr" => vec![c],
+                        "thead" | "tbody" | "tfoot" => {
+                            c.child_elements().filter(|r| r.name == "tr").collect()
+                        }
+                        _ => vec![],
+                    })
+                    .map(|row| {
+                        Node::table_row(
+                            row.child_elements()
+                                .filter(|c| c.name == "th" || c.name == "td")
+                                .map(|c| {
+                                    let content = non_empty(blocks(&c.children));
+                                    if c.name == "th" {
+                                        Node::table_header(content)
+                                    } else {
+                                        Node::table_cell(content)
+                                    }
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect();
+                Node::table(rows)
+            }
+            "div" | "ac:rich-text-body" => return Some(blocks(&e.children)),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(vec![b])
+}
+
+fn extension_attrs(m: &storage::Macro) -> ExtensionAttrs {
+    let mut params = Map::new();
+    for (name, value) in m.parameters() {
+        params.insert(name, json!({ "value": value }));
+    }
+    ExtensionAttrs {
+        extension_type: MACRO_EXTENSION_TYPE.to_string(),
+        extension_key: m.name().unwrap_or_default(),
+        parameters: Some(json!({ "macroParams": params })),
+    }
+}
+
+/// Converts inline storage content, applying `marks` to all text.
+fn inlines(node: &storage::Node, marks: &[Mark]) -> Vec<Node> {
+    let with = |mark: Mark| {
+        let mut m = marks.to_vec();
+        if !m.contains(&mark) {
+            m.push(mark);
+        }
+        m
+    };
+    let children = |e: &Element, marks: &[Mark]| -> Vec<Node> {
+        e.children.iter().flat_map(|c| inlines(c, marks)).collect()
+    };
+    match node {
+        storage::Node::Text(raw) => {
+            vec![text(&collapse_whitespace(&storage::unescape(raw)), marks)]
+        }
+        storage::Node::CData(value) => vec![text(value, marks)],
+        storage::Node::Link(link) => match link.resource().map(|r| r.identifier()) {
+            Some(ResourceIdentifier::User {
+                account_id,
+                user_key,
+                username,
+            }) => vec![Node::Mention {
+                attrs: MentionAttrs {
+                    id: account_id.or(user_key).or(username).unwrap_or_default(),
+                    text: None,
+                },
+            }],
+            Some(ResourceIdentifier::Url { value }) => {
+                let text = link.text().unwrap_or_else(|| value.clone());
+                vec![Node::marked(
+                    &text,
+                    with(Mark::Link {
+                        attrs: LinkAttrs {
+                            href: value,
+                            title: None,
+                        },
+                    }),
+                )]
+            }
+            resource => {
+                let fallback = match resource {
+                    Some(ResourceIdentifier::Page { content_title, .. }) => content_title,
+                    Some(ResourceIdentifier::Attachment { filename, .. }) => filename,
+                    _ => String::new(),
+                };
+                vec![text(&link.text().unwrap_or(fallback), marks)]
+            }
+        },
+        storage::Node::Macro(m) if m.name().as_deref() == Some("status") => {
+            let color = match m.parameter("colour").unwrap_or_default().as_str() {
+                "Red" => "red",
+                "Yellow" => "yellow",
+                "Green" => "green",
+                "Blue" => "blue",
+                "Purple" => "purple",
+                _ => "neutral",
+            };
+            vec![Node::Status {
+                attrs: StatusAttrs {
+                    text: m.parameter("title").unwrap_or_default(),
+                    color: color.to_string(),
+                    local_id: None,
+                },
+            }]
+        }
+        storage::Node::Macro(m) => vec![Node::InlineExtension {
+            attrs: extension_attrs(m),
+        }],
+        storage::Node::Element(e) => match e.name.as_str() {
+            "strong" | "b" => children(e, &with(Mark::Strong)),
+            "em" | "i" => children(e, &with(Mark::Em)),
+            "code" => children(e, &with(Mark::Code)),
+            "s" | "del" => children(e, &with(Mark::Strike)),
+            "u" => children(e, &with(Mark::Underline)),
+            "sub" | "sup" => children(
+                e,
+                &with(Mark::Subsup {
+                    attrs: SubsupAttrs {
+                        subsup_type: if e.name == "sub" {
+                            SubsupType::Sub
+                        } else {
+                            SubsupType::Sup
+                        },
+                    },
+                }),
+            ),
+            "a" => match e.attribute("href") {
+                Some(href) => children(
+                    e,
+                    &with(Mark::Link {
+                        attrs: LinkAttrs { href, title: None },
+                    }),
+                ),
+                None => children(e, marks),
+            },
+            "span" => {
+                let style = e.attribute("style").unwrap_or_default();
+                if style.contains("line-through") {
+                    children(e, &with(Mark::Strike))
+                } else if let Some(color) = style_color(&style) {
+                    children(
+                        e,
+                        &with(Mark::TextColor {
+                            attrs: ColorAttrs { color },
+                        }),
+                    )
+                } else {
+                    children(e, marks)
+                }
+            }
+            "br" => vec![Node::HardBreak],
+            "ac:emoticon" => vec![Node::Emoji {
+                attrs: EmojiAttrs {
+                    short_name: format!(":{}:", e.attribute("ac:name").unwrap_or_default()),
+                    text: None,
+                },
+            }],
+            "time" => match e.attribute("datetime").and_then(|d| days_from_date(&d)) {
+                Some(days) => vec![Node::Date {
+                    attrs: DateAttrs {
+                        timestamp: (days * 86_400_000).to_string(),
+                    },
+                }],
+                None => Vec::new(),
+            },
+            "ac:placeholder" => Vec::new(),
+            _ => children(e, marks),
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn style_color(style: &str) -> Option<String> {
+    style
+        .split(';')
+        .filter_map(|decl| decl.split_once(':'))
+        .find(|(k, _)| k.trim() == "color")
+        .map(|(_, v)| v.trim().to_string())
+}
+
+/// A text node. ADF doesn't allow formatting marks next to `code`, so those
+/// are dropped from code text.
+fn text(text: &str, marks: &[Mark]) -> Node {
+    let marks = if marks.contains(&Mark::Code) {
+        marks
+            .iter()
+            .filter(|m| matches!(m, Mark::Code | Mark::Link { .. }))
+            .cloned()
+            .collect()
+    } else {
+        marks.to_vec()
+    };
+    Node::marked(text, marks)
+}
+
+/// Merges adjacent text with the same marks, trims the ends of the run and
+/// drops empty text, which ADF rejects.
+fn tidy(nodes: Vec<Node>) -> Vec<Node> {
+    let mut out: Vec<Node> = Vec::new();
+    for node in nodes {
+        if let (
+            Some(Node::Text {
+                text: prev,
+                marks: pm,
+            }),
+            Node::Text { text, marks },
+        ) = (out.last_mut(), &node)
+        {
+            if pm == marks {
+                prev.push_str(text);
+                continue;
+            }
+        }
+        out.push(node);
+    }
+    if let Some(Node::Text { text, .. }) = out.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some(Node::Text { text, .. }) = out.last_mut() {
+        *text = text.trim_end().to_string();
+    }
+    out.retain(|n| !matches!(n, Node::Text { text, .. } if text.is_empty()));
+    out
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+        } else {
+            out.push(c);
+            space = false;
+        }
+    }
+    out
+}
+
+/// `YYYY-MM-DD` for a number of days since 1970-01-01.
+fn date_from_days(days: i64) -> String {
+    let (y, m, d) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Days since 1970-01-01 for a `YYYY-MM-DD` date.
+fn days_from_date(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.split('-');
+    let y: i64 = parts.next()?.parse().ok()?;
+    let m: i64 = parts.next()?.parse().ok()?;
+    let d: i64 = parts.next()?.parse().ok()?;
+    Some(days_from_civil(y, m, d))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adf::validate;
+
+    #[test]
+    fn storage_to_adf_and_back() {
+        let storage = "<h2>Plan</h2><p>Ship <strong>now</strong> or <a href=\"https://example.com\">later</a>.</p>\
+                       <ul><li>one</li><li><p>two</p><ul><li>nested</li></ul></li></ul>\
+                       <ac:structured-macro ac:name=\"info\" ac:schema-version=\"1\"><ac:rich-text-body>\
+                       <p>Heads up</p></ac:rich-text-body></ac:structured-macro>\
+                       <ac:structured-macro ac:name=\"code\" ac:schema-version=\"1\">\
+                       <ac:parameter ac:name=\"language\">rust</ac:parameter>\
+                       <ac:plain-text-body><![CDATA[fn main() {}]]></ac:plain-text-body></ac:structured-macro>\
+                       <p>Due <time datetime=\"2024-03-01\" /></p>";
+        let doc = from_storage(storage).unwrap();
+        validate(&doc).unwrap();
+        assert_eq!(doc.content[0], Node::heading(2, vec![Node::text("Plan")]));
+        assert_eq!(
+            doc.content[4],
+            Node::code_block(Some("rust"), "fn main() {}")
+        );
+        assert_eq!(
+            doc.content[5],
+            Node::paragraph(vec![
+                Node::text("Due "),
+                Node::Date {
+                    attrs: DateAttrs {
+                        timestamp: "1709251200000".to_string()
+                    }
+                }
+            ])
+        );
+
+        let back = to_storage(&doc);
+        assert_eq!(
+            back,
+            "<h2>Plan</h2><p>Ship <strong>now</strong> or <a href=\"https://example.com\">later</a>.</p>\
+             <ul><li>one</li><li><p>two</p><ul><li>nested</li></ul></li></ul>\
+             <ac:structured-macro ac:name=\"info\" ac:schema-version=\"1\"><ac:rich-text-body>\
+             <p>Heads up</p></ac:rich-text-body></ac:structured-macro>\
+             <ac:structured-macro ac:name=\"code\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"language\">rust</ac:parameter>\
+             <ac:plain-text-body><![CDATA[fn main() {}]]></ac:plain-text-body></ac:structured-macro>\
+             <p>Due <time datetime=\"2024-03-01\" /></p>"
+        );
+    }
+
+    #[test]
+    fn unknown_macros_become_extensions() {
+        let doc = from_storage(
+            "<ac:structured-macro ac:name=\"toc\"><ac:parameter ac:name=\"maxLevel\">2</ac:parameter>\
+             </ac:structured-macro>",
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&doc.content[0]).unwrap(),
+            json!({
+                "type": "extension",
+                "attrs": {
+                    "extensionType": "com.atlassian.confluence.macro.core",
+                    "extensionKey": "toc",
+                    "parameters": { "macroParams": { "maxLevel": { "value": "2" } } }
+                }
+            })
+        );
+        assert_eq!(
+            to_storage(&doc),
+            "<ac:structured-macro ac:name=\"toc\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"maxLevel\">2</ac:parameter></ac:structured-macro>"
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(19_783), "2024-03-01");
+        assert_eq!(days_from_date("1999-12-31"), Some(10_956));
+    }
+}
@@ -0,0 +1,21 @@
+//! Atlassian Document Format.
+//!
+//! ADF is the JSON document model Confluence Cloud's editor works in
+//! (`representation: "atlas_doc_format"`). [`Document`] deserializes any ADF
+//! body, keeping node types this crate doesn't model as [`Node::Unknown`];
+//! [`DocumentBuilder`] builds one checked against the schema, and
+//! [`to_storage`] / [`from_storage`] convert the common nodes to and from the
+//! storage format.
+//!
+//! ```
+//! let doc = confluence::adf::from_storage("<p>Hello <em>world</em></p>").unwrap();
+//! assert_eq!(confluence::adf::to_storage(&doc), "<p>Hello <em>world</em></p>");
+//! ```
+
+mod builder;
+mod convert;
+pub mod model;
+
+pub use builder::*;
+pub use convert::*;
+pub use model::*;
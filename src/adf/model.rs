@@ -0,0 +1,479 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+/// An Atlassian Document Format document, the `atlas_doc_format`
+/// representation of a body.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Document {
+    pub version: u32,
+    #[serde(rename = "type")]
+    pub doc_type: String,
+    #[serde(default)]
+    pub content: Vec<Node>,
+}
+
+impl Document {
+    pub fn new(content: Vec<Node>) -> Self {
+        Self {
+            version: 1,
+            doc_type: "doc".to_string(),
+            content,
+        }
+    }
+}
+
+/// Node types this crate models. Nodes of other types deserialize into
+/// `Node::Unknown` and serialize back unchanged.
+const KNOWN_NODES: &[&str] = &[
+    "paragraph",
+    "text",
+    "heading",
+    "bulletList",
+    "orderedList",
+    "listItem",
+    "codeBlock",
+    "blockquote",
+    "rule",
+    "hardBreak",
+    "panel",
+    "table",
+    "tableRow",
+    "tableHeader",
+    "tableCell",
+    "mediaSingle",
+    "media",
+    "inlineCard",
+    "mention",
+    "emoji",
+    "status",
+    "date",
+    "expand",
+    "taskList",
+    "taskItem",
+    "extension",
+    "bodiedExtension",
+    "inlineExtension",
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(remote = "Self", tag = "type", rename_all = "camelCase")]
+pub enum Node {
+    Paragraph {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        content: Vec<Node>,
+    },
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        marks: Vec<Mark>,
+    },
+    Heading {
+        attrs: HeadingAttrs,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        content: Vec<Node>,
+    },
+    BulletList {
+        content: Vec<Node>,
+    },
+    OrderedList {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<OrderedListAttrs>,
+        content: Vec<Node>,
+    },
+    ListItem {
+        content: Vec<Node>,
+    },
+    CodeBlock {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<CodeBlockAttrs>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        content: Vec<Node>,
+    },
+    Blockquote {
+        content: Vec<Node>,
+    },
+    Rule,
+    HardBreak,
+    Panel {
+        attrs: PanelAttrs,
+        content: Vec<Node>,
+    },
+    Table {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<TableAttrs>,
+        content: Vec<Node>,
+    },
+    TableRow {
+        content: Vec<Node>,
+    },
+    TableHeader {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<TableCellAttrs>,
+        content: Vec<Node>,
+    },
+    TableCell {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<TableCellAttrs>,
+        content: Vec<Node>,
+    },
+    MediaSingle {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<MediaSingleAttrs>,
+        content: Vec<Node>,
+    },
+    Media {
+        attrs: MediaAttrs,
+    },
+    InlineCard {
+        attrs: CardAttrs,
+    },
+    Mention {
+        attrs: MentionAttrs,
+    },
+    Emoji {
+        attrs: EmojiAttrs,
+    },
+    Status {
+        attrs: StatusAttrs,
+    },
+    Date {
+        attrs: DateAttrs,
+    },
+    Expand {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attrs: Option<ExpandAttrs>,
+        content: Vec<Node>,
+    },
+    TaskList {
+        attrs: LocalIdAttrs,
+        content: Vec<Node>,
+    },
+    TaskItem {
+        attrs: TaskItemAttrs,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        content: Vec<Node>,
+    },
+    Extension {
+        attrs: ExtensionAttrs,
+    },
+    BodiedExtension {
+        attrs: ExtensionAttrs,
+        content: Vec<Node>,
+    },
+    InlineExtension {
+        attrs: ExtensionAttrs,
+    },
+    /// A node of a type this crate doesn't model, kept as is.
+    #[serde(skip)]
+    Unknown(Value),
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Node::Unknown(value) => value.serialize(serializer),
+            _ => Node::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let known = value
+            .get("type")
+            .and_then(|t| t.as_str())
+            .map(|t| KNOWN_NODES.contains(&t))
+            .unwrap_or(false);
+        if known {
+            Node::deserialize(value).map_err(serde::de::Error::custom)
+        } else {
+            Ok(Node::Unknown(value))
+        }
+    }
+}
+
+impl Node {
+    /// The ADF type name, e.g. `bulletList`.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Node::Paragraph { .. } => "paragraph",
+            Node::Text { .. } => "text",
+            Node::Heading { .. } => "heading",
+            Node::BulletList { .. } => "bulletList",
+            Node::OrderedList { .. } => "orderedList",
+            Node::ListItem { .. } => "listItem",
+            Node::CodeBlock { .. } => "codeBlock",
+            Node::Blockquote { .. } => "blockquote",
+            Node::Rule => "rule",
+            Node::HardBreak => "hardBreak",
+            Node::Panel { .. } => "panel",
+            Node::Table { .. } => "table",
+            Node::TableRow { .. } => "tableRow",
+            Node::TableHeader { .. } => "tableHeader",
+            Node::TableCell { .. } => "tableCell",
+            Node::MediaSingle { .. } => "mediaSingle",
+            Node::Media { .. } => "media",
+            Node::InlineCard { .. } => "inlineCard",
+            Node::Mention { .. } => "mention",
+            Node::Emoji { .. } => "emoji",
+            Node::Status { .. } => "status",
+            Node::Date { .. } => "date",
+            Node::Expand { .. } => "expand",
+            Node::TaskList { .. } => "taskList",
+            Node::TaskItem { .. } => "taskItem",
+            Node::Extension { .. } => "extension",
+            Node::BodiedExtension { .. } => "bodiedExtension",
+            Node::InlineExtension { .. } => "inlineExtension",
+            Node::Unknown(value) => value.get("type").and_then(|t| t.as_str()).unwrap_or(""),
+        }
+    }
+
+    /// Child nodes, empty for leaf nodes.
+    pub fn content(&self) -> &[Node] {
+        match self {
+            Node::Paragraph { content }
+            | Node::Heading { content, .. }
+            | Node::BulletList { content }
+            | Node::OrderedList { content, .. }
+            | Node::ListItem { content }
+            | Node::CodeBlock { content, .. }
+            | Node::Blockquote { content }
+            | Node::Panel { content, .. }
+            | Node::Table { content, .. }
+            | Node::TableRow { content }
+            | Node::TableHeader { content, .. }
+            | Node::TableCell { content, .. }
+            | Node::MediaSingle { content, .. }
+            | Node::Expand { content, .. }
+            | Node::TaskList { content, .. }
+            | Node::TaskItem { content, .. }
+            | Node::BodiedExtension { content, .. } => content,
+            _ => &[],
+        }
+    }
+
+    /// Whether the node is an inline node, i.e. may appear in a paragraph.
+    pub fn is_inline(&self) -> bool {
+        matches!(
+            self,
+            Node::Text { .. }
+                | Node::HardBreak
+                | Node::InlineCard { .. }
+                | Node::Mention { .. }
+                | Node::Emoji { .. }
+                | Node::Status { .. }
+                | Node::Date { .. }
+                | Node::InlineExtension { .. }
+        )
+    }
+
+    /// The concatenated text of this node and its descendants.
+    pub fn text_content(&self) -> String {
+        match self {
+            Node::Text { text, .. } => text.clone(),
+            _ => self.content().iter().map(|n| n.text_content()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Mark {
+    Strong,
+    Em,
+    Code,
+    Strike,
+    Underline,
+    Link { attrs: LinkAttrs },
+    Subsup { attrs: SubsupAttrs },
+    TextColor { attrs: ColorAttrs },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HeadingAttrs {
+    pub level: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OrderedListAttrs {
+    pub order: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CodeBlockAttrs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PanelType {
+    Info,
+    Note,
+    Warning,
+    Success,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PanelAttrs {
+    #[serde(rename = "panelType")]
+    pub panel_type: PanelType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TableAttrs {
+    #[serde(
+        rename = "isNumberColumnEnabled",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub is_number_column_enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TableCellAttrs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colspan: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rowspan: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colwidth: Option<Vec<u32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct MediaSingleAttrs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    File,
+    Link,
+    External,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MediaAttrs {
+    #[serde(rename = "type")]
+    pub media_type: MediaType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CardAttrs {
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MentionAttrs {
+    /// The `accountId` of the mentioned user.
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EmojiAttrs {
+    #[serde(rename = "shortName")]
+    pub short_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StatusAttrs {
+    pub text: String,
+    /// `neutral`, `purple`, `blue`, `red`, `yellow` or `green`.
+    pub color: String,
+    #[serde(rename = "localId", default, skip_serializing_if = "Option::is_none")]
+    pub local_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DateAttrs {
+    /// Milliseconds since the epoch, as a string.
+    pub timestamp: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct ExpandAttrs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LocalIdAttrs {
+    #[serde(rename = "localId")]
+    pub local_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TaskState {
+    Todo,
+    Done,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TaskItemAttrs {
+    #[serde(rename = "localId")]
+    pub local_id: String,
+    pub state: TaskState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExtensionAttrs {
+    #[serde(rename = "extensionType")]
+    pub extension_type: String,
+    #[serde(rename = "extensionKey")]
+    pub extension_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkAttrs {
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubsupType {
+    Sub,
+    Sup,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SubsupAttrs {
+    #[serde(rename = "type")]
+    pub subsup_type: SubsupType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ColorAttrs {
+    /// Hex colour, e.g. `#ff5630`.
+    pub color: String,
+}
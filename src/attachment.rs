@@ -0,0 +1,643 @@
+//! Uploading attachments only when their bytes changed.
+//!
+//! [`AttachmentSync::ensure`] finds an attachment of a page by file name
+//! and compares its size and the SHA-256 hash stored with it to the data
+//! to upload. Only new or changed files are uploaded, so publishing the
+//! same files again creates no new attachment versions. The hash is kept
+//! in the attachment's comment as `sha256:<hex>`, or in a page property
+//! mapping file names to hashes. An attachment without a stored hash is
+//! downloaded and compared once.
+//!
+//! ```no_run
+//! use confluence::attachment::{AttachmentSync, Outcome};
+//! use confluence::client::Confluence;
+//!
+//! let confluence = Confluence::new("https://wiki.example.com/rest/api");
+//! let diagram = std::fs::read("architecture.png").unwrap();
+//! let ensured = AttachmentSync::new(&confluence, "123456")
+//!     .ensure("architecture.png", diagram, "image/png")
+//!     .unwrap();
+//! if ensured.outcome == Outcome::Unchanged {
+//!     println!("{} is up to date", ensured.id);
+//! }
+//! ```
+//!
+//! Large files are uploaded with [`AttachmentUpload`], which streams them
+//! from disk or any reader instead of reading them into memory and reports
+//! progress. Every upload, including those of [`AttachmentSync`], refuses
+//! files over the instance's maximum attachment size before sending them:
+//!
+//! ```no_run
+//! use confluence::attachment::{AttachmentTooLarge, AttachmentUpload};
+//! use confluence::client::Confluence;
+//!
+//! let confluence = Confluence::new("https://wiki.example.com/rest/api")
+//!     .with_max_attachment_size(2 * 1024 * 1024 * 1024);
+//! let upload = AttachmentUpload::from_file(&confluence, "123456", "recording.mp4")
+//!     .unwrap()
+//!     .with_media_type("video/mp4")
+//!     .with_progress(|sent, total| eprint!("\r{}/{} bytes", sent, total));
+//! match upload.create() {
+//!     Ok(attachment) => println!("uploaded {}", attachment.id),
+//!     Err(e) if e.is::<AttachmentTooLarge>() => eprintln!("{}", e),
+//!     Err(e) => panic!("{}", e),
+//! }
+//! ```
+
+use crate::client::{Confluence, Executor, Result};
+use crate::content::model::{Content, Results};
+use crate::util::parse;
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Prefix of the hash in an attachment comment.
+pub const HASH_PREFIX: &str = "sha256:";
+
+/// Where the content hash of an attachment is kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HashStore {
+    /// In the attachment's comment.
+    Comment,
+    /// In the page property with this key, an object of hashes by file
+    /// name.
+    PageProperty(String),
+}
+
+/// What [`AttachmentSync::ensure`] did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnsuredAttachment {
+    pub id: String,
+    pub version: u64,
+    pub outcome: Outcome,
+}
+
+pub struct AttachmentSync {
+    confluence: Confluence,
+    page_id: String,
+    store: HashStore,
+}
+
+impl AttachmentSync {
+    /// Keeps the attachments of `page_id` in sync, with hashes in their
+    /// comments.
+    pub fn new(confluence: &Confluence, page_id: &str) -> Self {
+        Self {
+            confluence: confluence.clone(),
+            page_id: page_id.to_string(),
+            store: HashStore::Comment,
+        }
+    }
+
+    pub fn with_hash_store(mut self, store: HashStore) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Uploads `data` as `filename` unless the attachment already has these
+    /// bytes.
+    pub fn ensure(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        media_type: &str,
+    ) -> Result<EnsuredAttachment> {
+        let hash = hash(&data);
+        let (_, status, d) = self
+            .confluence
+            .get()
+            .content()
+            .content_id(&self.page_id)
+            .child()
+            .attachment()
+            .filename(filename)
+            .execute::<Results<Content>>()?;
+        let existing = parse(status, d, "find attachment")?
+            .results
+            .into_iter()
+            .find(|a| a.title == filename);
+
+        let existing = match existing {
+            Some(existing) => existing,
+            None => {
+                let form = self.form(filename, data, media_type, &hash)?;
+                let (_, status, d) = self
+                    .confluence
+                    .post(())
+                    .content()
+                    .content_id(&self.page_id)
+                    .child()
+                    .attachment(form)
+                    .execute::<Results<Content>>()?;
+                let created = parse(status, d, "upload attachment")?
+                    .results
+                    .into_iter()
+                    .next()
+                    .ok_or("Unable to upload attachment: empty response")?;
+                self.store_hash(filename, &hash)?;
+                return Ok(ensured(&created, Outcome::Created));
+            }
+        };
+
+        let extensions = existing.extensions.as_ref();
+        let size = extensions.and_then(|e| e.file_size);
+        if size == Some(data.len() as u64) {
+            let unchanged = match self.stored_hash(&existing, filename)? {
+                Some(stored) => stored == hash,
+                None => {
+                    let same = match existing.links.download.as_deref() {
+                        Some(download) => self.confluence.download(download)? == data,
+                        None => false,
+                    };
+                    if same {
+                        self.store_hash(filename, &hash)?;
+                    }
+                    same
+                }
+            };
+            if unchanged {
+                return Ok(ensured(&existing, Outcome::Unchanged));
+            }
+        }
+
+        let form = self.form(filename, data, media_type, &hash)?;
+        let (_, status, d) = self
+            .confluence
+            .post(())
+            .content()
+            .content_id(&self.page_id)
+            .child()
+            .attachment(form)
+            .attachment_id(&existing.id)
+            .data()
+            .execute::<Content>()?;
+        let updated = parse(status, d, "update attachment")?;
+        self.store_hash(filename, &hash)?;
+        Ok(ensured(&updated, Outcome::Updated))
+    }
+
+    fn form(&self, filename: &str, data: Vec<u8>, media_type: &str, hash: &str) -> Result<Form> {
+        let part = file_part(&self.confluence, filename, data, media_type)?;
+        let form = Form::new().part("file", part).text("minorEdit", "true");
+        Ok(match self.store {
+            HashStore::Comment => form.text("comment", format!("{}{}", HASH_PREFIX, hash)),
+            HashStore::PageProperty(_) => form,
+        })
+    }
+
+    fn stored_hash(&self, attachment: &Content, filename: &str) -> Result<Option<String>> {
+        match &self.store {
+            HashStore::Comment => Ok(attachment
+                .extensions
+                .as_ref()
+                .and_then(|e| e.comment.as_deref())
+                .and_then(|c| c.strip_prefix(HASH_PREFIX))
+                .map(str::to_string)),
+            HashStore::PageProperty(key) => {
+                let (_, value) = self.property(key)?;
+                Ok(value[filename].as_str().map(str::to_string))
+            }
+        }
+    }
+
+    fn store_hash(&self, filename: &str, hash: &str) -> Result<()> {
+        let key = match &self.store {
+            HashStore::Comment => return Ok(()),
+            HashStore::PageProperty(key) => key,
+        };
+        let (version, mut value) = self.property(key)?;
+        if !value.is_object() {
+            value = json!({});
+        }
+        value[filename] = Value::String(hash.to_string());
+        let endpoint = format!("content/{}/property/{}", self.page_id, key);
+        let (_, status, d) = match version {
+            Some(version) => self
+                .confluence
+                .put(json!({ "key": key, "value": value, "version": { "number": version + 1 } }))
+                .custom_endpoint(&endpoint)
+                .execute::<Value>()?,
+            None => self
+                .confluence
+                .post(json!({ "key": key, "value": value }))
+                .custom_endpoint(&format!("content/{}/property", self.page_id))
+                .execute::<Value>()?,
+        };
+        parse(status, d, "store attachment hash").map(|_| ())
+    }
+
+    /// The version and value of a page property, if it is set.
+    fn property(&self, key: &str) -> Result<(Option<u64>, Value)> {
+        let (_, status, d) = self
+            .confluence
+            .get()
+            .custom_endpoint(&format!("content/{}/property/{}", self.page_id, key))
+            .execute::<Value>()?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok((None, Value::Null));
+        }
+        let property = parse(status, d, "get property")?;
+        Ok((
+            property["version"]["number"].as_u64(),
+            property["value"].clone(),
+        ))
+    }
+}
+
+/// The error of an upload larger than the instance accepts, returned
+/// before any request is sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttachmentTooLarge {
+    pub filename: String,
+    pub size: u64,
+    pub max_size: u64,
+}
+
+impl fmt::Display for AttachmentTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unable to upload {}: {} bytes exceed the maximum attachment size of {} bytes",
+            self.filename, self.size, self.max_size
+        )
+    }
+}
+
+impl std::error::Error for AttachmentTooLarge {}
+
+type Progress = Box<dyn FnMut(u64, u64) + Send>;
+
+/// An attachment uploaded as it is read, without holding it in memory.
+pub struct AttachmentUpload {
+    confluence: Confluence,
+    page_id: String,
+    filename: String,
+    media_type: String,
+    comment: Option<String>,
+    reader: Box<dyn Read + Send>,
+    size: u64,
+    progress: Option<Progress>,
+}
+
+impl AttachmentUpload {
+    /// Uploads the file at `path` to `page_id`, named after the file.
+    pub fn from_file(
+        confluence: &Confluence,
+        page_id: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("Unable to name attachment {}", path.display()))?;
+        let file =
+            File::open(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let size = file
+            .metadata()
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?
+            .len();
+        Ok(Self::from_reader(
+            confluence, page_id, &filename, file, size,
+        ))
+    }
+
+    /// Uploads `size` bytes read from `reader` to `page_id` as `filename`.
+    pub fn from_reader(
+        confluence: &Confluence,
+        page_id: &str,
+        filename: &str,
+        reader: impl Read + Send + 'static,
+        size: u64,
+    ) -> Self {
+        Self {
+            confluence: confluence.clone(),
+            page_id: page_id.to_string(),
+            filename: filename.to_string(),
+            media_type: "application/octet-stream".to_string(),
+            comment: None,
+            reader: Box::new(reader),
+            size,
+            progress: None,
+        }
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> Self {
+        self.filename = filename.to_string();
+        self
+    }
+
+    /// Defaults to `application/octet-stream`.
+    pub fn with_media_type(mut self, media_type: &str) -> Self {
+        self.media_type = media_type.to_string();
+        self
+    }
+
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.comment = Some(comment.to_string());
+        self
+    }
+
+    /// Calls `progress` with the bytes sent so far and the size as the
+    /// file is read.
+    pub fn with_progress(mut self, progress: impl FnMut(u64, u64) + Send + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Uploads a new attachment.
+    pub fn create(self) -> Result<Content> {
+        let confluence = self.confluence.clone();
+        let page_id = self.page_id.clone();
+        let form = self.form()?;
+        let (_, status, d) = confluence
+            .post(())
+            .content()
+            .content_id(&page_id)
+            .child()
+            .attachment(form)
+            .execute::<Results<Content>>()?;
+        parse(status, d, "upload attachment")?
+            .results
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Unable to upload attachment: empty response".into())
+    }
+
+    /// Uploads a new version of the attachment `attachment_id`.
+    pub fn replace(self, attachment_id: &str) -> Result<Content> {
+        let confluence = self.confluence.clone();
+        let page_id = self.page_id.clone();
+        let form = self.form()?;
+        let (_, status, d) = confluence
+            .post(())
+            .content()
+            .content_id(&page_id)
+            .child()
+            .attachment(form)
+            .attachment_id(attachment_id)
+            .data()
+            .execute::<Content>()?;
+        parse(status, d, "update attachment")
+    }
+
+    fn form(self) -> Result<Form> {
+        check_size(&self.confluence, &self.filename, self.size)?;
+        let reader = ProgressReader {
+            inner: self.reader.take(self.size),
+            sent: 0,
+            size: self.size,
+            progress: self.progress,
+        };
+        let part = Part::reader_with_length(reader, self.size)
+            .file_name(self.filename)
+            .mime_str(&self.media_type)?;
+        let form = Form::new().part("file", part);
+        Ok(match self.comment {
+            Some(comment) => form.text("comment", comment),
+            None => form,
+        })
+    }
+}
+
+/// Fails with [`AttachmentTooLarge`] if `size` bytes exceed the maximum
+/// attachment size of `confluence`.
+pub(crate) fn check_size(confluence: &Confluence, filename: &str, size: u64) -> Result<()> {
+    let max_size = confluence.max_attachment_size();
+    if size > max_size {
+        return Err(Box::new(AttachmentTooLarge {
+            filename: filename.to_string(),
+            size,
+            max_size,
+        }));
+    }
+    Ok(())
+}
+
+/// The `file` part of an upload of `data`, which every upload of bytes
+/// already in memory goes through.
+pub(crate) fn file_part(
+    confluence: &Confluence,
+    filename: &str,
+    data: Vec<u8>,
+    media_type: &str,
+) -> Result<Part> {
+    check_size(confluence, filename, data.len() as u64)?;
+    Ok(Part::bytes(data)
+        .file_name(filename.to_string())
+        .mime_str(media_type)?)
+}
+
+/// Reports the bytes read through it.
+struct ProgressReader {
+    inner: std::io::Take<Box<dyn Read + Send>>,
+    sent: u64,
+    size: u64,
+    progress: Option<Progress>,
+}
+
+impl Read for ProgressReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 {
+            self.sent += read as u64;
+            if let Some(progress) = self.progress.as_mut() {
+                progress(self.sent, self.size);
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// The hex encoded SHA-256 hash of `data`.
+pub fn hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn ensured(attachment: &Content, outcome: Outcome) -> EnsuredAttachment {
+    EnsuredAttachment {
+        id: attachment.id.clone(),
+        version: attachment.version.as_ref().map_or(1, |v| v.number),
+        outcome,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deployment::{ContentApi, Deployment, DeploymentClient};
+    use crate::fake::FakeConfluence;
+
+    #[test]
+    fn uploads_only_changed_bytes() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let page = server.create_page("DOC", "Home", None);
+        let sync = AttachmentSync::new(&server.client(), &page);
+        let ensure = |data: &[u8]| sync.ensure("a.png", data.to_vec(), "image/png").unwrap();
+
+        let created = ensure(b"one");
+        assert_eq!((created.outcome, created.version), (Outcome::Created, 1));
+        assert_eq!(ensure(b"one").outcome, Outcome::Unchanged);
+        // Same size, other bytes.
+        let updated = ensure(b"two");
+        assert_eq!((updated.outcome, updated.version), (Outcome::Updated, 2));
+        assert_eq!(updated.id, created.id);
+        assert_eq!(ensure(b"two").outcome, Outcome::Unchanged);
+        let stored = server.content(&created.id).unwrap();
+        assert_eq!(stored.comment, Some(format!("sha256:{}", hash(b"two"))));
+    }
+
+    #[test]
+    fn hashes_can_be_kept_in_a_page_property() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let page = server.create_page("DOC", "Home", None);
+        let sync = AttachmentSync::new(&server.client(), &page)
+            .with_hash_store(HashStore::PageProperty("attachment-hashes".to_string()));
+        // Uploaded before, without a hash: compared by downloading it.
+        AttachmentSync::new(&server.client(), &page)
+            .ensure("a.txt", b"one".to_vec(), "text/plain")
+            .unwrap();
+        let ensure = |name: &str, data: &[u8]| {
+            sync.ensure(name, data.to_vec(), "text/plain")
+                .unwrap()
+                .outcome
+        };
+        assert_eq!(ensure("a.txt", b"one"), Outcome::Unchanged);
+        assert_eq!(ensure("a.txt", b"uno"), Outcome::Updated);
+        assert_eq!(ensure("b.txt", b"two"), Outcome::Created);
+        assert_eq!(ensure("a.txt", b"uno"), Outcome::Unchanged);
+
+        let properties = server.content(&page).unwrap().properties;
+        assert_eq!(
+            properties[0].value,
+            json!({ "a.txt": hash(b"uno"), "b.txt": hash(b"two") })
+        );
+    }
+
+    #[test]
+    fn streams_uploads_and_reports_progress() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let page = server.create_page("DOC", "Home", None);
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress = reported.clone();
+        let created = AttachmentUpload::from_reader(
+            &server.client(),
+            &page,
+            "big.bin",
+            std::io::Cursor::new(data.clone()),
+            data.len() as u64,
+        )
+        .with_progress(move |sent, total| progress.lock().unwrap().push((sent, total)))
+        .create()
+        .unwrap();
+
+        let reported = reported.lock().unwrap();
+        assert!(reported.len() > 1);
+        assert!(reported.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(reported.last(), Some(&(200_000, 200_000)));
+        let stored = server.content(&created.id).unwrap();
+        assert_eq!(stored.data, data);
+
+        let replaced = AttachmentUpload::from_reader(
+            &server.client(),
+            &page,
+            "big.bin",
+            std::io::Cursor::new(b"small".to_vec()),
+            5,
+        )
+        .with_media_type("text/plain")
+        .replace(&created.id)
+        .unwrap();
+        assert_eq!(replaced.version.map(|v| v.number), Some(2));
+    }
+
+    /// Hands out `chunks` chunks of ten bytes, 100ms apart.
+    struct SlowReader {
+        chunks: usize,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.chunks == 0 {
+                return Ok(0);
+            }
+            self.chunks -= 1;
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            let n = buf.len().min(10);
+            buf[..n].fill(b'x');
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn uploads_outlast_the_request_timeout() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let page = server.create_page("DOC", "Home", None);
+        let timeout = Some(std::time::Duration::from_millis(200));
+        let confluence = server.client().with_timeout(timeout);
+        let upload = |confluence: &Confluence| {
+            AttachmentUpload::from_reader(
+                confluence,
+                &page,
+                "slow.bin",
+                SlowReader { chunks: 4 },
+                40,
+            )
+            .create()
+        };
+        let created = upload(&confluence).unwrap();
+        assert_eq!(server.content(&created.id).unwrap().data.len(), 40);
+
+        assert!(upload(&confluence.with_upload_timeout(timeout)).is_err());
+    }
+
+    #[test]
+    fn refuses_uploads_over_the_maximum_size_before_sending() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let page = server.create_page("DOC", "Home", None);
+        let confluence = server.client().with_max_attachment_size(4);
+        let error = AttachmentUpload::from_reader(
+            &confluence,
+            &page,
+            "big.bin",
+            std::io::Cursor::new(b"12345".to_vec()),
+            5,
+        )
+        .create()
+        .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<AttachmentTooLarge>(),
+            Some(&AttachmentTooLarge {
+                filename: "big.bin".to_string(),
+                size: 5,
+                max_size: 4,
+            })
+        );
+
+        let error = AttachmentSync::new(&confluence, &page)
+            .ensure("big.bin", b"12345".to_vec(), "text/plain")
+            .unwrap_err();
+        assert!(error.is::<AttachmentTooLarge>());
+        let error = DeploymentClient::new(&confluence, Deployment::DataCenter)
+            .upload_attachment(&page, "big.bin", b"12345".to_vec(), "text/plain")
+            .unwrap_err();
+        assert!(error.is::<AttachmentTooLarge>());
+        assert_eq!(server.contents().len(), 1);
+    }
+}
@@ -0,0 +1,810 @@
+//! The subcommands, run against the REST API.
+
+use crate::output::Output;
+use crate::{
+    AttachmentCommand, BodySource, Command, LabelCommand, PageCommand, PropertyCommand,
+    RestrictionCommand, Subjects,
+};
+use confluence::attachment::AttachmentUpload;
+use confluence::client::{Confluence, Executor, HeaderMap, Result, StatusCode};
+use confluence::config::{Config, Profile};
+use confluence::content::model::Ancestor;
+use confluence::deployment::{Attachment, ContentApi, DeploymentClient, NewPage, Page};
+use confluence::export::SpaceExporter;
+use confluence::import::SpaceImporter;
+use confluence::markdown::MarkdownConverter;
+use confluence::migrate::{SpaceMigration, UserMap};
+use confluence::model::{Representation, Storage, UpdateAttachmentPropertiesRequest, Version};
+use confluence::restriction::{
+    ContentRestriction, ContentRestrictions, Operation, Restriction, UserRef,
+};
+use confluence::sync::{DeletedPages, DirectorySync};
+use confluence::util::{encode_query, get_all, media_type};
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::Path;
+
+const PAGE_COLUMNS: &[&str] = &["id", "title", "version", "parentId"];
+const ATTACHMENT_COLUMNS: &[&str] = &["id", "title", "version", "mediaType", "fileSize"];
+const LABEL_COLUMNS: &[&str] = &["prefix", "name"];
+const VERSION_COLUMNS: &[&str] = &["number", "when", "by.displayName", "message"];
+const PROPERTY_COLUMNS: &[&str] = &["key", "version.number", "value"];
+const RESTRICTION_COLUMNS: &[&str] = &["operation", "type", "name"];
+const SEARCH_COLUMNS: &[&str] = &["id", "type", "title", "space.key"];
+
+pub fn run(config: &Config, profile: &Profile, command: Command) -> Result<Output> {
+    let confluence = profile.connect()?;
+    match command {
+        Command::Page(command) => page(&confluence, profile, command),
+        Command::Attachment(command) => attachment(&confluence, command),
+        Command::Label(command) => label(&confluence, command),
+        Command::Property(command) => property(&confluence, command),
+        Command::Restriction(command) => restriction(&confluence, command),
+        Command::Search { cql, limit } => {
+            let endpoint = format!(
+                "content/search?cql={}&limit={}&expand=space",
+                encode_query(&cql),
+                limit
+            );
+            let value = expect(
+                confluence.get().custom_endpoint(&endpoint).execute()?,
+                "search",
+            )?;
+            Ok(Output::Value(value, SEARCH_COLUMNS))
+        }
+        Command::Sync {
+            dir,
+            parent,
+            space,
+            dry_run,
+            trash_deleted,
+            archive_under,
+        } => {
+            let space = space
+                .or_else(|| profile.default_space.clone())
+                .ok_or("Unable to find the space: use --space or set a default space")?;
+            let deleted = match (trash_deleted, archive_under) {
+                (_, Some(page)) => DeletedPages::MoveUnder(Ancestor::new(&page)),
+                (true, None) => DeletedPages::Trash,
+                (false, None) => DeletedPages::Keep,
+            };
+            let sync = DirectorySync::new(&confluence, dir, &space, Ancestor::new(&parent))
+                .with_deleted_pages(deleted);
+            let plan = sync.plan()?;
+            if !dry_run {
+                sync.apply(&plan)?;
+            }
+            Ok(Output::Text(plan.to_string().trim_end().to_string()))
+        }
+        Command::Export { space, dir } => {
+            let report = SpaceExporter::new(&confluence, &space, dir).export()?;
+            Ok(Output::Text(format!(
+                "{} exported, {} unchanged, {} removed",
+                report.exported.len(),
+                report.unchanged.len(),
+                report.removed.len()
+            )))
+        }
+        Command::Import { dir, space, parent } => {
+            let mut importer = SpaceImporter::new(&confluence, dir, &space);
+            if let Some(parent) = parent {
+                importer = importer.with_parent(Ancestor::new(&parent));
+            }
+            let report = importer.import()?;
+            Ok(Output::Text(report.to_string().trim_end().to_string()))
+        }
+        Command::Migrate {
+            space,
+            to_profile,
+            to_space,
+            parent,
+            users,
+            checkpoint,
+        } => {
+            let target = config
+                .profiles
+                .get(&to_profile)
+                .ok_or_else(|| format!("Unable to find profile {}", to_profile))?
+                .connect()?;
+            let to_space = to_space.unwrap_or_else(|| space.clone());
+            let mut migration = SpaceMigration::new(&confluence, &space, &target, &to_space);
+            if let Some(parent) = parent {
+                migration = migration.with_parent(Ancestor::new(&parent));
+            }
+            if let Some(users) = users {
+                migration = migration.with_users(UserMap::load(users)?);
+            }
+            if let Some(checkpoint) = checkpoint {
+                migration = migration.with_checkpoint(checkpoint);
+            }
+            let report = migration.migrate()?;
+            if !report.verification.is_ok() {
+                return Err(format!(
+                    "Unable to verify the migration:\n{}",
+                    report.to_string().trim_end()
+                )
+                .into());
+            }
+            Ok(Output::Text(report.to_string().trim_end().to_string()))
+        }
+        Command::Raw { method, path, data } => raw(&confluence, &method, &path, data),
+    }
+}
+
+fn page(confluence: &Confluence, profile: &Profile, command: PageCommand) -> Result<Output> {
+    let api = DeploymentClient::detect(confluence)?;
+    let space = |space: Option<String>| {
+        space
+            .or_else(|| profile.default_space.clone())
+            .ok_or("Unable to find the space: use --space or set a default space")
+    };
+    match command {
+        PageCommand::Get { id, body: true } => {
+            Ok(Output::Text(api.get_page(&id)?.body.unwrap_or_default()))
+        }
+        PageCommand::Get { id, body: false } => {
+            Ok(Output::Value(page_json(&api.get_page(&id)?), PAGE_COLUMNS))
+        }
+        PageCommand::Find { title, space: key } => match api.find_page(&space(key)?, &title)? {
+            Some(page) => Ok(Output::Value(page_json(&page), PAGE_COLUMNS)),
+            None => Err(format!("Unable to find page {}", title).into()),
+        },
+        PageCommand::Create {
+            title,
+            space: key,
+            parent,
+            body,
+        } => {
+            let page = NewPage::new(&space(key)?, &title, body.read()?, parent.as_deref());
+            Ok(Output::Value(
+                page_json(&api.create_page(&page)?),
+                PAGE_COLUMNS,
+            ))
+        }
+        PageCommand::Update { id, title, body } => {
+            let current = api.get_page(&id)?;
+            let content = expect(
+                confluence
+                    .get()
+                    .custom_endpoint(&format!("content/{}?expand=space", id))
+                    .execute()?,
+                "get page",
+            )?;
+            let space_key = content["space"]["key"].as_str().unwrap_or_default();
+            let page = NewPage::new(
+                space_key,
+                title.as_deref().unwrap_or(&current.title),
+                body.read()?,
+                current.parent_id.as_deref(),
+            );
+            let updated = api.update_page(&id, &page, current.version + 1)?;
+            Ok(Output::Value(page_json(&updated), PAGE_COLUMNS))
+        }
+        PageCommand::Delete { id } => {
+            api.delete_page(&id)?;
+            Ok(Output::Nothing)
+        }
+        PageCommand::Children { id } => {
+            let endpoint = format!("content/{}/child/page?expand=version", id);
+            let pages = get_all(confluence, &endpoint, "list child pages")?;
+            Ok(Output::Value(
+                Value::Array(pages),
+                &["id", "title", "version.number"],
+            ))
+        }
+    }
+}
+
+impl BodySource {
+    fn read(&self) -> Result<Storage> {
+        let text = match (&self.body, &self.file) {
+            (Some(body), _) => body.clone(),
+            (None, Some(path)) if path == Path::new("-") => {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            }
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?,
+            (None, None) => return Err("Unable to read the body: use --body or --file".into()),
+        };
+        let storage = if self.markdown {
+            MarkdownConverter::new().convert(&text).storage
+        } else {
+            text
+        };
+        Ok(Storage::new(&storage, Representation::Storage))
+    }
+}
+
+fn attachment(confluence: &Confluence, command: AttachmentCommand) -> Result<Output> {
+    let api = DeploymentClient::detect(confluence)?;
+    match command {
+        AttachmentCommand::List { page } => {
+            let attachments = api.attachments(&page)?;
+            let value = attachments.iter().map(attachment_json).collect();
+            Ok(Output::Value(Value::Array(value), ATTACHMENT_COLUMNS))
+        }
+        AttachmentCommand::Upload {
+            page,
+            file,
+            name,
+            content_type,
+        } => {
+            let name = match name {
+                Some(name) => name,
+                None => file
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .ok_or_else(|| format!("Unable to name attachment {}", file.display()))?,
+            };
+            let media_type = content_type.unwrap_or_else(|| media_type(&name).to_string());
+            let existing = api
+                .attachments(&page)?
+                .into_iter()
+                .find(|a| a.title == name);
+            let upload = AttachmentUpload::from_file(confluence, &page, &file)?
+                .with_filename(&name)
+                .with_media_type(&media_type);
+            let uploaded = match existing {
+                Some(existing) => upload.replace(&existing.id)?,
+                None => upload.create()?,
+            };
+            let extensions = uploaded.extensions.as_ref();
+            let uploaded = json!({
+                "id": uploaded.id,
+                "title": uploaded.title,
+                "version": uploaded.version.as_ref().map_or(1, |v| v.number),
+                "mediaType": extensions.and_then(|e| e.media_type.as_deref()),
+                "fileSize": extensions.and_then(|e| e.file_size),
+                "download": uploaded.links.download,
+            });
+            Ok(Output::Value(uploaded, ATTACHMENT_COLUMNS))
+        }
+        AttachmentCommand::Download {
+            page,
+            name,
+            out,
+            version: Some(version),
+        } => {
+            find_attachment(&api, &page, &name)?;
+            let data = confluence.download_attachment(&page, &name, Some(version))?;
+            write_out(data, out)
+        }
+        AttachmentCommand::Download {
+            page,
+            name,
+            out,
+            version: None,
+        } => {
+            let path = find_attachment(&api, &page, &name)?
+                .download
+                .ok_or_else(|| format!("Unable to download {}: no download link", name))?;
+            write_out(confluence.download(&path)?, out)
+        }
+        AttachmentCommand::Update {
+            page,
+            name,
+            rename,
+            comment,
+            content_type,
+        } => {
+            let attachment = find_attachment(&api, &page, &name)?;
+            let mut request = UpdateAttachmentPropertiesRequest::new(
+                &attachment.id,
+                rename.as_deref().unwrap_or(&name),
+                Version::new(attachment.version + 1),
+            );
+            if let Some(comment) = comment {
+                request = request.with_comment(&comment);
+            }
+            if let Some(media_type) = content_type.as_deref().or(attachment.media_type.as_deref()) {
+                request = request.with_media_type(media_type);
+            }
+            update_attachment(confluence, &page, &attachment.id, request)
+        }
+        AttachmentCommand::Move { page, name, to } => {
+            let attachment = find_attachment(&api, &page, &name)?;
+            let mut request = UpdateAttachmentPropertiesRequest::new(
+                &attachment.id,
+                &name,
+                Version::new(attachment.version + 1),
+            )
+            .with_container(&to, "page");
+            if let Some(media_type) = &attachment.media_type {
+                request = request.with_media_type(media_type);
+            }
+            update_attachment(confluence, &page, &attachment.id, request)
+        }
+        AttachmentCommand::Versions { page, name } => {
+            let attachment = find_attachment(&api, &page, &name)?;
+            let response = confluence
+                .get()
+                .content()
+                .content_id(&attachment.id)
+                .version()
+                .execute()?;
+            Ok(Output::Value(
+                expect(response, "list versions")?,
+                VERSION_COLUMNS,
+            ))
+        }
+        AttachmentCommand::DeleteVersion {
+            page,
+            name,
+            version,
+        } => {
+            let attachment = find_attachment(&api, &page, &name)?;
+            let response = confluence
+                .delete()
+                .content()
+                .content_id(&attachment.id)
+                .version()
+                .version_number(&version.to_string())
+                .execute()?;
+            check(response, "delete version")?;
+            Ok(Output::Nothing)
+        }
+    }
+}
+
+fn find_attachment(api: &DeploymentClient, page: &str, name: &str) -> Result<Attachment> {
+    api.attachments(page)?
+        .into_iter()
+        .find(|a| a.title == name)
+        .ok_or_else(|| format!("Unable to find attachment {}", name).into())
+}
+
+fn update_attachment(
+    confluence: &Confluence,
+    page: &str,
+    id: &str,
+    request: UpdateAttachmentPropertiesRequest,
+) -> Result<Output> {
+    let response = confluence
+        .put(request)
+        .content()
+        .content_id(page)
+        .child()
+        .attachment()
+        .attachment_id(id)
+        .execute()?;
+    Ok(Output::Value(
+        expect(response, "update attachment")?,
+        ATTACHMENT_COLUMNS,
+    ))
+}
+
+/// Writes downloaded data to `out`, or prints it.
+fn write_out(data: Vec<u8>, out: Option<std::path::PathBuf>) -> Result<Output> {
+    match out {
+        Some(out) => {
+            std::fs::write(&out, data)
+                .map_err(|e| format!("Unable to write {}: {}", out.display(), e))?;
+            Ok(Output::Nothing)
+        }
+        None => Ok(Output::Bytes(data)),
+    }
+}
+
+fn label(confluence: &Confluence, command: LabelCommand) -> Result<Output> {
+    match command {
+        LabelCommand::List { id } => {
+            let endpoint = format!("content/{}/label", id);
+            let value = expect(
+                confluence.get().custom_endpoint(&endpoint).execute()?,
+                "list labels",
+            )?;
+            Ok(Output::Value(value, LABEL_COLUMNS))
+        }
+        LabelCommand::Add { id, labels } => {
+            let labels: Vec<Value> = labels
+                .iter()
+                .map(|name| json!({ "prefix": "global", "name": name }))
+                .collect();
+            let endpoint = format!("content/{}/label", id);
+            let value = expect(
+                confluence
+                    .post(labels)
+                    .custom_endpoint(&endpoint)
+                    .execute()?,
+                "add labels",
+            )?;
+            Ok(Output::Value(value, LABEL_COLUMNS))
+        }
+        LabelCommand::Remove { id, label } => {
+            let endpoint = format!("content/{}/label?name={}", id, encode_query(&label));
+            check(
+                confluence.delete().custom_endpoint(&endpoint).execute()?,
+                "remove label",
+            )?;
+            Ok(Output::Nothing)
+        }
+    }
+}
+
+fn restriction(confluence: &Confluence, command: RestrictionCommand) -> Result<Output> {
+    match command {
+        RestrictionCommand::List { id } => {
+            let restrictions = ContentRestrictions::new(confluence, &id).get()?;
+            Ok(Output::Value(
+                restriction_json(&restrictions),
+                RESTRICTION_COLUMNS,
+            ))
+        }
+        RestrictionCommand::Add {
+            id,
+            operation,
+            subjects,
+        } => {
+            let (users, groups) = subjects.split();
+            let mut restriction = Restriction::new(operation);
+            restriction.users = users;
+            restriction.groups = groups;
+            let restrictions = ContentRestrictions::new(confluence, &id).add(&[restriction])?;
+            Ok(Output::Value(
+                restriction_json(&restrictions),
+                RESTRICTION_COLUMNS,
+            ))
+        }
+        RestrictionCommand::Remove {
+            id,
+            operation,
+            subjects,
+        } => {
+            let restrictions = ContentRestrictions::new(confluence, &id);
+            let (users, groups) = subjects.split();
+            for user in &users {
+                restrictions.remove_user(operation, user)?;
+            }
+            for group in &groups {
+                restrictions.remove_group(operation, group)?;
+            }
+            Ok(Output::Nothing)
+        }
+        RestrictionCommand::Clear { id } => {
+            ContentRestrictions::new(confluence, &id).clear()?;
+            Ok(Output::Nothing)
+        }
+    }
+}
+
+impl Subjects {
+    fn split(self) -> (Vec<UserRef>, Vec<String>) {
+        let users = self
+            .user
+            .iter()
+            .map(|u| UserRef::username(u))
+            .chain(self.account_id.iter().map(|a| UserRef::account_id(a)))
+            .collect();
+        (users, self.group)
+    }
+}
+
+/// A row for every user and group of every operation.
+fn restriction_json(restrictions: &[ContentRestriction]) -> Value {
+    let row = |operation: Operation, kind: &str, name: Option<&str>| json!({ "operation": operation, "type": kind, "name": name });
+    let rows = restrictions
+        .iter()
+        .flat_map(|r| {
+            let users = r.users().iter().map(move |u| {
+                let name = u.username.as_deref().or(u.account_id.as_deref());
+                row(r.operation, "user", name)
+            });
+            let groups = r
+                .groups()
+                .iter()
+                .map(move |g| row(r.operation, "group", Some(&g.name)));
+            users.chain(groups)
+        })
+        .collect();
+    Value::Array(rows)
+}
+
+fn property(confluence: &Confluence, command: PropertyCommand) -> Result<Output> {
+    let endpoint = |id: &str, key: Option<&str>| match key {
+        Some(key) => format!("content/{}/property/{}", id, encode_query(key)),
+        None => format!("content/{}/property", id),
+    };
+    match command {
+        PropertyCommand::List { id } => {
+            let value = expect(
+                confluence
+                    .get()
+                    .custom_endpoint(&endpoint(&id, None))
+                    .execute()?,
+                "list properties",
+            )?;
+            Ok(Output::Value(value, PROPERTY_COLUMNS))
+        }
+        PropertyCommand::Get { id, key } => {
+            let value = expect(
+                confluence
+                    .get()
+                    .custom_endpoint(&endpoint(&id, Some(&key)))
+                    .execute()?,
+                "get property",
+            )?;
+            Ok(Output::Value(value, PROPERTY_COLUMNS))
+        }
+        PropertyCommand::Set { id, key, value } => {
+            let value: Value = serde_json::from_str(&value)
+                .map_err(|e| format!("Unable to parse the value as JSON: {}", e))?;
+            let (_, status, current) = confluence
+                .get()
+                .custom_endpoint(&endpoint(&id, Some(&key)))
+                .execute::<Value>()?;
+            let response = match current {
+                Some(current) if status.is_success() => {
+                    let version = current["version"]["number"].as_u64().unwrap_or(0) + 1;
+                    let body =
+                        json!({ "key": key, "value": value, "version": { "number": version } });
+                    confluence
+                        .put(body)
+                        .custom_endpoint(&endpoint(&id, Some(&key)))
+                        .execute()?
+                }
+                _ => confluence
+                    .post(json!({ "key": key, "value": value }))
+                    .custom_endpoint(&endpoint(&id, None))
+                    .execute()?,
+            };
+            Ok(Output::Value(
+                expect(response, "set property")?,
+                PROPERTY_COLUMNS,
+            ))
+        }
+        PropertyCommand::Delete { id, key } => {
+            check(
+                confluence
+                    .delete()
+                    .custom_endpoint(&endpoint(&id, Some(&key)))
+                    .execute()?,
+                "delete property",
+            )?;
+            Ok(Output::Nothing)
+        }
+    }
+}
+
+fn raw(confluence: &Confluence, method: &str, path: &str, data: Option<String>) -> Result<Output> {
+    let body: Option<Value> = match data {
+        Some(data) => {
+            let text = match data.strip_prefix('@') {
+                Some(file) => std::fs::read_to_string(file)
+                    .map_err(|e| format!("Unable to read {}: {}", file, e))?,
+                None => data,
+            };
+            Some(
+                serde_json::from_str(&text)
+                    .map_err(|e| format!("Unable to parse the data as JSON: {}", e))?,
+            )
+        }
+        None => None,
+    };
+    let path = path.trim_start_matches('/');
+    let response = match method.to_ascii_uppercase().as_str() {
+        "GET" => confluence.get().custom_endpoint(path).execute()?,
+        "POST" => confluence
+            .post(body.unwrap_or(Value::Null))
+            .custom_endpoint(path)
+            .execute()?,
+        "PUT" => confluence
+            .put(body.unwrap_or(Value::Null))
+            .custom_endpoint(path)
+            .execute()?,
+        "DELETE" => confluence.delete().custom_endpoint(path).execute()?,
+        other => return Err(format!("Unable to send {} requests", other).into()),
+    };
+    let (_, status, value) = response;
+    let value = value.unwrap_or(Value::Null);
+    if !status.is_success() {
+        return Err(format!("{} {}: {}", status, path, value).into());
+    }
+    Ok(Output::Value(value, &[]))
+}
+
+/// The body of a successful response.
+fn expect(response: (HeaderMap, StatusCode, Option<Value>), action: &str) -> Result<Value> {
+    let (_, status, value) = response;
+    confluence::util::expect(status, value, action)
+}
+
+fn check(response: (HeaderMap, StatusCode, Option<Value>), action: &str) -> Result<()> {
+    expect(response, action).map(|_| ())
+}
+
+fn page_json(page: &Page) -> Value {
+    json!({
+        "id": page.id,
+        "title": page.title,
+        "version": page.version,
+        "parentId": page.parent_id,
+        "body": page.body,
+    })
+}
+
+fn attachment_json(attachment: &Attachment) -> Value {
+    json!({
+        "id": attachment.id,
+        "title": attachment.title,
+        "version": attachment.version,
+        "mediaType": attachment.media_type,
+        "fileSize": attachment.file_size,
+        "download": attachment.download,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use confluence::fake::FakeConfluence;
+
+    fn run_args(server: &FakeConfluence, args: &[&str]) -> Result<Output> {
+        let cli = crate::Cli::try_parse_from(std::iter::once(&"confluence").chain(args))?;
+        let profile = Profile {
+            host: Some(server.url()),
+            ..Profile::default()
+        };
+        run(&Config::default(), &profile, cli.command)
+    }
+
+    fn value(output: Output) -> Value {
+        match output {
+            Output::Value(value, _) => value,
+            _ => panic!("expected a value"),
+        }
+    }
+
+    #[test]
+    fn pages_labels_and_properties() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let page = value(
+            run_args(
+                &server,
+                &[
+                    "page",
+                    "create",
+                    "Home",
+                    "--space",
+                    "DOCS",
+                    "--body",
+                    "<p>Hi</p>",
+                ],
+            )
+            .unwrap(),
+        );
+        let id = page["id"].as_str().unwrap();
+
+        match run_args(&server, &["page", "get", id, "--body"]).unwrap() {
+            Output::Text(body) => assert_eq!(body, "<p>Hi</p>"),
+            _ => panic!("expected the body"),
+        }
+        let updated =
+            value(run_args(&server, &["page", "update", id, "--body", "<p>Bye</p>"]).unwrap());
+        assert_eq!(updated["version"], 2);
+        assert_eq!(updated["title"], "Home");
+
+        run_args(&server, &["label", "add", id, "draft", "docs"]).unwrap();
+        run_args(&server, &["label", "remove", id, "draft"]).unwrap();
+        assert_eq!(server.content(id).unwrap().labels, vec!["docs"]);
+
+        run_args(
+            &server,
+            &["property", "set", id, "owner", r#"{"team":"docs"}"#],
+        )
+        .unwrap();
+        run_args(&server, &["property", "set", id, "owner", r#""ops""#]).unwrap();
+        let property = value(run_args(&server, &["property", "get", id, "owner"]).unwrap());
+        assert_eq!(property["value"], "ops");
+        assert_eq!(property["version"]["number"], 2);
+
+        let args = ["restriction", "add", id, "read", "--group", "finance"];
+        run_args(&server, &args).unwrap();
+        run_args(
+            &server,
+            &["restriction", "add", id, "update", "--user", "admin"],
+        )
+        .unwrap();
+        run_args(
+            &server,
+            &["restriction", "remove", id, "read", "--group", "finance"],
+        )
+        .unwrap();
+        let restrictions = value(run_args(&server, &["restriction", "list", id]).unwrap());
+        assert_eq!(
+            restrictions,
+            json!([{ "operation": "update", "type": "user", "name": "admin" }])
+        );
+        assert!(run_args(&server, &["restriction", "add", id, "read"]).is_err());
+
+        let found = value(run_args(&server, &["search", "label = docs"]).unwrap());
+        assert_eq!(found["results"][0]["id"], id);
+        let err = run_args(&server, &["raw", "GET", "content/999"])
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("404 Not Found"));
+    }
+
+    #[test]
+    fn lists_every_child_page() {
+        let server = FakeConfluence::start()
+            .unwrap()
+            .with_space("DOCS", "Docs")
+            .with_max_limit(2);
+        let home = server.create_page("DOCS", "Home", None);
+        for title in ["One", "Two", "Three"] {
+            server.create_page("DOCS", title, Some(&home));
+        }
+        let children = value(run_args(&server, &["page", "children", &home]).unwrap());
+        let titles: Vec<&str> = children
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, ["One", "Two", "Three"]);
+    }
+
+    #[test]
+    fn attachments_round_trip() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let page = value(
+            run_args(
+                &server,
+                &["page", "create", "Home", "--space", "DOCS", "--body", ""],
+            )
+            .unwrap(),
+        );
+        let id = page["id"].as_str().unwrap();
+        let file = std::env::temp_dir().join(format!("cli-{}.txt", std::process::id()));
+        std::fs::write(&file, "first").unwrap();
+        let path = file.to_str().unwrap();
+        run_args(
+            &server,
+            &["attachment", "upload", id, path, "--name", "notes.txt"],
+        )
+        .unwrap();
+        std::fs::write(&file, "second").unwrap();
+        run_args(
+            &server,
+            &["attachment", "upload", id, path, "--name", "notes.txt"],
+        )
+        .unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let list = value(run_args(&server, &["attachment", "list", id]).unwrap());
+        assert_eq!(list.as_array().unwrap().len(), 1);
+        assert_eq!(list[0]["mediaType"], "text/plain");
+        match run_args(&server, &["attachment", "download", id, "notes.txt"]).unwrap() {
+            Output::Bytes(bytes) => assert_eq!(bytes, b"second"),
+            _ => panic!("expected the attachment"),
+        }
+
+        let archive = value(
+            run_args(
+                &server,
+                &["page", "create", "Archive", "--space", "DOCS", "--body", ""],
+            )
+            .unwrap(),
+        );
+        let archive = archive["id"].as_str().unwrap();
+        run_args(&server, &["attachment", "move", id, "notes.txt", archive]).unwrap();
+        let versions =
+            value(run_args(&server, &["attachment", "versions", archive, "notes.txt"]).unwrap());
+        assert_eq!(versions["results"][0]["number"], 3);
+        let args = [
+            "attachment",
+            "download",
+            archive,
+            "notes.txt",
+            "--version",
+            "1",
+        ];
+        match run_args(&server, &args).unwrap() {
+            Output::Bytes(bytes) => assert_eq!(bytes, b"first"),
+            _ => panic!("expected the first version"),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! `confluence`, a command-line client for ad-hoc tasks. Built with the
+//! `cli` feature:
+//!
+//! ```text
+//! cargo install confluence --features cli
+//! confluence --profile work page get 123456 --body
+//! confluence attachment upload 123456 diagram.png
+//! confluence -o json search 'space = DOCS and label = release'
+//! ```
+//!
+//! Connections are configured like `confluence::config`, with flags taking
+//! precedence over the environment and the config file.
+
+mod commands;
+mod output;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use confluence::client::Result;
+use confluence::config::{Auth, Config, Profile, Secret};
+use confluence::restriction::Operation;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(
+    name = "confluence",
+    version,
+    about = "Work with Confluence pages, attachments, labels and properties"
+)]
+struct Cli {
+    #[command(flatten)]
+    connection: Connection,
+    #[arg(short, long, global = true, value_enum, default_value_t = Format::Table)]
+    output: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Args)]
+struct Connection {
+    /// Profile of the config file.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Config file to read instead of `~/.config/confluence/config.toml`.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Base URL, e.g. `https://wiki.example.com`.
+    #[arg(long, global = true)]
+    host: Option<String>,
+    #[arg(long, global = true)]
+    context_path: Option<String>,
+    /// Username for basic authentication.
+    #[arg(long, global = true)]
+    username: Option<String>,
+    /// Environment variable holding the password or API token.
+    #[arg(long, global = true)]
+    password_env: Option<String>,
+    /// File holding the password or API token.
+    #[arg(long, global = true)]
+    password_file: Option<PathBuf>,
+    /// Environment variable holding a personal access token.
+    #[arg(long, global = true)]
+    token_env: Option<String>,
+    /// File holding a personal access token.
+    #[arg(long, global = true)]
+    token_file: Option<PathBuf>,
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+    /// In seconds.
+    #[arg(long, global = true)]
+    connect_timeout: Option<u64>,
+    /// In seconds, for each request as a whole; uploads aren't bounded.
+    #[arg(long, global = true)]
+    read_timeout: Option<u64>,
+}
+
+impl Connection {
+    /// The profile selected and overridden by the environment, then by the
+    /// flags.
+    fn config(&self) -> Result<Config> {
+        match &self.config {
+            Some(path) => Config::load(path),
+            None => Config::discover(),
+        }
+    }
+
+    fn profile(&self, config: &Config) -> Result<Profile> {
+        let mut profile = config.profile(self.profile.as_deref())?;
+        let set = |field: &mut Option<String>, value: &Option<String>| {
+            if value.is_some() {
+                field.clone_from(value);
+            }
+        };
+        set(&mut profile.host, &self.host);
+        set(&mut profile.context_path, &self.context_path);
+        set(&mut profile.proxy, &self.proxy);
+        profile.connect_timeout = self.connect_timeout.or(profile.connect_timeout);
+        profile.read_timeout = self.read_timeout.or(profile.read_timeout);
+
+        let secret = |env: &Option<String>, file: &Option<PathBuf>| match (env, file) {
+            (Some(env), _) => Some(Secret::Env { env: env.clone() }),
+            (None, Some(file)) => Some(Secret::File { file: file.clone() }),
+            (None, None) => None,
+        };
+        let password = secret(&self.password_env, &self.password_file);
+        if let Some(token) = secret(&self.token_env, &self.token_file) {
+            profile.auth = Auth::Bearer { token };
+        } else if self.username.is_some() || password.is_some() {
+            let (username, current) = match profile.auth {
+                Auth::Basic { username, password } => (Some(username), Some(password)),
+                _ => (None, None),
+            };
+            profile.auth = Auth::Basic {
+                username: self
+                    .username
+                    .clone()
+                    .or(username)
+                    .ok_or("Unable to use basic auth: --username is missing")?,
+                password: password.or(current).ok_or(
+                    "Unable to use basic auth: --password-env or --password-file is missing",
+                )?,
+            };
+        }
+        Ok(profile)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Get, find, create, update or delete pages.
+    #[command(subcommand)]
+    Page(PageCommand),
+    /// List, upload or download the attachments of a page.
+    #[command(subcommand)]
+    Attachment(AttachmentCommand),
+    /// List, add or remove the labels of content.
+    #[command(subcommand)]
+    Label(LabelCommand),
+    /// List, get, set or delete the properties of content.
+    #[command(subcommand)]
+    Property(PropertyCommand),
+    /// List, add or remove who may read or update content.
+    #[command(subcommand)]
+    Restriction(RestrictionCommand),
+    /// Search content with CQL.
+    Search {
+        cql: String,
+        #[arg(long, default_value_t = 25)]
+        limit: u32,
+    },
+    /// Publish a directory of Markdown documents as a page tree.
+    Sync {
+        dir: PathBuf,
+        /// The page to publish under.
+        #[arg(long)]
+        parent: String,
+        #[arg(long)]
+        space: Option<String>,
+        /// Print the changes without making them.
+        #[arg(long)]
+        dry_run: bool,
+        /// Trash the pages of removed documents.
+        #[arg(long, conflicts_with = "archive_under")]
+        trash_deleted: bool,
+        /// Move the pages of removed documents under this page.
+        #[arg(long)]
+        archive_under: Option<String>,
+    },
+    /// Export a space to a directory, or update a previous export.
+    Export { space: String, dir: PathBuf },
+    /// Import an export into a space and print the old and new ids.
+    Import {
+        dir: PathBuf,
+        space: String,
+        /// The page to create the top pages under.
+        #[arg(long)]
+        parent: Option<String>,
+    },
+    /// Copy a space to another instance, resuming from a checkpoint.
+    Migrate {
+        space: String,
+        /// Profile of the config file to copy to, without the environment
+        /// overrides.
+        #[arg(long)]
+        to_profile: String,
+        /// Defaults to the key of the source space.
+        #[arg(long)]
+        to_space: Option<String>,
+        /// The page to create the top pages under.
+        #[arg(long)]
+        parent: Option<String>,
+        /// `username,accountId` lines mapping source users to the target.
+        #[arg(long)]
+        users: Option<PathBuf>,
+        /// File to save progress to and resume from.
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+    },
+    /// Send a request to an endpoint of the REST API, e.g. `GET space`.
+    Raw {
+        method: String,
+        /// Relative to the REST API, with the query string.
+        path: String,
+        /// JSON request body, or `@file`.
+        #[arg(long)]
+        data: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PageCommand {
+    Get {
+        id: String,
+        /// Print the storage format body only.
+        #[arg(long)]
+        body: bool,
+    },
+    Find {
+        title: String,
+        /// Defaults to the profile's default space.
+        #[arg(long)]
+        space: Option<String>,
+    },
+    Create {
+        title: String,
+        #[arg(long)]
+        space: Option<String>,
+        #[arg(long)]
+        parent: Option<String>,
+        #[command(flatten)]
+        body: BodySource,
+    },
+    Update {
+        id: String,
+        /// Keeps the current title unless given.
+        #[arg(long)]
+        title: Option<String>,
+        #[command(flatten)]
+        body: BodySource,
+    },
+    Delete {
+        id: String,
+    },
+    /// List the child pages.
+    Children {
+        id: String,
+    },
+}
+
+/// Where a page body comes from.
+#[derive(Args)]
+struct BodySource {
+    /// Storage format body.
+    #[arg(long, conflicts_with = "file")]
+    body: Option<String>,
+    /// File with the body, or `-` for stdin.
+    #[arg(long)]
+    file: Option<PathBuf>,
+    /// The body is Markdown, converted to storage format.
+    #[arg(long)]
+    markdown: bool,
+}
+
+#[derive(Subcommand)]
+enum AttachmentCommand {
+    List {
+        page: String,
+    },
+    /// Upload a file, replacing an attachment of the same name.
+    Upload {
+        page: String,
+        file: PathBuf,
+        /// Defaults to the file name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Guessed from the file name unless given.
+        #[arg(long)]
+        content_type: Option<String>,
+    },
+    Download {
+        page: String,
+        name: String,
+        /// Defaults to stdout.
+        #[arg(short = 'O', long)]
+        out: Option<PathBuf>,
+        /// Defaults to the current version.
+        #[arg(long)]
+        version: Option<u64>,
+    },
+    /// Rename an attachment or change its comment or media type.
+    Update {
+        page: String,
+        name: String,
+        #[arg(long)]
+        rename: Option<String>,
+        #[arg(long)]
+        comment: Option<String>,
+        #[arg(long)]
+        content_type: Option<String>,
+    },
+    /// Move an attachment to another page.
+    Move {
+        page: String,
+        name: String,
+        to: String,
+    },
+    /// List the versions of an attachment, the current one first.
+    Versions {
+        page: String,
+        name: String,
+    },
+    /// Delete a previous version of an attachment.
+    DeleteVersion {
+        page: String,
+        name: String,
+        version: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum LabelCommand {
+    List {
+        id: String,
+    },
+    Add {
+        id: String,
+        #[arg(required = true)]
+        labels: Vec<String>,
+    },
+    Remove {
+        id: String,
+        label: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PropertyCommand {
+    List {
+        id: String,
+    },
+    Get {
+        id: String,
+        key: String,
+    },
+    /// Create or update a property with a JSON value.
+    Set {
+        id: String,
+        key: String,
+        value: String,
+    },
+    Delete {
+        id: String,
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum RestrictionCommand {
+    List {
+        id: String,
+    },
+    /// Restrict an operation to users and groups, in addition to the
+    /// current ones.
+    Add {
+        id: String,
+        /// `read` or `update`.
+        operation: Operation,
+        #[command(flatten)]
+        subjects: Subjects,
+    },
+    Remove {
+        id: String,
+        operation: Operation,
+        #[command(flatten)]
+        subjects: Subjects,
+    },
+    /// Remove all restrictions.
+    Clear {
+        id: String,
+    },
+}
+
+/// The users and groups of a restriction.
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+struct Subjects {
+    /// Username, on Server and Data Center.
+    #[arg(long)]
+    user: Vec<String>,
+    /// Account id, on Cloud.
+    #[arg(long)]
+    account_id: Vec<String>,
+    #[arg(long)]
+    group: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let format = cli.output;
+    let result = cli
+        .connection
+        .config()
+        .and_then(|config| {
+            let profile = cli.connection.profile(&config)?;
+            commands::run(&config, &profile, cli.command)
+        })
+        .and_then(|out| output::print(&out, format));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("confluence: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
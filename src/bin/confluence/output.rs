@@ -0,0 +1,111 @@
+//! Printing command results as a table, JSON or YAML.
+
+use crate::Format;
+use confluence::client::Result;
+use serde_json::Value;
+use std::io::Write;
+
+/// What a command prints.
+pub enum Output {
+    /// Shown in a table with the given columns, dotted paths into each
+    /// row, or as JSON or YAML.
+    Value(Value, &'static [&'static str]),
+    /// Printed as is, whatever the format.
+    Text(String),
+    Bytes(Vec<u8>),
+    Nothing,
+}
+
+pub fn print(output: &Output, format: Format) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    match output {
+        Output::Value(value, columns) => {
+            let text = match format {
+                Format::Table if !columns.is_empty() => table(value, columns),
+                Format::Yaml => serde_yaml::to_string(value)?,
+                Format::Table | Format::Json => serde_json::to_string_pretty(value)? + "\n",
+            };
+            out.write_all(text.as_bytes())?;
+        }
+        Output::Text(text) => writeln!(out, "{}", text)?,
+        Output::Bytes(bytes) => out.write_all(bytes)?,
+        Output::Nothing => {}
+    }
+    Ok(out.flush()?)
+}
+
+/// Rows of `value`, or of its `results` for a page of results, aligned in
+/// columns.
+fn table(value: &Value, columns: &[&str]) -> String {
+    let rows: Vec<&Value> = match value {
+        Value::Array(rows) => rows.iter().collect(),
+        Value::Object(map) => match map.get("results") {
+            Some(Value::Array(rows)) => rows.iter().collect(),
+            _ => vec![value],
+        },
+        _ => vec![value],
+    };
+    let mut cells: Vec<Vec<String>> = vec![columns.iter().map(|c| c.to_uppercase()).collect()];
+    cells.extend(rows.iter().map(|row| {
+        columns
+            .iter()
+            .map(|column| cell(lookup(row, column)))
+            .collect()
+    }));
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut text = String::new();
+    for row in cells {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        text.push_str(line.join("  ").trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+fn lookup<'v>(value: &'v Value, path: &str) -> &'v Value {
+    path.split('.').fold(value, |value, key| &value[key])
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.replace('\n', " "),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn results_are_aligned_in_columns() {
+        let value = json!({"results": [
+            {"id": "1", "title": "Home", "version": {"number": 12}},
+            {"id": "12345", "title": "Release notes", "version": {"number": 1}},
+            {"id": "7"},
+        ]});
+        assert_eq!(
+            table(&value, &["id", "title", "version.number"]),
+            "ID     TITLE          VERSION.NUMBER\n\
+             1      Home           12\n\
+             12345  Release notes  1\n\
+             7\n"
+        );
+        assert_eq!(table(&json!({"key": "k"}), &["key"]), "KEY\nk\n");
+    }
+}
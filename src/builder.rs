@@ -0,0 +1,433 @@
+//! Configurable construction of a [`Confluence`] client.
+//!
+//! ```
+//! use confluence::client::Confluence;
+//! use std::time::Duration;
+//!
+//! let confluence = Confluence::builder("https://confluence.example.com/rest/api")
+//!     .with_connect_timeout(Duration::from_secs(5))
+//!     .with_read_timeout(Duration::from_secs(60))
+//!     .with_proxy("http://proxy.example.com:3128")
+//!     .with_no_proxy("localhost,.internal.example.com")
+//!     .with_user_agent("docs-publisher/1.0")
+//!     .with_header("X-Request-Source", "ci")
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(confluence.default_headers()["user-agent"], "docs-publisher/1.0");
+//! ```
+
+use crate::client::{Confluence, Result, DEFAULT_MAX_ATTACHMENT_SIZE};
+use crate::util::is_sensitive;
+use base64::Engine;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE, USER_AGENT};
+use reqwest::{Certificate, Identity, NoProxy, Proxy};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// The user agent requests are sent with unless configured otherwise.
+pub const DEFAULT_USER_AGENT: &str = "bardo-confluence";
+
+/// How long a request may take unless configured otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// PEM data given inline or read from a file when building.
+#[derive(Clone, Debug)]
+enum Pem {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl Pem {
+    fn read(&self) -> Result<Vec<u8>> {
+        match self {
+            Pem::Bytes(bytes) => Ok(bytes.clone()),
+            Pem::File(path) => std::fs::read(path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e).into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ClientIdentity {
+    Pkcs12 { der: Vec<u8>, password: String },
+    Pkcs8 { certificate: Pem, key: Pem },
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfluenceBuilder {
+    host: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    upload_timeout: Option<Duration>,
+    proxy: Option<String>,
+    proxy_auth: Option<(String, String)>,
+    no_proxy: Option<String>,
+    root_certificates: Vec<Pem>,
+    built_in_roots: bool,
+    identity: Option<ClientIdentity>,
+    accept_invalid_certs: bool,
+    user_agent: String,
+    headers: Vec<(String, String)>,
+    cookie_store: bool,
+    max_attachment_size: u64,
+}
+
+impl ConfluenceBuilder {
+    /// A builder for a client of the REST API at `host`, e.g.
+    /// `https://confluence.example.com/rest/api`.
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            connect_timeout: None,
+            read_timeout: Some(DEFAULT_TIMEOUT),
+            upload_timeout: None,
+            proxy: None,
+            proxy_auth: None,
+            no_proxy: None,
+            root_certificates: Vec::new(),
+            built_in_roots: true,
+            identity: None,
+            accept_invalid_certs: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            headers: Vec::new(),
+            cookie_store: true,
+            max_attachment_size: DEFAULT_MAX_ATTACHMENT_SIZE,
+        }
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Bounds each request as a whole, from connecting until the response
+    /// is read, not each read. Defaults to [`DEFAULT_TIMEOUT`]; `None`
+    /// removes the limit. Uploads have their own, see
+    /// [`with_upload_timeout`](Self::with_upload_timeout).
+    pub fn with_read_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.read_timeout = timeout.into();
+        self
+    }
+
+    /// Bounds each upload as a whole like
+    /// [`with_read_timeout`](Self::with_read_timeout). Uploads aren't
+    /// bounded by default, as large attachments take as long to send as
+    /// the connection needs.
+    pub fn with_upload_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.upload_timeout = timeout.into();
+        self
+    }
+
+    /// Sends HTTP and HTTPS requests through the proxy at `url`.
+    pub fn with_proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    pub fn with_proxy_auth(mut self, username: &str, password: &str) -> Self {
+        self.proxy_auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Bypasses the proxy for a comma separated list of hosts, domains
+    /// (`.example.com`) and IP ranges, like `NO_PROXY`.
+    pub fn with_no_proxy(mut self, hosts: &str) -> Self {
+        self.no_proxy = Some(hosts.to_string());
+        self
+    }
+
+    /// Trusts the certificates in a PEM bundle, e.g. an internal root CA.
+    pub fn with_root_certificate_pem(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(Pem::Bytes(pem.to_vec()));
+        self
+    }
+
+    pub fn with_root_certificate_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.root_certificates.push(Pem::File(path.into()));
+        self
+    }
+
+    /// Trusts only the added root certificates, not the system's.
+    pub fn with_built_in_roots(mut self, built_in_roots: bool) -> Self {
+        self.built_in_roots = built_in_roots;
+        self
+    }
+
+    /// Authenticates with a client certificate and key from a PKCS#12
+    /// archive.
+    pub fn with_client_pkcs12(mut self, der: &[u8], password: &str) -> Self {
+        self.identity = Some(ClientIdentity::Pkcs12 {
+            der: der.to_vec(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    /// Authenticates with a PEM client certificate chain and PKCS#8 key.
+    pub fn with_client_pem(mut self, certificate: &[u8], key: &[u8]) -> Self {
+        self.identity = Some(ClientIdentity::Pkcs8 {
+            certificate: Pem::Bytes(certificate.to_vec()),
+            key: Pem::Bytes(key.to_vec()),
+        });
+        self
+    }
+
+    pub fn with_client_pem_files(
+        mut self,
+        certificate: impl Into<PathBuf>,
+        key: impl Into<PathBuf>,
+    ) -> Self {
+        self.identity = Some(ClientIdentity::Pkcs8 {
+            certificate: Pem::File(certificate.into()),
+            key: Pem::File(key.into()),
+        });
+        self
+    }
+
+    /// Skips certificate validation. Only meant for test instances.
+    pub fn with_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Sends `name: value` with every request, replacing a default header
+    /// of the same name.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Authenticates every request with a username and password, or API
+    /// token on Confluence Cloud.
+    pub fn with_basic_auth(self, username: &str, password: &str) -> Self {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        self.with_header("Authorization", &format!("Basic {}", credentials))
+    }
+
+    /// Authenticates every request with a personal access token.
+    pub fn with_bearer_auth(self, token: &str) -> Self {
+        self.with_header("Authorization", &format!("Bearer {}", token))
+    }
+
+    /// Keeps cookies, such as a session cookie, between requests. On by
+    /// default.
+    pub fn with_cookie_store(mut self, cookie_store: bool) -> Self {
+        self.cookie_store = cookie_store;
+        self
+    }
+
+    /// See [`Confluence::with_max_attachment_size`].
+    pub fn with_max_attachment_size(mut self, bytes: u64) -> Self {
+        self.max_attachment_size = bytes;
+        self
+    }
+
+    pub fn build(self) -> Result<Confluence> {
+        reqwest::Url::parse(&self.host)
+            .map_err(|e| format!("Unable to parse host {}: {}", self.host, e))?;
+
+        // Timeouts are set per request, so uploads can go without one.
+        let mut client = Client::builder()
+            .timeout(None)
+            .cookie_store(self.cookie_store)
+            .tls_built_in_root_certs(self.built_in_roots)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(url) = &self.proxy {
+            let mut proxy = Proxy::all(url.as_str())
+                .map_err(|e| format!("Unable to use proxy {}: {}", url, e))?;
+            if let Some((username, password)) = &self.proxy_auth {
+                proxy = proxy.basic_auth(username, password);
+            }
+            if let Some(hosts) = &self.no_proxy {
+                proxy = proxy.no_proxy(NoProxy::from_string(hosts));
+            }
+            client = client.proxy(proxy);
+        }
+        for pem in &self.root_certificates {
+            let certificates = Certificate::from_pem_bundle(&pem.read()?)
+                .map_err(|e| format!("Unable to read root certificate: {}", e))?;
+            for certificate in certificates {
+                client = client.add_root_certificate(certificate);
+            }
+        }
+        if let Some(identity) = &self.identity {
+            let identity = match identity {
+                ClientIdentity::Pkcs12 { der, password } => {
+                    Identity::from_pkcs12_der(der, password)
+                }
+                ClientIdentity::Pkcs8 { certificate, key } => {
+                    Identity::from_pkcs8_pem(&certificate.read()?, &key.read()?)
+                }
+            };
+            let identity =
+                identity.map_err(|e| format!("Unable to read client certificate: {}", e))?;
+            client = client.identity(identity);
+        }
+        let client = client
+            .build()
+            .map_err(|e| format!("Unable to build the HTTP client: {}", e))?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(USER_AGENT, header_value(&self.user_agent)?);
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| format!("Unable to use header {}: {}", name, e))?;
+            let value = if is_sensitive(name.as_str()) {
+                HeaderValue::from_str(value)
+                    .map_err(|_| format!("Unable to use the value of header {}", name))?
+            } else {
+                header_value(value)?
+            };
+            headers.insert(name, value);
+        }
+        Ok(Confluence::with_client(Rc::new(client), &self.host)
+            .with_default_headers(headers)
+            .with_max_attachment_size(self.max_attachment_size)
+            .with_timeout(self.read_timeout)
+            .with_upload_timeout(self.upload_timeout))
+    }
+}
+
+fn header_value(value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value)
+        .map_err(|e| format!("Unable to use header value {}: {}", value, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Executor, Method};
+    use crate::transport::{Expectation, MockTransport};
+
+    #[test]
+    fn default_headers_are_sent() {
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(Expectation::new(Method::GET, "/rest/api/space"));
+        let confluence = Confluence::builder("https://confluence.example.com/rest/api")
+            .with_user_agent("publisher/2.0")
+            .with_header("Accept", "application/json, text/plain")
+            .with_header("X-Team", "docs")
+            .build()
+            .unwrap()
+            .with_transport(mock.clone());
+        confluence
+            .get()
+            .custom_endpoint("space")
+            .execute::<serde_json::Value>()
+            .unwrap();
+        let requests = mock.requests();
+        let headers = &requests[0].headers;
+        assert_eq!(headers["user-agent"], "publisher/2.0");
+        assert_eq!(headers["accept"], "application/json, text/plain");
+        assert_eq!(headers["x-team"], "docs");
+        assert_eq!(headers["content-type"], "application/json");
+    }
+
+    #[test]
+    fn default_headers_are_sent_with_uploads_and_follow_up_pages() {
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(Expectation::new(
+            Method::POST,
+            "/wiki/rest/api/content/42/child/attachment",
+        ));
+        mock.expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/pages").respond_json(
+                200,
+                &serde_json::json!({
+                    "results": [],
+                    "_links": { "next": "/wiki/api/v2/pages?cursor=b" },
+                }),
+            ),
+        );
+        mock.expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/pages")
+                .query("cursor", "b")
+                .respond_json(200, &serde_json::json!({ "results": [] })),
+        );
+        let confluence = Confluence::builder("https://example.atlassian.net/wiki/rest/api")
+            .with_user_agent("publisher/2.0")
+            .with_header("X-Team", "docs")
+            .build()
+            .unwrap()
+            .with_transport(mock.clone());
+        let part = reqwest::blocking::multipart::Part::bytes(b"hi".to_vec()).file_name("a.txt");
+        let form = reqwest::blocking::multipart::Form::new().part("file", part);
+        confluence
+            .post(())
+            .content()
+            .content_id("42")
+            .child()
+            .attachment(form)
+            .execute::<serde_json::Value>()
+            .unwrap();
+        let v2 = confluence.with_api_version(crate::client::ApiVersion::V2);
+        assert_eq!(
+            v2.cursor::<serde_json::Value, _>(v2.get().pages()).count(),
+            0
+        );
+        mock.assert_done();
+        for request in mock.requests() {
+            assert_eq!(request.headers["user-agent"], "publisher/2.0");
+            assert_eq!(request.headers["x-team"], "docs");
+        }
+    }
+
+    #[test]
+    fn uploads_are_authenticated() {
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(Expectation::new(
+            Method::POST,
+            "/rest/api/content/42/child/attachment",
+        ));
+        let confluence = Confluence::builder("https://confluence.example.com/rest/api")
+            .with_bearer_auth("SECRET")
+            .build()
+            .unwrap()
+            .with_transport(mock.clone());
+        let part = reqwest::blocking::multipart::Part::bytes(b"hi".to_vec()).file_name("a.txt");
+        let form = reqwest::blocking::multipart::Form::new().part("file", part);
+        confluence
+            .post(())
+            .content()
+            .content_id("42")
+            .child()
+            .attachment(form)
+            .execute::<serde_json::Value>()
+            .unwrap();
+        let headers = &mock.requests()[0].headers;
+        assert_eq!(headers["authorization"], "Bearer SECRET");
+        assert_eq!(headers["x-atlassian-token"], "nocheck");
+        assert!(headers["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("multipart/form-data; boundary="));
+    }
+
+    #[test]
+    fn invalid_settings_fail_the_build() {
+        let build = |builder: ConfluenceBuilder| builder.build().err().unwrap().to_string();
+        let builder = || ConfluenceBuilder::new("https://confluence.example.com/rest/api");
+        assert!(build(ConfluenceBuilder::new("not a url")).starts_with("Unable to parse host"));
+        assert!(build(builder().with_proxy("::")).starts_with("Unable to use proxy"));
+        assert!(
+            build(builder().with_root_certificate_file("/nonexistent.pem"))
+                .starts_with("Unable to read /nonexistent.pem")
+        );
+        assert!(
+            build(builder().with_header("X-Bad", "a\nb")).starts_with("Unable to use header value")
+        );
+    }
+}
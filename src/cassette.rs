@@ -0,0 +1,434 @@
+//! Recording and replaying HTTP interactions.
+//!
+//! A [`Cassette`] is a [`Transport`] that either records the interactions of
+//! another transport to a JSON file or replays them from one, so tests can
+//! run against real responses without a Confluence instance. Credentials and
+//! cookies are scrubbed before anything is written, and multipart boundaries
+//! are replaced by `[boundary]`.
+//!
+//! Replay matches requests by method and URL, and by the headers named with
+//! [`Cassette::match_header`]. A scrubbed header only has to be present.
+//!
+//! ```no_run
+//! use confluence::cassette::Cassette;
+//! use confluence::client::{Confluence, Executor};
+//! use std::rc::Rc;
+//!
+//! let cassette = Rc::new(Cassette::replay("tests/cassettes/list_content.json").unwrap());
+//! let confluence = Confluence::new("https://your.confluence.example.com/rest/api")
+//!     .with_transport(cassette);
+//! let (_, status, _) = confluence
+//!     .get()
+//!     .custom_endpoint("content")
+//!     .execute::<serde_json::Value>()
+//!     .unwrap();
+//! assert_eq!(status, 200);
+//! ```
+
+use crate::client::Result;
+use crate::transport::Transport;
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const REDACTED: &str = "[REDACTED]";
+const BOUNDARY: &str = "[boundary]";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The body if it was JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
+    /// The body if it was other text. Streamed bodies aren't recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+}
+
+enum Mode {
+    Record(Rc<dyn Transport>),
+    Replay,
+}
+
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: RefCell<Vec<Interaction>>,
+    used: RefCell<Vec<bool>>,
+    /// Whether recorded interactions still need to be written.
+    dirty: Cell<bool>,
+    scrub_headers: Vec<String>,
+    replacements: Vec<(String, String)>,
+    matched_headers: Vec<String>,
+}
+
+impl Cassette {
+    /// Serves the interactions recorded in `path`.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| format!("Unable to read cassette {}: {}", path.display(), e))?;
+        let tape: Tape = serde_json::from_slice(&data)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            used: RefCell::new(vec![false; tape.interactions.len()]),
+            interactions: RefCell::new(tape.interactions),
+            dirty: Cell::new(false),
+            scrub_headers: Vec::new(),
+            replacements: Vec::new(),
+            matched_headers: Vec::new(),
+        })
+    }
+
+    /// Sends requests with `inner` and records them to `path` when saved or
+    /// dropped.
+    pub fn record(path: impl AsRef<Path>, inner: Rc<dyn Transport>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record(inner),
+            interactions: RefCell::new(Vec::new()),
+            used: RefCell::new(Vec::new()),
+            dirty: Cell::new(false),
+            scrub_headers: Vec::new(),
+            replacements: Vec::new(),
+            matched_headers: Vec::new(),
+        }
+    }
+
+    /// Redacts `header` in addition to the credential and cookie headers.
+    pub fn scrub_header(mut self, header: &str) -> Self {
+        self.scrub_headers.push(header.to_ascii_lowercase());
+        self
+    }
+
+    /// Replaces `secret` with `replacement` in recorded URLs, headers and
+    /// bodies, e.g. a real host name with an example one.
+    pub fn scrub_text(mut self, secret: &str, replacement: &str) -> Self {
+        self.replacements
+            .push((secret.to_string(), replacement.to_string()));
+        self
+    }
+
+    /// Replays an interaction only if the request has the recorded value of
+    /// `header`, e.g. `authorization` or `user-agent`.
+    pub fn match_header(mut self, header: &str) -> Self {
+        self.matched_headers.push(header.to_ascii_lowercase());
+        self
+    }
+
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions.borrow().clone()
+    }
+
+    /// Writes the recorded interactions.
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tape = Tape {
+            interactions: self.interactions(),
+        };
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&tape)?)?;
+        self.dirty.set(false);
+        Ok(())
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        self.replacements
+            .iter()
+            .fold(text.to_string(), |text, (secret, replacement)| {
+                text.replace(secret, replacement)
+            })
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let name = name.as_str().to_string();
+                let sensitive =
+                    crate::util::is_sensitive(&name) || self.scrub_headers.contains(&name);
+                let value = if sensitive {
+                    REDACTED.to_string()
+                } else {
+                    self.scrub(&String::from_utf8_lossy(value.as_bytes()))
+                };
+                let value = match value.split_once("boundary=") {
+                    Some((before, _)) => format!("{}boundary={}", before, BOUNDARY),
+                    None => value,
+                };
+                (name, value)
+            })
+            .collect()
+    }
+
+    /// The first matched header whose value differs from the recorded one.
+    fn mismatch(&self, recorded: &RecordedRequest, request: &Request) -> Option<String> {
+        let actual = self.headers(request.headers());
+        self.matched_headers
+            .iter()
+            .find(|name| recorded.headers.get(*name) != actual.get(*name))
+            .map(|name| {
+                format!(
+                    "{}: {} recorded, {} sent",
+                    name,
+                    recorded.headers.get(name).map_or("none", String::as_str),
+                    actual.get(name).map_or("none", String::as_str)
+                )
+            })
+    }
+
+    /// Splits a body into its JSON or text form.
+    fn body(&self, bytes: Option<&[u8]>) -> (Option<serde_json::Value>, Option<String>) {
+        let text = match bytes.map(std::str::from_utf8) {
+            Some(Ok(text)) if !text.is_empty() => self.scrub(text),
+            _ => return (None, None),
+        };
+        match serde_json::from_str(&text) {
+            Ok(json) => (Some(json), None),
+            Err(_) => (None, Some(text)),
+        }
+    }
+
+    fn play(&self, request: &Request) -> Result<Response> {
+        let method = request.method().as_str();
+        let url = request.url().as_str();
+        let interactions = self.interactions.borrow();
+        let mut used = self.used.borrow_mut();
+        let mut mismatch = None;
+        let index = interactions
+            .iter()
+            .enumerate()
+            .position(|(i, interaction)| {
+                if used[i] || interaction.request.method != method || interaction.request.url != url
+                {
+                    return false;
+                }
+                mismatch = self.mismatch(&interaction.request, request);
+                mismatch.is_none()
+            })
+            .ok_or_else(|| {
+                let reason = mismatch.map(|m| format!(" ({})", m)).unwrap_or_default();
+                format!(
+                    "No interaction for {} {} in cassette {}{}",
+                    method,
+                    url,
+                    self.path.display(),
+                    reason
+                )
+            })?;
+        used[index] = true;
+        response(&interactions[index].response)
+    }
+
+    fn capture(&self, inner: &Rc<dyn Transport>, request: Request) -> Result<Response> {
+        let (json, body) = self.body(request.body().and_then(|b| b.as_bytes()));
+        let recorded = RecordedRequest {
+            method: request.method().to_string(),
+            url: self.scrub(request.url().as_str()),
+            headers: self.headers(request.headers()),
+            json,
+            body,
+        };
+        let res = inner.send(request)?;
+        let status = res.status().as_u16();
+        let headers = res.headers().clone();
+        let bytes = res.bytes()?;
+        let (json, body) = self.body(Some(&bytes));
+        let interaction = Interaction {
+            request: recorded,
+            response: RecordedResponse {
+                status,
+                headers: self.headers(&headers),
+                json,
+                body,
+            },
+        };
+        self.interactions.borrow_mut().push(interaction);
+        self.dirty.set(true);
+
+        // Hand the unscrubbed response to the caller.
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers.iter() {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(bytes.to_vec())?.into())
+    }
+}
+
+fn response(recorded: &RecordedResponse) -> Result<Response> {
+    let mut builder = http::Response::builder().status(recorded.status);
+    for (name, value) in &recorded.headers {
+        builder = builder.header(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    let body = match (&recorded.json, &recorded.body) {
+        (Some(json), _) => serde_json::to_vec(json)?,
+        (None, Some(body)) => body.as_bytes().to_vec(),
+        (None, None) => Vec::new(),
+    };
+    Ok(builder.body(body)?.into())
+}
+
+impl Transport for Cassette {
+    fn send(&self, request: Request) -> Result<Response> {
+        match &self.mode {
+            Mode::Replay => self.play(&request),
+            Mode::Record(inner) => self.capture(inner, request),
+        }
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        if self.dirty.get() {
+            let _ = self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Confluence, Executor, Method};
+    use crate::transport::{Expectation, MockTransport};
+    use serde_json::json;
+
+    #[test]
+    fn records_scrubbed_and_replays() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", std::process::id()));
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(
+            Expectation::new(Method::GET, "/rest/api/content/1")
+                .query("expand", "version")
+                .respond_json(200, &json!({ "id": "1", "host": "wiki.corp.example" }))
+                .header("Set-Cookie", "JSESSIONID=secret"),
+        );
+        {
+            let cassette = Rc::new(
+                Cassette::record(&path, mock)
+                    .scrub_text("wiki.corp.example", "confluence.example.com"),
+            );
+            let (headers, _, d) = Confluence::new("https://wiki.corp.example/rest/api")
+                .with_transport(cassette.clone())
+                .get()
+                .set_header(
+                    reqwest::header::AUTHORIZATION,
+                    HeaderValue::from_static("Bearer token"),
+                )
+                .content()
+                .content_id("1")
+                .expand("version")
+                .execute::<serde_json::Value>()
+                .unwrap();
+            assert_eq!(headers["set-cookie"], "JSESSIONID=secret");
+            assert_eq!(d.unwrap()["host"], "wiki.corp.example");
+        }
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(!written.contains("secret") && !written.contains("Bearer"));
+        assert!(!written.contains("wiki.corp.example"));
+
+        let cassette = Rc::new(Cassette::replay(&path).unwrap());
+        let confluence =
+            Confluence::new("https://confluence.example.com/rest/api").with_transport(cassette);
+        let (headers, status, d) = confluence
+            .get()
+            .content()
+            .content_id("1")
+            .expand("version")
+            .execute::<serde_json::Value>()
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(headers["set-cookie"], REDACTED);
+        assert_eq!(d.unwrap()["host"], "confluence.example.com");
+        assert!(confluence
+            .get()
+            .content()
+            .content_id("1")
+            .expand("version")
+            .execute::<serde_json::Value>()
+            .is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_matches_configured_headers() {
+        let path = std::env::temp_dir().join(format!("cassette-h-{}.json", std::process::id()));
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(Expectation::new(
+            Method::POST,
+            "/rest/api/content/1/child/attachment",
+        ));
+        let upload = |confluence: &Confluence| {
+            let part = reqwest::blocking::multipart::Part::bytes(b"hi".to_vec()).file_name("a");
+            confluence
+                .post(())
+                .content()
+                .content_id("1")
+                .child()
+                .attachment(reqwest::blocking::multipart::Form::new().part("file", part))
+                .execute::<serde_json::Value>()
+        };
+        let authenticated = || {
+            Confluence::builder("https://confluence.example.com/rest/api")
+                .with_bearer_auth("token")
+                .build()
+                .unwrap()
+        };
+        {
+            let cassette = Rc::new(Cassette::record(&path, mock));
+            upload(&authenticated().with_transport(cassette)).unwrap();
+        }
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("multipart/form-data; boundary=[boundary]"));
+
+        let replay = || {
+            Rc::new(
+                Cassette::replay(&path)
+                    .unwrap()
+                    .match_header("authorization")
+                    .match_header("content-type"),
+            )
+        };
+        let anonymous = Confluence::new("https://confluence.example.com/rest/api");
+        let error = upload(&anonymous.with_transport(replay()))
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with("(authorization: [REDACTED] recorded, none sent)"));
+        upload(&authenticated().with_transport(replay())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
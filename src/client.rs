@@ -1,5 +1,9 @@
+use crate::builder::{ConfluenceBuilder, DEFAULT_USER_AGENT};
+use crate::ratelimit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::transport::{ReqwestTransport, Transport};
 use crate::util::url_join;
-use reqwest::blocking::{Client, Request};
+use reqwest::blocking::{Client, Request, Response};
 pub use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
     Method, StatusCode,
@@ -8,25 +12,60 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>;
 
+/// The largest attachment a Confluence instance accepts unless its
+/// administrators changed it, 100 MiB.
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: u64 = 100 * 1024 * 1024;
+
 pub trait Executor {
     fn execute<T>(self) -> Result<(HeaderMap, StatusCode, Option<T>)>
     where
         T: DeserializeOwned;
 }
 
+/// The REST API a `Confluence` client talks to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// `/rest/api`, available on Server, Data Center and Cloud.
+    V1,
+    /// Cloud's `/wiki/api/v2`, see the `v2` module.
+    V2,
+}
+
 pub struct Confluence {
-    client: Rc<Client>,
+    pub(crate) client: Rc<Client>,
+    transport: Rc<dyn Transport>,
     host: String,
+    api_version: ApiVersion,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    headers: HeaderMap,
+    max_attachment_size: u64,
+    timeout: Option<Duration>,
+    upload_timeout: Option<Duration>,
+    #[cfg(feature = "tracing")]
+    logging: crate::trace::RequestLogging,
 }
 
 impl<'g> Clone for Confluence {
     fn clone(&self) -> Self {
         Self {
             client: Rc::clone(&self.client),
+            transport: Rc::clone(&self.transport),
             host: self.host.clone(),
+            api_version: self.api_version,
+            retry: self.retry.clone(),
+            limiter: self.limiter.clone(),
+            headers: self.headers.clone(),
+            max_attachment_size: self.max_attachment_size,
+            timeout: self.timeout,
+            upload_timeout: self.upload_timeout,
+            #[cfg(feature = "tracing")]
+            logging: self.logging.clone(),
         }
     }
 }
@@ -34,25 +73,213 @@ impl<'g> Clone for Confluence {
 new_type!(GetQueryBuilder);
 new_type!(PostQueryBuilder);
 new_type!(PutQueryBuilder);
+new_type!(DeleteQueryBuilder);
 new_type!(CustomQuery);
 exec!(CustomQuery);
 
 impl Confluence {
+    /// A client with the default settings. Panics if the HTTP client can't
+    /// be built; use `builder` to handle that and to configure it.
     pub fn new(host: &str) -> Self {
-        let client = Client::builder().cookie_store(true).build().unwrap();
-        Self {
-            client: Rc::new(client),
-            host: host.to_string(),
-        }
+        Self::builder(host).build().unwrap()
+    }
+
+    pub fn builder(host: &str) -> ConfluenceBuilder {
+        ConfluenceBuilder::new(host)
     }
 
     pub fn with_client(client: Rc<Client>, host: &str) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            HeaderValue::from_static(DEFAULT_USER_AGENT),
+        );
+        headers.insert(
+            reqwest::header::ACCEPT,
+            HeaderValue::from_static("application/json"),
+        );
         Self {
+            transport: Rc::new(ReqwestTransport::new(Rc::clone(&client))),
             client,
             host: host.to_string(),
+            api_version: ApiVersion::V1,
+            retry: RetryPolicy::none(),
+            limiter: None,
+            headers,
+            max_attachment_size: DEFAULT_MAX_ATTACHMENT_SIZE,
+            timeout: None,
+            upload_timeout: None,
+            #[cfg(feature = "tracing")]
+            logging: crate::trace::RequestLogging::default(),
         }
     }
 
+    /// Selects the REST API requests are sent to. `host` stays the v1 root,
+    /// e.g. `https://example.atlassian.net/wiki/rest/api`; with
+    /// `ApiVersion::V2` requests go to the matching `/wiki/api/v2`.
+    pub fn with_api_version(mut self, api_version: ApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    /// Retries transient failures of every request as `retry` allows. No
+    /// request is retried by default.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sends requests with `transport` instead of the `reqwest` client. The
+    /// client is still used to build multipart requests.
+    pub fn with_transport(mut self, transport: Rc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Throttles every request, retries included, with `limiter`.
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Logs headers and bodies of requests and responses, redacted, in
+    /// addition to the span of every request.
+    #[cfg(feature = "tracing")]
+    pub fn with_request_logging(mut self, logging: crate::trace::RequestLogging) -> Self {
+        self.logging = logging;
+        self
+    }
+
+    /// Replaces the headers every request starts out with.
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn default_headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The largest attachment in bytes the instance accepts, set under
+    /// General Configuration > Attachment Settings. Streamed uploads check
+    /// it before sending.
+    pub fn with_max_attachment_size(mut self, bytes: u64) -> Self {
+        self.max_attachment_size = bytes;
+        self
+    }
+
+    pub fn max_attachment_size(&self) -> u64 {
+        self.max_attachment_size
+    }
+
+    /// Bounds each request as a whole, from connecting until the response
+    /// is read. `None` leaves it to the HTTP client.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Like [`with_timeout`](Self::with_timeout), for requests with a
+    /// streamed body such as attachment uploads, which may take far longer
+    /// to send.
+    pub fn with_upload_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.upload_timeout = timeout;
+        self
+    }
+
+    pub fn rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.limiter.as_ref()
+    }
+
+    pub fn api_version(&self) -> ApiVersion {
+        self.api_version
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The root URL of the selected API.
+    pub fn base_url(&self) -> String {
+        match self.api_version {
+            ApiVersion::V1 => self.host.clone(),
+            ApiVersion::V2 => {
+                let host = self.host.trim_end_matches('/');
+                let site = host.strip_suffix("/rest/api").unwrap_or(host);
+                format!("{}/api/v2", site)
+            }
+        }
+    }
+
+    /// Fetches a download link relative to the site, such as an
+    /// attachment's `_links.download`.
+    pub fn download(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(self.download_stream(path)?.bytes()?.to_vec())
+    }
+
+    /// Like [`download`](Self::download), returning the response to read the
+    /// body from as it arrives.
+    pub fn download_stream(&self, path: &str) -> Result<Response> {
+        let host = self.host.trim_end_matches('/');
+        let site = host.strip_suffix("/rest/api").unwrap_or(host);
+        let url = reqwest::Url::parse(&format!("{}{}", site, path))
+            .map_err(|e| format!("Unable to download {}: {}", path, e))?;
+        let mut request = Request::new(Method::GET, url);
+        *request.headers_mut() = self.headers.clone();
+        request
+            .headers_mut()
+            .insert(reqwest::header::ACCEPT, HeaderValue::from_static("*/*"));
+        let res = self.send(request)?;
+        if !res.status().is_success() {
+            return Err(format!("Unable to download {}: {}", path, res.status()).into());
+        }
+        Ok(res)
+    }
+
+    /// Downloads `version` of the attachment `filename` of the page or blog
+    /// post `container_id`, or its current version.
+    pub fn download_attachment(
+        &self,
+        container_id: &str,
+        filename: &str,
+        version: Option<u64>,
+    ) -> Result<Vec<u8>> {
+        let mut path = format!(
+            "/download/attachments/{}/{}?api=v2",
+            container_id,
+            crate::util::encode_query(filename)
+        );
+        if let Some(version) = version {
+            path.push_str(&format!("&version={}", version));
+        }
+        self.download(&path)
+    }
+
+    /// Sends a request built by one of the query builders.
+    pub(crate) fn send(&self, mut request: Request) -> Result<Response> {
+        if request.timeout().is_none() {
+            let streamed = request.body().is_some_and(|b| b.as_bytes().is_none());
+            *request.timeout_mut() = if streamed {
+                self.upload_timeout
+            } else {
+                self.timeout
+            };
+        }
+        #[cfg(feature = "tracing")]
+        return crate::trace::traced(&self.logging, &self.retry, request, |req| self.attempt(req));
+        #[cfg(not(feature = "tracing"))]
+        self.retry.execute(request, |req| self.attempt(req))
+    }
+
+    fn attempt(&self, request: Request) -> Result<Response> {
+        let _permit = self.limiter.as_ref().map(|l| l.acquire());
+        self.transport.send(request)
+    }
+
     pub fn get(&self) -> GetQueryBuilder {
         self.into()
     }
@@ -97,12 +324,20 @@ impl Confluence {
 
         qb
     }
+
+    pub fn delete(&self) -> DeleteQueryBuilder {
+        self.into()
+    }
 }
 
 impl<'g> GetQueryBuilder<'g> {
     func_client!(custom_endpoint, CustomQuery, endpoint_str);
 
     func_client!(content, crate::content::get::Content<'g>);
+    func_client!(pages, crate::v2::get::Pages<'g>);
+    func_client!(blogposts, crate::v2::get::BlogPosts<'g>);
+    func_client!(attachments, crate::v2::get::Attachments<'g>);
+    func_client!(spaces, crate::v2::get::Spaces<'g>);
 
     pub fn set_header(
         mut self,
@@ -125,6 +360,9 @@ impl<'g> GetQueryBuilder<'g> {
 impl<'g> PostQueryBuilder<'g> {
     func_client!(custom_endpoint, CustomQuery, endpoint_str);
     func_client!(content, crate::content::post::Content<'g>);
+    func_client!(contentbody, crate::contentbody::post::ContentBody<'g>);
+    func_client!(pages, crate::v2::post::Pages<'g>);
+    func_client!(blogposts, crate::v2::post::BlogPosts<'g>);
 
     pub fn set_header(
         mut self,
@@ -147,6 +385,16 @@ impl<'g> PostQueryBuilder<'g> {
 impl<'g> PutQueryBuilder<'g> {
     func_client!(custom_endpoint, CustomQuery, endpoint_str);
     func_client!(content, crate::content::put::Content<'g>);
+    func_client!(pages, crate::v2::put::Pages<'g>);
+    func_client!(blogposts, crate::v2::put::BlogPosts<'g>);
+}
+
+impl<'g> DeleteQueryBuilder<'g> {
+    func_client!(custom_endpoint, CustomQuery, endpoint_str);
+    func_client!(content, crate::content::delete::Content<'g>);
+    func_client!(pages, crate::v2::delete::Pages<'g>);
+    func_client!(blogposts, crate::v2::delete::BlogPosts<'g>);
+    func_client!(attachments, crate::v2::delete::Attachments<'g>);
 }
 
 from!(
@@ -156,6 +404,8 @@ from!(
         => "POST"
     @PutQueryBuilder
         => "PUT"
+    @DeleteQueryBuilder
+        => "DELETE"
 );
 
 from!(
@@ -165,6 +415,8 @@ from!(
         => CustomQuery
     @PutQueryBuilder
         => CustomQuery
+    @DeleteQueryBuilder
+        => CustomQuery
 );
 
 impl<'a> CustomQuery<'a> {
@@ -189,19 +441,65 @@ impl<'a> CustomQuery<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cassette::Cassette;
     use crate::content::model::{Ancestor, ChildContentServiceResponse, Content, Results};
     use crate::model::*;
     use std::fs::File;
     use std::io::prelude::*;
     use std::io::BufReader;
 
-    fn setup_confluence_connection() -> Confluence {
-        Confluence::new("https://your.confluence.example.com/rest/api")
+    const HOST: &str = "https://your.confluence.example.com";
+
+    /// Replays `tests/cassettes/{cassette}.json`, checking that requests
+    /// are authenticated and carry the recorded user agent and content
+    /// type. With `CONFLUENCE_RECORD` set to a real `.../rest/api` root the
+    /// test runs against it instead, authenticated as the default profile,
+    /// and records a fresh cassette with the host replaced by the example
+    /// one.
+    fn setup_confluence_connection(cassette: &str) -> Confluence {
+        let path = format!(
+            "{}/tests/cassettes/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            cassette
+        );
+        match std::env::var("CONFLUENCE_RECORD") {
+            Ok(host) => {
+                let mut profile = crate::config::Config::discover()
+                    .and_then(|config| config.profile(None))
+                    .unwrap();
+                profile.host = Some(host.clone());
+                let confluence = profile.connect().unwrap();
+                let inner = Rc::new(ReqwestTransport::new(Rc::clone(&confluence.client)));
+                let root = host.trim_end_matches('/');
+                let site = root.trim_end_matches("/rest/api").trim_end_matches("/wiki");
+                let cassette = Cassette::record(path, inner)
+                    .scrub_text(root, &format!("{}/rest/api", HOST))
+                    .scrub_text(site, HOST);
+                confluence.with_transport(Rc::new(cassette))
+            }
+            Err(_) => {
+                let cassette = Cassette::replay(path)
+                    .unwrap()
+                    .match_header("authorization")
+                    .match_header("user-agent")
+                    .match_header("content-type")
+                    .match_header("x-atlassian-token");
+                Confluence::builder(&format!("{}/rest/api", HOST))
+                    .with_bearer_auth("replayed")
+                    .build()
+                    .unwrap()
+                    .with_transport(Rc::new(cassette))
+            }
+        }
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
     }
 
     #[test]
     fn list_content() {
-        let (_, status, d) = setup_confluence_connection()
+        let (_, status, d) = setup_confluence_connection("list_content")
             .get()
             .custom_endpoint("content")
             .execute::<serde_json::Value>()
@@ -213,7 +511,7 @@ mod tests {
 
     #[test]
     fn list_child_pages() {
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("list_child_pages")
             .get()
             .content()
             .content_id("205613650")
@@ -228,7 +526,7 @@ mod tests {
 
     #[test]
     fn find_page() {
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("find_page")
             .get()
             .content()
             .space_key("ICF")
@@ -243,7 +541,7 @@ mod tests {
 
     #[test]
     fn find_child_page() {
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("find_child_page")
             .get()
             .content()
             .content_id("205618124")
@@ -258,7 +556,7 @@ mod tests {
 
     #[test]
     fn find_attachments() {
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("find_attachments")
             .get()
             .content()
             .content_id("205618124")
@@ -273,7 +571,7 @@ mod tests {
 
     #[test]
     fn find_attachments_by() {
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("find_attachments_by")
             .get()
             .content()
             .content_id("205618124")
@@ -293,10 +591,10 @@ mod tests {
             "page",
             "Hello World",
             Space::new("~john.doe@example.com"),
-            Body::new(Storage::new("no text", "storage")),
+            Body::new(Storage::new("no text", Representation::Storage)),
             None,
         );
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("create_page")
             .post(request)
             .content()
             .execute::<serde_json::Value>()
@@ -313,12 +611,12 @@ mod tests {
             "page",
             "Once Upon",
             Space::new("~john.doe@example.com"),
-            Body::new(Storage::new("no text", "storage")),
+            Body::new(Storage::new("no text", Representation::Storage)),
             None,
             Version::new(2),
         );
 
-        let (headers, status, d) = setup_confluence_connection()
+        let (headers, status, d) = setup_confluence_connection("update_page")
             .put(request)
             .content()
             .content_id("205618124")
@@ -335,10 +633,10 @@ mod tests {
             "page",
             "Hello Foo",
             Space::new("~john.doe@example.com"),
-            Body::new(Storage::new("no text", "storage")),
+            Body::new(Storage::new("no text", Representation::Storage)),
             Some(vec![Ancestor::new("205618124")]),
         );
-        let (_, status, d) = setup_confluence_connection()
+        let (_, status, d) = setup_confluence_connection("create_child_page")
             .post(request)
             .content()
             .execute::<serde_json::Value>()
@@ -348,14 +646,20 @@ mod tests {
         assert!(d.is_some());
     }
 
+    #[test]
+    fn convert_wiki_to_storage() {
+        let storage = setup_confluence_connection("convert_wiki_to_storage")
+            .convert(Representation::Wiki, Representation::Storage, "h1. Hello")
+            .unwrap();
+
+        assert_eq!(storage.representation, Representation::Storage);
+        assert_eq!(storage.value, "<h1>Hello</h1>");
+    }
+
     #[test]
     fn upload_attachment() {
-        let request = UploadAttachmentRequest::new(
-            "205618124",
-            "sequence.png",
-            "/absolute/path/attachment/sequence.png",
-            "",
-        );
+        let request =
+            UploadAttachmentRequest::new("205618124", "sequence.png", &fixture("sequence.png"), "");
         let file = File::open(request.file.clone()).unwrap();
         let mut buf_reader = BufReader::new(file);
         let mut content = Vec::new();
@@ -367,7 +671,7 @@ mod tests {
             .unwrap();
 
         let form = reqwest::blocking::multipart::Form::new().part("file", part);
-        let (_, status, d) = setup_confluence_connection()
+        let (_, status, d) = setup_confluence_connection("upload_attachment")
             .post(request)
             .content()
             .content_id("205618124")
@@ -377,17 +681,14 @@ mod tests {
             .unwrap();
 
         assert_eq!(status, reqwest::StatusCode::OK);
-        assert!(d.is_some());
+        let size = std::fs::metadata(fixture("sequence.png")).unwrap().len();
+        assert_eq!(d.unwrap()["results"][0]["extensions"]["fileSize"], size);
     }
 
     #[test]
     fn update_attachment() {
-        let request = UploadAttachmentRequest::new(
-            "205618124",
-            "sequence.png",
-            "/absolute/path/attachment/sequence.png",
-            "",
-        );
+        let request =
+            UploadAttachmentRequest::new("205618124", "sequence.png", &fixture("sequence.png"), "");
         let file = File::open(request.file.clone()).unwrap();
         let mut buf_reader = BufReader::new(file);
         let mut content = Vec::new();
@@ -399,7 +700,7 @@ mod tests {
             .unwrap();
 
         let form = reqwest::blocking::multipart::Form::new().part("file", part);
-        let (_, status, d) = setup_confluence_connection()
+        let (_, status, d) = setup_confluence_connection("update_attachment")
             .post(request)
             .content()
             .content_id("205618124")
@@ -411,6 +712,7 @@ mod tests {
             .unwrap();
 
         assert_eq!(status, reqwest::StatusCode::OK);
-        assert!(d.is_some());
+        let size = std::fs::metadata(fixture("sequence.png")).unwrap().len();
+        assert_eq!(d.unwrap()["extensions"]["fileSize"], size);
     }
 }
@@ -0,0 +1,440 @@
+//! Named connection profiles read from a TOML file, with `CONFLUENCE_*`
+//! environment overrides.
+//!
+//! ```toml
+//! default_profile = "work"
+//!
+//! [profiles.work]
+//! host = "https://wiki.example.com"
+//! context_path = "/confluence"
+//! default_space = "DOCS"
+//! proxy = "http://proxy.example.com:3128"
+//! connect_timeout = 5
+//! read_timeout = 60
+//! max_attachment_size = 524288000
+//!
+//! [profiles.work.auth]
+//! method = "basic"
+//! username = "jo"
+//! password = { env = "WIKI_PASSWORD" }
+//!
+//! [profiles.cloud]
+//! host = "https://example.atlassian.net/wiki"
+//! auth = { method = "bearer", token = { file = "/run/secrets/confluence" } }
+//! ```
+//!
+//! Secrets are given inline, or better as `{ env = "NAME" }` or
+//! `{ file = "path" }` and only read when connecting. The file is looked up
+//! at `$CONFLUENCE_CONFIG`, else `$XDG_CONFIG_HOME/confluence/config.toml`
+//! or `~/.config/confluence/config.toml`; without one, the profile is made
+//! from the environment alone.
+//!
+//! The profile is the one named, else `$CONFLUENCE_PROFILE`, else
+//! `default_profile`, else `default`. These variables override its settings:
+//!
+//! | Variable | Setting |
+//! |---|---|
+//! | `CONFLUENCE_HOST` | `host` |
+//! | `CONFLUENCE_CONTEXT_PATH` | `context_path` |
+//! | `CONFLUENCE_DEFAULT_SPACE` | `default_space` |
+//! | `CONFLUENCE_PROXY`, `CONFLUENCE_NO_PROXY` | `proxy`, `no_proxy` |
+//! | `CONFLUENCE_CONNECT_TIMEOUT`, `CONFLUENCE_READ_TIMEOUT` | timeouts in seconds |
+//! | `CONFLUENCE_AUTH` | `auth.method`: `none`, `basic` or `bearer` |
+//! | `CONFLUENCE_USERNAME` | `auth.username` |
+//! | `CONFLUENCE_PASSWORD`, `CONFLUENCE_PASSWORD_FILE` | `auth.password` |
+//! | `CONFLUENCE_TOKEN`, `CONFLUENCE_TOKEN_FILE` | `auth.token` |
+//!
+//! Without `CONFLUENCE_AUTH`, a token selects bearer and a username or
+//! password basic authentication.
+//!
+//! ```no_run
+//! let confluence = confluence::config::connect(Some("work")).unwrap();
+//! ```
+
+use crate::builder::ConfluenceBuilder;
+use crate::client::{Confluence, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Connects with the profile `name`, see the module documentation.
+pub fn connect(name: Option<&str>) -> Result<Confluence> {
+    Config::discover()?.profile(name)?.connect()
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The profile used unless another one is named.
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    pub fn parse(toml: &str) -> Result<Config> {
+        toml::from_str(toml).map_err(|e| format!("Unable to parse config: {}", e).into())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let toml = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        toml::from_str(&toml)
+            .map_err(|e| format!("Unable to parse {}: {}", path.display(), e).into())
+    }
+
+    /// Where the config file is looked up.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("CONFLUENCE_CONFIG") {
+            return Some(path.into());
+        }
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_dir.join("confluence").join("config.toml"))
+    }
+
+    /// Loads the file at [`Config::default_path`]. Only a file named by
+    /// `CONFLUENCE_CONFIG` has to exist.
+    pub fn discover() -> Result<Config> {
+        match Self::default_path() {
+            Some(path) if path.exists() || std::env::var_os("CONFLUENCE_CONFIG").is_some() => {
+                Self::load(path)
+            }
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// The profile `name`, or the default one, with the environment
+    /// overrides applied.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        self.resolve(name, &|var| std::env::var(var).ok())
+    }
+
+    fn resolve(&self, name: Option<&str>, env: &dyn Fn(&str) -> Option<String>) -> Result<Profile> {
+        let name = name
+            .map(str::to_string)
+            .or_else(|| env("CONFLUENCE_PROFILE"))
+            .or_else(|| self.default_profile.clone());
+        let profile = match name {
+            Some(name) => self
+                .profiles
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| format!("Unable to find profile {}", name))?,
+            None => self.profiles.get("default").cloned().unwrap_or_default(),
+        };
+        profile.with_env(env)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// The base URL, e.g. `https://wiki.example.com`.
+    pub host: Option<String>,
+    /// Where Confluence is deployed on the host, e.g. `/confluence`.
+    pub context_path: Option<String>,
+    pub auth: Auth,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    /// In seconds.
+    pub connect_timeout: Option<u64>,
+    /// In seconds, for each request as a whole; uploads aren't bounded.
+    pub read_timeout: Option<u64>,
+    pub default_space: Option<String>,
+    /// In bytes, see [`Confluence::with_max_attachment_size`].
+    pub max_attachment_size: Option<u64>,
+}
+
+impl Profile {
+    /// The REST API of the profile's host, e.g.
+    /// `https://wiki.example.com/confluence/rest/api`.
+    pub fn base_url(&self) -> Result<String> {
+        let host = self
+            .host
+            .as_deref()
+            .ok_or("Unable to connect: no host configured, set host or CONFLUENCE_HOST")?
+            .trim_end_matches('/');
+        if host.ends_with("/rest/api") {
+            return Ok(host.to_string());
+        }
+        let context_path = self.context_path.as_deref().unwrap_or("").trim_matches('/');
+        if context_path.is_empty() {
+            Ok(format!("{}/rest/api", host))
+        } else {
+            Ok(format!("{}/{}/rest/api", host, context_path))
+        }
+    }
+
+    pub fn default_space(&self) -> Option<&str> {
+        self.default_space.as_deref()
+    }
+
+    /// A builder with the profile's settings, for further configuration.
+    /// Secrets are read here.
+    pub fn builder(&self) -> Result<ConfluenceBuilder> {
+        let mut builder = ConfluenceBuilder::new(&self.base_url()?);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.with_proxy(proxy);
+        }
+        if let Some(hosts) = &self.no_proxy {
+            builder = builder.with_no_proxy(hosts);
+        }
+        if let Some(secs) = self.connect_timeout {
+            builder = builder.with_connect_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.read_timeout {
+            builder = builder.with_read_timeout(Duration::from_secs(secs));
+        }
+        if let Some(bytes) = self.max_attachment_size {
+            builder = builder.with_max_attachment_size(bytes);
+        }
+        Ok(match &self.auth {
+            Auth::None => builder,
+            Auth::Basic { username, password } => {
+                builder.with_basic_auth(username, &password.resolve()?)
+            }
+            Auth::Bearer { token } => builder.with_bearer_auth(&token.resolve()?),
+        })
+    }
+
+    pub fn connect(&self) -> Result<Confluence> {
+        self.builder()?.build()
+    }
+
+    fn with_env(mut self, env: &dyn Fn(&str) -> Option<String>) -> Result<Profile> {
+        let set = |field: &mut Option<String>, var: &str| {
+            if let Some(value) = env(var) {
+                *field = Some(value);
+            }
+        };
+        set(&mut self.host, "CONFLUENCE_HOST");
+        set(&mut self.context_path, "CONFLUENCE_CONTEXT_PATH");
+        set(&mut self.default_space, "CONFLUENCE_DEFAULT_SPACE");
+        set(&mut self.proxy, "CONFLUENCE_PROXY");
+        set(&mut self.no_proxy, "CONFLUENCE_NO_PROXY");
+        let seconds = |var: &str| -> Result<Option<u64>> {
+            env(var)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|e| format!("Unable to parse {}: {}", var, e).into())
+                })
+                .transpose()
+        };
+        if let Some(secs) = seconds("CONFLUENCE_CONNECT_TIMEOUT")? {
+            self.connect_timeout = Some(secs);
+        }
+        if let Some(secs) = seconds("CONFLUENCE_READ_TIMEOUT")? {
+            self.read_timeout = Some(secs);
+        }
+        self.auth = self.auth.with_env(env)?;
+        Ok(self)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(tag = "method", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Auth {
+    /// Anonymous, or authenticated by other means such as a session cookie.
+    #[default]
+    None,
+    /// A username and password, or an API token on Confluence Cloud.
+    Basic { username: String, password: Secret },
+    /// A personal access token.
+    Bearer { token: Secret },
+}
+
+impl Auth {
+    fn with_env(self, env: &dyn Fn(&str) -> Option<String>) -> Result<Auth> {
+        let secret = |var: &str| {
+            env(var).map(Secret::Value).or_else(|| {
+                env(&format!("{}_FILE", var)).map(|file| Secret::File { file: file.into() })
+            })
+        };
+        let username = env("CONFLUENCE_USERNAME");
+        let password = secret("CONFLUENCE_PASSWORD");
+        let token = secret("CONFLUENCE_TOKEN");
+        let method = match env("CONFLUENCE_AUTH") {
+            Some(method) => method.to_ascii_lowercase(),
+            None if token.is_some() => "bearer".to_string(),
+            None if username.is_some() || password.is_some() => "basic".to_string(),
+            None => return Ok(self),
+        };
+
+        let (current_username, current_password, current_token) = match self {
+            Auth::None => (None, None, None),
+            Auth::Basic { username, password } => (Some(username), Some(password), None),
+            Auth::Bearer { token } => (None, None, Some(token)),
+        };
+        match method.as_str() {
+            "none" => Ok(Auth::None),
+            "basic" => Ok(Auth::Basic {
+                username: username
+                    .or(current_username)
+                    .ok_or("Unable to use basic auth: CONFLUENCE_USERNAME is not set")?,
+                password: password
+                    .or(current_password)
+                    .ok_or("Unable to use basic auth: CONFLUENCE_PASSWORD is not set")?,
+            }),
+            "bearer" => Ok(Auth::Bearer {
+                token: token
+                    .or(current_token)
+                    .ok_or("Unable to use bearer auth: CONFLUENCE_TOKEN is not set")?,
+            }),
+            other => Err(format!("Unable to use auth method {}", other).into()),
+        }
+    }
+}
+
+/// A password or token, given inline or read from elsewhere when
+/// connecting.
+#[derive(Clone, Deserialize, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Secret {
+    /// Read from an environment variable.
+    Env {
+        env: String,
+    },
+    /// Read from a file, without the trailing newline.
+    File {
+        file: PathBuf,
+    },
+    Value(String),
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Secret::Env { env } => std::env::var(env)
+                .map_err(|e| format!("Unable to read secret from {}: {}", env, e).into()),
+            Secret::File { file } => std::fs::read_to_string(file)
+                .map(|secret| secret.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| {
+                    format!("Unable to read secret from {}: {}", file.display(), e).into()
+                }),
+            Secret::Value(value) => Ok(value.clone()),
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Secret::Env { env } => write!(f, "Env({})", env),
+            Secret::File { file } => write!(f, "File({})", file.display()),
+            Secret::Value(_) => write!(f, "Value([REDACTED])"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Executor, Method};
+    use crate::transport::{Expectation, MockTransport};
+    use std::rc::Rc;
+
+    const CONFIG: &str = r#"
+        default_profile = "work"
+
+        [profiles.work]
+        host = "https://wiki.example.com/"
+        context_path = "/confluence"
+        default_space = "DOCS"
+        connect_timeout = 5
+        auth = { method = "basic", username = "jo", password = { env = "WIKI_PASSWORD" } }
+
+        [profiles.cloud]
+        host = "https://example.atlassian.net/wiki/rest/api"
+        auth = { method = "bearer", token = "abc" }
+    "#;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn profiles_are_selected_and_overridden() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        let work = config.resolve(None, &env(&[])).unwrap();
+        assert_eq!(
+            work.base_url().unwrap(),
+            "https://wiki.example.com/confluence/rest/api"
+        );
+        assert_eq!(work.default_space(), Some("DOCS"));
+        assert_eq!(work.connect_timeout, Some(5));
+        assert_eq!(
+            work.auth,
+            Auth::Basic {
+                username: "jo".to_string(),
+                password: Secret::Env {
+                    env: "WIKI_PASSWORD".to_string()
+                }
+            }
+        );
+
+        let vars = &[
+            ("CONFLUENCE_PROFILE", "cloud"),
+            ("CONFLUENCE_DEFAULT_SPACE", "OPS"),
+            ("CONFLUENCE_TOKEN", "xyz"),
+        ];
+        let cloud = config.resolve(None, &env(vars)).unwrap();
+        assert_eq!(
+            cloud.base_url().unwrap(),
+            "https://example.atlassian.net/wiki/rest/api"
+        );
+        assert_eq!(cloud.default_space(), Some("OPS"));
+        assert_eq!(
+            cloud.auth,
+            Auth::Bearer {
+                token: Secret::Value("xyz".to_string())
+            }
+        );
+
+        let vars = &[("CONFLUENCE_USERNAME", "kim")];
+        let err = config.resolve(Some("cloud"), &env(vars)).unwrap_err();
+        assert!(err.to_string().contains("CONFLUENCE_PASSWORD is not set"));
+        let err = config.resolve(Some("home"), &env(&[])).unwrap_err();
+        assert_eq!(err.to_string(), "Unable to find profile home");
+        let err = Config::parse("[profiles.x]\nhots = \"h\"").unwrap_err();
+        assert!(err.to_string().starts_with("Unable to parse config"));
+    }
+
+    #[test]
+    fn environment_alone_is_enough() {
+        let path = std::env::temp_dir().join(format!("confluence-token-{}", std::process::id()));
+        std::fs::write(&path, "s3cret\n").unwrap();
+        let vars = [
+            ("CONFLUENCE_HOST", "https://wiki.example.com"),
+            ("CONFLUENCE_USERNAME", "jo"),
+            ("CONFLUENCE_PASSWORD_FILE", path.to_str().unwrap()),
+        ];
+        let profile = Config::default().resolve(None, &env(&vars)).unwrap();
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(Expectation::new(Method::GET, "/rest/api/space"));
+        let confluence = profile.connect().unwrap().with_transport(mock.clone());
+        std::fs::remove_file(&path).unwrap();
+
+        confluence
+            .get()
+            .custom_endpoint("space")
+            .execute::<serde_json::Value>()
+            .unwrap();
+        // base64("jo:s3cret")
+        assert_eq!(
+            mock.requests()[0].headers["authorization"],
+            "Basic am86czNjcmV0"
+        );
+        assert!(!format!("{:?}", Secret::Value("s3cret".into())).contains("s3cret"));
+    }
+}
@@ -0,0 +1,36 @@
+imports!();
+
+use crate::client::DeleteQueryBuilder;
+
+new_type!(
+    Content
+    Id
+    Version
+    VersionNumber
+);
+
+from!(
+    @DeleteQueryBuilder
+        -> Content = "content"
+    @Content
+        => Id
+    @Id
+        -> Version = "version"
+    @Version
+        => VersionNumber
+);
+
+impl_macro!(
+    @Content
+        |
+        |=> content_id -> Id = content_id_str
+    @Id
+        |=> version -> Version
+        |
+    @Version
+        |
+        |=> version_number -> VersionNumber = version_number_str
+);
+
+exec!(Id);
+exec!(VersionNumber);
@@ -11,6 +11,7 @@ new_type!(
     Title
     SpaceKey
     Filename
+    Version
 );
 
 from!(
@@ -26,6 +27,10 @@ from!(
         ?> SpaceKey = "spaceKey"
     @Id
         -> Child = "child"
+    @Id
+        ?> Expand = "expand"
+    @Id
+        -> Version = "version"
     @Child
         ?> Expand = "expand"
     @Child
@@ -44,7 +49,9 @@ impl_macro!(
         |?> space_key -> SpaceKey = space_key_str
     @Id
         |=> child -> Child
+        |=> version -> Version
         |
+        |?> expand -> Expand = expand
     @Child
         |=> attachment -> Attachment
         |
@@ -60,6 +67,8 @@ impl_macro!(
 );
 
 exec!(Content);
+exec!(Id);
 exec!(Expand);
 exec!(Attachment);
 exec!(Filename);
+exec!(Version);
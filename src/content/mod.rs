@@ -1,3 +1,4 @@
+pub mod delete;
 pub mod get;
 pub mod model;
 pub mod post;
@@ -1,3 +1,4 @@
+use crate::model::Representation;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -59,9 +60,14 @@ pub struct UserExpandable {
 pub struct User {
     #[serde(rename = "type")]
     pub user_type: String,
+    /// Empty on Cloud, which identifies users by `account_id`.
+    #[serde(default)]
     pub username: String,
-    #[serde(rename = "userKey")]
+    #[serde(rename = "userKey", default)]
     pub user_key: String,
+    /// Only set on Cloud.
+    #[serde(rename = "accountId", default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
     #[serde(rename = "profilePicture")]
     pub profile_picture: ProfilePicture,
     #[serde(rename = "displayName")]
@@ -72,6 +78,14 @@ pub struct User {
     pub expandable: UserExpandable,
 }
 
+impl User {
+    /// The id other requests refer to the user by: the account id on Cloud,
+    /// the username on Server and Data Center.
+    pub fn id(&self) -> &str {
+        self.account_id.as_deref().unwrap_or(&self.username)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ContentVersionExpandable {
     pub content: String,
@@ -83,8 +97,10 @@ pub struct ContentVersion {
     pub when: String,
     pub message: Option<String>,
     pub number: u64,
-    #[serde(rename = "minorEdit")]
+    #[serde(rename = "minorEdit", default)]
     pub minor_edit: bool,
+    /// Not listed by `content/{id}/version` on Cloud.
+    #[serde(default)]
     pub hidden: bool,
 }
 
@@ -183,7 +199,7 @@ pub struct ContentServiceLinks {
     pub webui: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Ancestor {
     pub id: String,
 }
@@ -212,7 +228,7 @@ pub struct ContentServiceBodyStorageExpandable {
 pub struct ContentServiceBodyStorage {
     #[serde(rename = "_expandable")]
     pub expandable: ContentServiceBodyStorageExpandable,
-    pub representation: String,
+    pub representation: Representation,
     pub value: String,
 }
 
@@ -301,6 +317,9 @@ pub struct ContentServiceBody {
     #[serde(rename = "_expandable")]
     pub expandable: ContentServiceBodyExpandable,
     pub storage: ContentServiceBodyStorage,
+    /// Present when the body was expanded as `body.atlas_doc_format`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atlas_doc_format: Option<ContentServiceBodyStorage>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
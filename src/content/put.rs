@@ -3,6 +3,9 @@ imports!();
 use crate::client::PutQueryBuilder;
 
 new_type!(
+    Attachment
+    AttachmentId
+    Child
     Content
     Id
 );
@@ -12,13 +15,29 @@ from!(
         -> Content = "content"
     @Content
         => Id
+    @Id
+        -> Child = "child"
+    @Child
+        -> Attachment = "attachment"
+    @Attachment
+        => AttachmentId
 );
 
 impl_macro!(
     @Content
         |
         |=> content_id -> Id = content_id_str
+    @Id
+        |=> child -> Child
+        |
+    @Child
+        |=> attachment -> Attachment
+        |
+    @Attachment
+        |
+        |=> attachment_id -> AttachmentId = attachment_id_str
 );
 
 exec!(Content);
 exec!(Id);
+exec!(AttachmentId);
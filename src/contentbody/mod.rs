@@ -0,0 +1,22 @@
+pub mod post;
+
+use crate::client::{Confluence, Executor, Result};
+use crate::model::{Representation, Storage};
+
+impl Confluence {
+    /// Converts a content body from one representation into another using
+    /// `/contentbody/convert/{to}`, e.g. wiki markup into storage format or
+    /// storage format into `view` HTML.
+    pub fn convert(&self, from: Representation, to: Representation, body: &str) -> Result<Storage> {
+        let (_, status, d) = self
+            .post(Storage::new(body, from.clone()))
+            .contentbody()
+            .convert()
+            .to(to.as_str())
+            .execute::<Storage>()?;
+        match d {
+            Some(storage) if status.is_success() => Ok(storage),
+            _ => Err(format!("Unable to convert {} to {}: {}", from, to, status).into()),
+        }
+    }
+}
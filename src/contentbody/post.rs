@@ -0,0 +1,29 @@
+imports!();
+
+use crate::client::PostQueryBuilder;
+
+new_type!(
+    ContentBody
+    Convert
+    To
+);
+
+from!(
+    @PostQueryBuilder
+        -> ContentBody = "contentbody"
+    @ContentBody
+        -> Convert = "convert"
+    @Convert
+        => To
+);
+
+impl_macro!(
+    @ContentBody
+        |=> convert -> Convert
+        |
+    @Convert
+        |
+        |=> to -> To = representation_str
+);
+
+exec!(To);
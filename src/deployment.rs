@@ -0,0 +1,766 @@
+//! A common API over Server/Data Center and Cloud.
+//!
+//! The two deployments differ in endpoints (v1 vs v2), identifiers (numeric
+//! ids and account ids on Cloud, usernames on Data Center) and URL layout.
+//! [`ContentApi`] hides those differences for the usual page and attachment
+//! operations; [`DeploymentClient`] implements it for either deployment,
+//! which [`Deployment::detect`] works out from the instance's system info.
+//!
+//! ```no_run
+//! use confluence::client::Confluence;
+//! use confluence::deployment::{ContentApi, DeploymentClient, NewPage};
+//! use confluence::model::{Representation, Storage};
+//!
+//! let api = DeploymentClient::detect(&Confluence::new("https://example.atlassian.net/wiki/rest/api")).unwrap();
+//! let page = api
+//!     .create_page(&NewPage::new(
+//!         "DOC",
+//!         "Release notes",
+//!         Storage::new("<p>Hello</p>", Representation::Storage),
+//!         None,
+//!     ))
+//!     .unwrap();
+//! println!("created {} in a {:?} instance", page.id, api.deployment());
+//! ```
+
+use crate::attachment::file_part;
+use crate::client::{ApiVersion, Confluence, Executor, Result};
+use crate::content::model::Ancestor;
+use crate::model::{Body, CreatePageRequest, Space, Storage, UpdatePageRequest, Version};
+use crate::util::{check, get_all, parse};
+use crate::v2;
+use crate::v2::model::{BodyWrite, MultiEntityResult, VersionWrite};
+use reqwest::blocking::multipart::Form;
+use serde::Deserialize;
+
+/// The kind of Confluence instance a client talks to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Deployment {
+    /// Server or Data Center: v1 API only, users identified by username.
+    DataCenter,
+    /// Cloud: v2 API for content, users identified by account id.
+    Cloud,
+}
+
+#[derive(Deserialize, Debug)]
+struct SystemInfo {
+    #[serde(rename = "cloudId")]
+    cloud_id: Option<String>,
+}
+
+impl Deployment {
+    /// Asks the instance for its system info, which only Cloud serves, and
+    /// falls back to looking at the host name.
+    pub fn detect(confluence: &Confluence) -> Result<Self> {
+        let (_, status, info) = confluence
+            .get()
+            .custom_endpoint("settings/systemInfo")
+            .execute::<SystemInfo>()?;
+        match info {
+            Some(SystemInfo { cloud_id: Some(_) }) if status.is_success() => Ok(Deployment::Cloud),
+            _ => Ok(Self::from_host(confluence.host())),
+        }
+    }
+
+    fn from_host(host: &str) -> Self {
+        let site = reqwest::Url::parse(host)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.to_string()))
+            .unwrap_or_default();
+        if site.ends_with(".atlassian.net") || site.ends_with(".jira.com") {
+            Deployment::Cloud
+        } else {
+            Deployment::DataCenter
+        }
+    }
+
+    /// The key of a user's personal space, given the user's id as returned
+    /// by `content::model::User::id`.
+    pub fn personal_space_key(&self, user_id: &str) -> String {
+        format!("~{}", user_id)
+    }
+}
+
+/// A page as returned by [`ContentApi`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub id: String,
+    pub title: String,
+    pub version: u64,
+    pub parent_id: Option<String>,
+    /// The storage format body, when it was fetched.
+    pub body: Option<String>,
+}
+
+/// An attachment as returned by [`ContentApi`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub id: String,
+    pub title: String,
+    pub version: u64,
+    pub media_type: Option<String>,
+    pub file_size: Option<u64>,
+    /// Download path relative to the site's base URL.
+    pub download: Option<String>,
+}
+
+/// The content of a page to create or update.
+#[derive(Debug)]
+pub struct NewPage {
+    pub space_key: String,
+    pub title: String,
+    pub body: Storage,
+    pub parent_id: Option<String>,
+}
+
+impl NewPage {
+    pub fn new(space_key: &str, title: &str, body: Storage, parent_id: Option<&str>) -> Self {
+        Self {
+            space_key: space_key.to_string(),
+            title: title.to_string(),
+            body,
+            parent_id: parent_id.map(|p| p.to_string()),
+        }
+    }
+}
+
+/// Page and attachment operations supported by every deployment.
+pub trait ContentApi {
+    fn deployment(&self) -> Deployment;
+
+    /// Fetches a page including its storage format body.
+    fn get_page(&self, id: &str) -> Result<Page>;
+
+    fn find_page(&self, space_key: &str, title: &str) -> Result<Option<Page>>;
+
+    fn create_page(&self, page: &NewPage) -> Result<Page>;
+
+    /// Replaces a page's content; `version` is the new version number.
+    fn update_page(&self, id: &str, page: &NewPage, version: u64) -> Result<Page>;
+
+    fn delete_page(&self, id: &str) -> Result<()>;
+
+    fn attachments(&self, page_id: &str) -> Result<Vec<Attachment>>;
+
+    fn upload_attachment(
+        &self,
+        page_id: &str,
+        filename: &str,
+        data: Vec<u8>,
+        media_type: &str,
+    ) -> Result<Attachment>;
+
+    fn delete_attachment(&self, id: &str) -> Result<()>;
+}
+
+/// Dispatches [`ContentApi`] calls to the endpoints of a deployment.
+pub struct DeploymentClient {
+    deployment: Deployment,
+    v1: Confluence,
+    v2: Confluence,
+}
+
+impl DeploymentClient {
+    /// `confluence` must point at the v1 REST root, e.g.
+    /// `https://example.atlassian.net/wiki/rest/api` on Cloud.
+    pub fn new(confluence: &Confluence, deployment: Deployment) -> Self {
+        Self {
+            deployment,
+            v1: confluence.clone().with_api_version(ApiVersion::V1),
+            v2: confluence.clone().with_api_version(ApiVersion::V2),
+        }
+    }
+
+    pub fn detect(confluence: &Confluence) -> Result<Self> {
+        Ok(Self::new(confluence, Deployment::detect(confluence)?))
+    }
+
+    fn space_id(&self, space_key: &str) -> Result<String> {
+        let (_, status, d) = self
+            .v2
+            .get()
+            .spaces()
+            .keys(space_key)
+            .execute::<MultiEntityResult<v2::model::Space>>()?;
+        check(status, "find space")?;
+        d.and_then(|r| r.results.into_iter().next())
+            .map(|s| s.id)
+            .ok_or_else(|| format!("No space with key {}", space_key).into())
+    }
+}
+
+const V1_PAGE_EXPAND: &str = "body.storage,version,ancestors";
+
+#[derive(Deserialize, Debug)]
+struct V1Results<T> {
+    results: Vec<T>,
+}
+
+#[derive(Deserialize, Debug)]
+struct V1Version {
+    number: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct V1Storage {
+    value: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct V1Body {
+    storage: Option<V1Storage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct V1Extensions {
+    #[serde(rename = "mediaType")]
+    media_type: Option<String>,
+    #[serde(rename = "fileSize")]
+    file_size: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct V1Links {
+    download: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct V1Content {
+    id: String,
+    title: String,
+    version: Option<V1Version>,
+    ancestors: Option<Vec<Ancestor>>,
+    body: Option<V1Body>,
+    extensions: Option<V1Extensions>,
+    #[serde(rename = "_links")]
+    links: Option<V1Links>,
+}
+
+impl From<V1Content> for Page {
+    fn from(c: V1Content) -> Self {
+        Self {
+            id: c.id,
+            title: c.title,
+            version: c.version.map(|v| v.number).unwrap_or(1),
+            parent_id: c.ancestors.and_then(|a| a.last().map(|a| a.id.clone())),
+            body: c.body.and_then(|b| b.storage).map(|s| s.value),
+        }
+    }
+}
+
+impl From<V1Content> for Attachment {
+    fn from(c: V1Content) -> Self {
+        let (media_type, file_size) = c
+            .extensions
+            .map(|e| (e.media_type, e.file_size))
+            .unwrap_or_default();
+        Self {
+            id: c.id,
+            title: c.title,
+            version: c.version.map(|v| v.number).unwrap_or(1),
+            media_type,
+            file_size,
+            download: c.links.and_then(|l| l.download),
+        }
+    }
+}
+
+impl From<v2::model::Page> for Page {
+    fn from(p: v2::model::Page) -> Self {
+        Self {
+            id: p.id,
+            title: p.title,
+            version: p.version.map(|v| v.number).unwrap_or(1),
+            parent_id: p.parent_id,
+            body: p.body.and_then(|b| b.storage).map(|s| s.value),
+        }
+    }
+}
+
+impl From<v2::model::Attachment> for Attachment {
+    fn from(a: v2::model::Attachment) -> Self {
+        Self {
+            id: a.id,
+            title: a.title,
+            version: a.version.map(|v| v.number).unwrap_or(1),
+            media_type: a.media_type,
+            file_size: a.file_size,
+            download: a.download_link,
+        }
+    }
+}
+
+impl ContentApi for DeploymentClient {
+    fn deployment(&self) -> Deployment {
+        self.deployment
+    }
+
+    fn get_page(&self, id: &str) -> Result<Page> {
+        match self.deployment {
+            Deployment::DataCenter => {
+                let (_, status, d) = self
+                    .v1
+                    .get()
+                    .content()
+                    .content_id(id)
+                    .expand(V1_PAGE_EXPAND)
+                    .execute::<V1Content>()?;
+                parse(status, d, "get page").map(Page::from)
+            }
+            Deployment::Cloud => {
+                let (_, status, d) = self
+                    .v2
+                    .get()
+                    .pages()
+                    .page_id(id)
+                    .body_format("storage")
+                    .execute::<v2::model::Page>()?;
+                parse(status, d, "get page").map(Page::from)
+            }
+        }
+    }
+
+    fn find_page(&self, space_key: &str, title: &str) -> Result<Option<Page>> {
+        let title = crate::util::encode_query(title);
+        match self.deployment {
+            Deployment::DataCenter => {
+                let (_, status, d) = self
+                    .v1
+                    .get()
+                    .content()
+                    .space_key(space_key)
+                    .title(&title)
+                    .expand(V1_PAGE_EXPAND)
+                    .execute::<V1Results<V1Content>>()?;
+                let results = parse(status, d, "find page")?;
+                Ok(results.results.into_iter().next().map(Page::from))
+            }
+            Deployment::Cloud => {
+                let space_id = self.space_id(space_key)?;
+                let (_, status, d) = self
+                    .v2
+                    .get()
+                    .pages()
+                    .space_id(&space_id)
+                    .title(&title)
+                    .body_format("storage")
+                    .execute::<MultiEntityResult<v2::model::Page>>()?;
+                let results = parse(status, d, "find page")?;
+                Ok(results.results.into_iter().next().map(Page::from))
+            }
+        }
+    }
+
+    fn create_page(&self, page: &NewPage) -> Result<Page> {
+        match self.deployment {
+            Deployment::DataCenter => {
+                let request = CreatePageRequest::new(
+                    "page",
+                    &page.title,
+                    Space::new(&page.space_key),
+                    Body::new(Storage::new(
+                        &page.body.value,
+                        page.body.representation.clone(),
+                    )),
+                    page.parent_id.as_deref().map(|p| vec![Ancestor::new(p)]),
+                );
+                let (_, status, d) = self.v1.post(request).content().execute::<V1Content>()?;
+                parse(status, d, "create page").map(Page::from)
+            }
+            Deployment::Cloud => {
+                let request = v2::model::CreatePageRequest::new(
+                    &self.space_id(&page.space_key)?,
+                    &page.title,
+                    BodyWrite::new(&page.body.value, page.body.representation.clone()),
+                    page.parent_id.as_deref(),
+                );
+                let (_, status, d) = self.v2.post(request).pages().execute::<v2::model::Page>()?;
+                parse(status, d, "create page").map(Page::from)
+            }
+        }
+    }
+
+    fn update_page(&self, id: &str, page: &NewPage, version: u64) -> Result<Page> {
+        match self.deployment {
+            Deployment::DataCenter => {
+                let mut request = UpdatePageRequest::new(
+                    id,
+                    "page",
+                    &page.title,
+                    Space::new(&page.space_key),
+                    Body::new(Storage::new(
+                        &page.body.value,
+                        page.body.representation.clone(),
+                    )),
+                    None,
+                    Version::new(version),
+                );
+                request.ancestors = page.parent_id.as_deref().map(|p| vec![Ancestor::new(p)]);
+                let (_, status, d) = self
+                    .v1
+                    .put(request)
+                    .content()
+                    .content_id(id)
+                    .execute::<V1Content>()?;
+                parse(status, d, "update page").map(Page::from)
+            }
+            Deployment::Cloud => {
+                let request = v2::model::UpdatePageRequest::new(
+                    id,
+                    &page.title,
+                    BodyWrite::new(&page.body.value, page.body.representation.clone()),
+                    page.parent_id.as_deref(),
+                    VersionWrite::new(version),
+                );
+                let (_, status, d) = self
+                    .v2
+                    .put(request)
+                    .pages()
+                    .page_id(id)
+                    .execute::<v2::model::Page>()?;
+                parse(status, d, "update page").map(Page::from)
+            }
+        }
+    }
+
+    fn delete_page(&self, id: &str) -> Result<()> {
+        let (_, status, _) = match self.deployment {
+            Deployment::DataCenter => self
+                .v1
+                .delete()
+                .content()
+                .content_id(id)
+                .execute::<serde_json::Value>()?,
+            Deployment::Cloud => self
+                .v2
+                .delete()
+                .pages()
+                .page_id(id)
+                .execute::<serde_json::Value>()?,
+        };
+        check(status, "delete page")
+    }
+
+    fn attachments(&self, page_id: &str) -> Result<Vec<Attachment>> {
+        match self.deployment {
+            Deployment::DataCenter => {
+                let endpoint = format!("content/{}/child/attachment", page_id);
+                get_all(&self.v1, &endpoint, "list attachments")?
+                    .into_iter()
+                    .map(|a| {
+                        serde_json::from_value::<V1Content>(a)
+                            .map(Attachment::from)
+                            .map_err(|e| format!("Unable to list attachments: {}", e).into())
+                    })
+                    .collect()
+            }
+            Deployment::Cloud => self
+                .v2
+                .cursor::<v2::model::Attachment, _>(
+                    self.v2
+                        .get()
+                        .pages()
+                        .page_id(page_id)
+                        .attachments()
+                        .limit("250"),
+                )
+                .map(|a| a.map(Attachment::from))
+                .collect(),
+        }
+    }
+
+    fn upload_attachment(
+        &self,
+        page_id: &str,
+        filename: &str,
+        data: Vec<u8>,
+        media_type: &str,
+    ) -> Result<Attachment> {
+        let form = Form::new().part("file", file_part(&self.v1, filename, data, media_type)?);
+        // v2 has no upload endpoint, so both deployments use v1 here.
+        let (_, status, d) = self
+            .v1
+            .post(())
+            .content()
+            .content_id(page_id)
+            .child()
+            .attachment(form)
+            .execute::<V1Results<V1Content>>()?;
+        parse(status, d, "upload attachment")?
+            .results
+            .into_iter()
+            .next()
+            .map(Attachment::from)
+            .ok_or_else(|| "Unable to upload attachment: empty response".into())
+    }
+
+    fn delete_attachment(&self, id: &str) -> Result<()> {
+        let (_, status, _) = match self.deployment {
+            Deployment::DataCenter => self
+                .v1
+                .delete()
+                .content()
+                .content_id(id)
+                .execute::<serde_json::Value>()?,
+            Deployment::Cloud => self
+                .v2
+                .delete()
+                .attachments()
+                .attachment_id(id)
+                .execute::<serde_json::Value>()?,
+        };
+        check(status, "delete attachment")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Representation;
+    use crate::transport::{Expectation, MockTransport};
+    use reqwest::Method;
+    use serde_json::json;
+    use std::rc::Rc;
+
+    fn client(host: &str, deployment: Deployment, mock: &Rc<MockTransport>) -> DeploymentClient {
+        let confluence = Confluence::new(host).with_transport(mock.clone());
+        DeploymentClient::new(&confluence, deployment)
+    }
+
+    fn new_page() -> NewPage {
+        NewPage::new(
+            "DOC",
+            "Guide",
+            Storage::new("<p>Hi</p>", Representation::Storage),
+            Some("10"),
+        )
+    }
+
+    #[test]
+    fn deployment_from_host() {
+        assert_eq!(
+            Deployment::from_host("https://example.atlassian.net/wiki/rest/api"),
+            Deployment::Cloud
+        );
+        assert_eq!(
+            Deployment::from_host("https://confluence.example.com/rest/api"),
+            Deployment::DataCenter
+        );
+        assert_eq!(
+            Deployment::Cloud.personal_space_key("557058:f5b0a9ef"),
+            "~557058:f5b0a9ef"
+        );
+    }
+
+    #[test]
+    fn data_center_uses_the_v1_endpoints() {
+        let mock = Rc::new(MockTransport::new());
+        let v1_page = json!({
+            "id": "11", "type": "page", "title": "Guide", "version": { "number": 2 },
+            "ancestors": [{ "id": "10" }],
+            "body": { "storage": { "value": "<p>Hi</p>", "representation": "storage" } },
+        });
+        let v1_attachment = json!({
+            "id": "att12", "type": "attachment", "title": "a.png", "version": { "number": 1 },
+            "extensions": { "mediaType": "image/png", "fileSize": 3 },
+            "_links": { "download": "/download/attachments/11/a.png" },
+        });
+        mock.expect(
+            Expectation::new(Method::GET, "/rest/api/content/11")
+                .query("expand", V1_PAGE_EXPAND)
+                .respond_json(200, &v1_page),
+        )
+        .expect(
+            Expectation::new(Method::POST, "/rest/api/content")
+                .json_body(&json!({
+                    "type": "page", "title": "Guide", "space": { "key": "DOC" },
+                    "body": { "storage": { "value": "<p>Hi</p>", "representation": "storage" } },
+                    "ancestors": [{ "id": "10" }],
+                }))
+                .respond_json(200, &v1_page),
+        )
+        .expect(
+            Expectation::new(Method::PUT, "/rest/api/content/11")
+                .json_body(&json!({
+                    "type": "page", "title": "Guide", "space": { "key": "DOC" },
+                    "body": { "storage": { "value": "<p>Hi</p>", "representation": "storage" } },
+                    "ancestors": [{ "id": "10" }], "version": { "number": 2 },
+                }))
+                .respond_json(200, &v1_page),
+        )
+        .expect(
+            Expectation::new(Method::GET, "/rest/api/content/11/child/attachment")
+                .query("start", "0")
+                .respond_json(200, &json!({ "results": [v1_attachment], "_links": {} })),
+        )
+        .expect(
+            Expectation::new(Method::POST, "/rest/api/content/11/child/attachment")
+                .respond_json(200, &json!({ "results": [v1_attachment] })),
+        )
+        .expect(Expectation::new(Method::DELETE, "/rest/api/content/att12").respond(204, ""));
+
+        let api = client(
+            "https://wiki.example.com/rest/api",
+            Deployment::DataCenter,
+            &mock,
+        );
+        let page = api.get_page("11").unwrap();
+        assert_eq!(page.parent_id.as_deref(), Some("10"));
+        assert_eq!(api.create_page(&new_page()).unwrap(), page);
+        assert_eq!(api.update_page("11", &new_page(), 2).unwrap().version, 2);
+        let attachments = api.attachments("11").unwrap();
+        assert_eq!(attachments[0].file_size, Some(3));
+        let uploaded = api
+            .upload_attachment("11", "a.png", b"png".to_vec(), "image/png")
+            .unwrap();
+        assert_eq!(uploaded, attachments[0]);
+        let upload = &mock.requests()[4];
+        assert!(upload.headers["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("multipart/form-data"));
+        api.delete_attachment("att12").unwrap();
+        mock.assert_done();
+    }
+
+    #[test]
+    fn data_center_lists_every_page_of_attachments() {
+        let mock = Rc::new(MockTransport::new());
+        let attachment = |id: &str| json!({ "id": id, "type": "attachment", "title": id });
+        mock.expect(
+            Expectation::new(Method::GET, "/rest/api/content/11/child/attachment")
+                .query("start", "0")
+                .respond_json(
+                    200,
+                    &json!({
+                        "results": [attachment("att1"), attachment("att2")],
+                        "_links": { "next": "/rest/api/content/11/child/attachment?start=2" },
+                    }),
+                ),
+        )
+        .expect(
+            Expectation::new(Method::GET, "/rest/api/content/11/child/attachment")
+                .query("start", "2")
+                .respond_json(
+                    200,
+                    &json!({ "results": [attachment("att3")], "_links": {} }),
+                ),
+        );
+
+        let api = client(
+            "https://wiki.example.com/rest/api",
+            Deployment::DataCenter,
+            &mock,
+        );
+        let ids: Vec<_> = api
+            .attachments("11")
+            .unwrap()
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+        assert_eq!(ids, ["att1", "att2", "att3"]);
+        mock.assert_done();
+    }
+
+    #[test]
+    fn cloud_uses_the_v2_endpoints() {
+        let mock = Rc::new(MockTransport::new());
+        let v2_page = json!({
+            "id": "11", "status": "current", "title": "Guide", "spaceId": "98",
+            "parentId": "10", "version": { "number": 2 },
+            "body": { "storage": { "value": "<p>Hi</p>", "representation": "storage" } },
+        });
+        let body = json!({ "value": "<p>Hi</p>", "representation": "storage" });
+        mock.expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/pages/11")
+                .query("body-format", "storage")
+                .respond_json(200, &v2_page),
+        )
+        .expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/spaces")
+                .query("keys", "DOC")
+                .respond_json(
+                    200,
+                    &json!({ "results": [{
+                        "id": "98", "key": "DOC", "name": "Docs", "type": "global", "status": "current",
+                    }] }),
+                ),
+        )
+        .expect(
+            Expectation::new(Method::POST, "/wiki/api/v2/pages")
+                .json_body(&json!({
+                    "spaceId": "98", "status": "current", "title": "Guide", "parentId": "10",
+                    "body": body,
+                }))
+                .respond_json(200, &v2_page),
+        )
+        .expect(
+            Expectation::new(Method::PUT, "/wiki/api/v2/pages/11")
+                .json_body(&json!({
+                    "id": "11", "status": "current", "title": "Guide", "parentId": "10",
+                    "body": body, "version": { "number": 2 },
+                }))
+                .respond_json(200, &v2_page),
+        )
+        .expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/pages/11/attachments")
+                .query("limit", "250")
+                .respond_json(
+                    200,
+                    &json!({ "results": [{
+                        "id": "att12", "status": "current", "title": "a.png",
+                        "mediaType": "image/png", "fileSize": 3,
+                        "downloadLink": "/download/attachments/11/a.png",
+                    }], "_links": {} }),
+                ),
+        )
+        // v2 has no upload endpoint.
+        .expect(
+            Expectation::new(Method::POST, "/wiki/rest/api/content/11/child/attachment")
+                .respond_json(
+                    200,
+                    &json!({ "results": [{ "id": "att13", "type": "attachment", "title": "b.png" }] }),
+                ),
+        )
+        .expect(Expectation::new(Method::DELETE, "/wiki/api/v2/attachments/att12").respond(204, ""));
+
+        let api = client(
+            "https://example.atlassian.net/wiki/rest/api",
+            Deployment::Cloud,
+            &mock,
+        );
+        let page = api.get_page("11").unwrap();
+        assert_eq!(page.parent_id.as_deref(), Some("10"));
+        assert_eq!(api.create_page(&new_page()).unwrap(), page);
+        assert_eq!(api.update_page("11", &new_page(), 2).unwrap().version, 2);
+        let attachments = api.attachments("11").unwrap();
+        assert_eq!(
+            attachments[0].download.as_deref(),
+            Some("/download/attachments/11/a.png")
+        );
+        let uploaded = api
+            .upload_attachment("11", "b.png", b"png".to_vec(), "image/png")
+            .unwrap();
+        assert_eq!(uploaded.id, "att13");
+        api.delete_attachment("att12").unwrap();
+        mock.assert_done();
+    }
+
+    #[test]
+    fn v1_content_into_page() {
+        let json = r#"{"id":"205618124","type":"page","status":"current","title":"Once Upon",
+            "version":{"number":4},"ancestors":[{"id":"1"},{"id":"205613650"}],
+            "body":{"storage":{"value":"<p>Hi</p>","representation":"storage"}}}"#;
+        let page: Page = serde_json::from_str::<V1Content>(json).unwrap().into();
+        assert_eq!(
+            page,
+            Page {
+                id: "205618124".to_string(),
+                title: "Once Upon".to_string(),
+                version: 4,
+                parent_id: Some("205613650".to_string()),
+                body: Some("<p>Hi</p>".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,588 @@
+//! Exporting a space to a local directory, for backups and git mirrors.
+//!
+//! Every page and blog post is written to a directory named after its id:
+//!
+//! ```text
+//! space.json
+//! page/<id>/body.xml           storage format body
+//! page/<id>/metadata.json      version, author, labels, properties, ancestors
+//! page/<id>/attachments/<name>
+//! blogpost/<id>/...
+//! ```
+//!
+//! JSON is written with sorted keys and lists in a stable order, so
+//! exporting the same content twice gives the same files. Exporting into a
+//! previous export only fetches the bodies of content whose
+//! `version.number` changed. Labels, properties and attachments don't
+//! change the version, so they are listed and compared every time, and
+//! attachments are downloaded again when their version changed. What was
+//! deleted is removed.
+//!
+//! ```no_run
+//! use confluence::client::Confluence;
+//! use confluence::export::SpaceExporter;
+//!
+//! let confluence = Confluence::new("https://wiki.example.com/rest/api");
+//! let report = SpaceExporter::new(&confluence, "DOCS", "backup/DOCS").export().unwrap();
+//! println!("{} exported, {} unchanged", report.exported.len(), report.unchanged.len());
+//! ```
+
+use crate::client::{Confluence, Executor, Result};
+use crate::content::model::Content;
+use crate::util::{encode_query, expect, get_all, get_json};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub const SPACE_FILE: &str = "space.json";
+pub const BODY_FILE: &str = "body.xml";
+pub const METADATA_FILE: &str = "metadata.json";
+pub const ATTACHMENTS_DIR: &str = "attachments";
+/// The content types exported, each into a directory of the same name.
+pub const CONTENT_TYPES: &[&str] = &["page", "blogpost"];
+
+/// The space of an export, in `space.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportedSpace {
+    pub key: String,
+    pub name: String,
+}
+
+/// A page or blog post, in its `metadata.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedContent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub content_type: String,
+    pub title: String,
+    pub space_key: String,
+    pub version: ExportedVersion,
+    /// From the space's home page down to the parent.
+    pub ancestors: Vec<ExportedAncestor>,
+    /// Sorted.
+    pub labels: Vec<String>,
+    pub properties: BTreeMap<String, Value>,
+    /// Sorted by title.
+    pub attachments: Vec<ExportedAttachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedVersion {
+    pub number: u64,
+    pub when: String,
+    pub message: Option<String>,
+    /// The account id on Cloud, the username on Server and Data Center.
+    pub author: Option<String>,
+    pub author_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExportedAncestor {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedAttachment {
+    pub id: String,
+    /// Also the name of the file in the `attachments` directory.
+    pub title: String,
+    pub version: u64,
+    pub media_type: Option<String>,
+    pub file_size: Option<u64>,
+    pub comment: Option<String>,
+}
+
+/// What an export did, by content id.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExportReport {
+    pub exported: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+pub struct SpaceExporter {
+    confluence: Confluence,
+    space_key: String,
+    dir: PathBuf,
+}
+
+impl SpaceExporter {
+    pub fn new(confluence: &Confluence, space_key: &str, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            confluence: confluence.clone(),
+            space_key: space_key.to_string(),
+            dir: dir.into(),
+        }
+    }
+
+    /// Exports the space, or what changed since the export in the
+    /// directory.
+    pub fn export(&self) -> Result<ExportReport> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Unable to create {}: {}", self.dir.display(), e))?;
+        let space = get_json(
+            &self.confluence,
+            &format!("space/{}", encode_query(&self.space_key)),
+            "get space",
+        )?;
+        let space = ExportedSpace {
+            key: self.space_key.clone(),
+            name: space["name"].as_str().unwrap_or_default().to_string(),
+        };
+        write_json(&self.dir.join(SPACE_FILE), &space)?;
+
+        let mut report = ExportReport::default();
+        for content_type in CONTENT_TYPES {
+            let type_dir = self.dir.join(content_type);
+            let mut listed = HashSet::new();
+            for content in self.list(content_type)? {
+                listed.insert(content.id.clone());
+                let dir = type_dir.join(&content.id);
+                let number = content.version.as_ref().map(|v| v.number);
+                let changed = match read_metadata(&dir) {
+                    Some(previous) if Some(previous.version.number) == number => {
+                        self.refresh(&content.id, &dir, previous)?
+                    }
+                    previous => {
+                        self.export_content(&content.id, &dir, previous.as_ref())?;
+                        true
+                    }
+                };
+                if changed {
+                    report.exported.push(content.id);
+                } else {
+                    report.unchanged.push(content.id);
+                }
+            }
+            report
+                .removed
+                .extend(self.remove_unlisted(&type_dir, &listed)?);
+        }
+        Ok(report)
+    }
+
+    /// Removes the directories of content no longer in the space. Each one
+    /// is looked up first, so content missing from the listing for any
+    /// other reason is kept.
+    fn remove_unlisted(&self, dir: &Path, listed: &HashSet<String>) -> Result<Vec<String>> {
+        let mut removed = Vec::new();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(removed),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let id = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir() && !listed.contains(&id) && self.is_gone(&id)? {
+                std::fs::remove_dir_all(entry.path())?;
+                removed.push(id);
+            }
+        }
+        removed.sort();
+        Ok(removed)
+    }
+
+    /// Whether content was deleted or moved out of the space.
+    fn is_gone(&self, id: &str) -> Result<bool> {
+        let (_, status, d) = self
+            .confluence
+            .get()
+            .custom_endpoint(&format!("content/{}?expand=space", id))
+            .execute::<Value>()?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(true);
+        }
+        let content = expect(status, d, &format!("get {}", id))?;
+        Ok(
+            content["space"]["key"].as_str() != Some(self.space_key.as_str())
+                || content["status"].as_str().is_some_and(|s| s != "current"),
+        )
+    }
+
+    /// Every page or blog post of the space, with its version.
+    fn list(&self, content_type: &str) -> Result<Vec<Content>> {
+        let endpoint = format!(
+            "content?spaceKey={}&type={}&expand=version",
+            encode_query(&self.space_key),
+            content_type
+        );
+        get_all(&self.confluence, &endpoint, "list content")?
+            .into_iter()
+            .map(|c| {
+                serde_json::from_value(c)
+                    .map_err(|e| format!("Unable to list content: {}", e).into())
+            })
+            .collect()
+    }
+
+    fn export_content(
+        &self,
+        id: &str,
+        dir: &Path,
+        previous: Option<&ExportedContent>,
+    ) -> Result<()> {
+        let content = get_json(
+            &self.confluence,
+            &format!("content/{}?expand=body.storage,version,ancestors,space", id),
+            "get content",
+        )?;
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+        let body = content["body"]["storage"]["value"]
+            .as_str()
+            .unwrap_or_default();
+        write(&dir.join(BODY_FILE), body.as_bytes())?;
+
+        let labels = self.labels(id)?;
+        let properties = self.properties(id)?;
+        let attachments = self.export_attachments(id, &dir.join(ATTACHMENTS_DIR), previous)?;
+        let version = &content["version"];
+        let by = &version["by"];
+        let text = |value: &Value| value.as_str().filter(|s| !s.is_empty()).map(str::to_string);
+        let metadata = ExportedContent {
+            id: id.to_string(),
+            content_type: text(&content["type"]).unwrap_or_default(),
+            title: text(&content["title"]).unwrap_or_default(),
+            space_key: text(&content["space"]["key"]).unwrap_or_else(|| self.space_key.clone()),
+            version: ExportedVersion {
+                number: version["number"].as_u64().unwrap_or(1),
+                when: text(&version["when"]).unwrap_or_default(),
+                message: text(&version["message"]),
+                author: text(&by["accountId"]).or_else(|| text(&by["username"])),
+                author_name: text(&by["displayName"]),
+            },
+            ancestors: content["ancestors"]
+                .as_array()
+                .map(|ancestors| {
+                    ancestors
+                        .iter()
+                        .map(|a| ExportedAncestor {
+                            id: text(&a["id"]).unwrap_or_default(),
+                            title: text(&a["title"]).unwrap_or_default(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            labels,
+            properties,
+            attachments,
+        };
+        write_json(&dir.join(METADATA_FILE), &metadata)
+    }
+
+    /// Updates the labels, properties and attachments of content whose
+    /// version didn't change. Returns whether any of them changed.
+    fn refresh(&self, id: &str, dir: &Path, previous: ExportedContent) -> Result<bool> {
+        let mut current = previous.clone();
+        current.labels = self.labels(id)?;
+        current.properties = self.properties(id)?;
+        current.attachments =
+            self.export_attachments(id, &dir.join(ATTACHMENTS_DIR), Some(&previous))?;
+        if current == previous {
+            return Ok(false);
+        }
+        write_json(&dir.join(METADATA_FILE), &current)?;
+        Ok(true)
+    }
+
+    /// The label names of content, sorted.
+    fn labels(&self, id: &str) -> Result<Vec<String>> {
+        let mut labels: Vec<String> = get_all(
+            &self.confluence,
+            &format!("content/{}/label", id),
+            "list labels",
+        )?
+        .iter()
+        .filter_map(|l| l["name"].as_str().map(str::to_string))
+        .collect();
+        labels.sort();
+        Ok(labels)
+    }
+
+    fn properties(&self, id: &str) -> Result<BTreeMap<String, Value>> {
+        let mut properties = BTreeMap::new();
+        for property in get_all(
+            &self.confluence,
+            &format!("content/{}/property", id),
+            "list properties",
+        )? {
+            if let Some(key) = property["key"].as_str() {
+                properties.insert(key.to_string(), property["value"].clone());
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Downloads the attachments whose version changed and removes those
+    /// deleted since.
+    fn export_attachments(
+        &self,
+        id: &str,
+        dir: &Path,
+        previous: Option<&ExportedContent>,
+    ) -> Result<Vec<ExportedAttachment>> {
+        let endpoint = format!("content/{}/child/attachment?expand=version", id);
+        let mut attachments = Vec::new();
+        for attachment in get_all(&self.confluence, &endpoint, "list attachments")? {
+            let exported = ExportedAttachment {
+                id: attachment["id"].as_str().unwrap_or_default().to_string(),
+                title: attachment["title"].as_str().unwrap_or_default().to_string(),
+                version: attachment["version"]["number"].as_u64().unwrap_or(1),
+                media_type: attachment["extensions"]["mediaType"]
+                    .as_str()
+                    .or_else(|| attachment["metadata"]["mediaType"].as_str())
+                    .map(str::to_string),
+                file_size: attachment["extensions"]["fileSize"].as_u64(),
+                comment: attachment["extensions"]["comment"]
+                    .as_str()
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_string),
+            };
+            let path = dir.join(file_name(&exported.title));
+            let unchanged = path.exists()
+                && previous.is_some_and(|p| {
+                    p.attachments
+                        .iter()
+                        .any(|a| a.id == exported.id && a.version == exported.version)
+                });
+            if !unchanged {
+                let download = attachment["_links"]["download"].as_str().ok_or_else(|| {
+                    format!("Unable to download {}: no download link", exported.title)
+                })?;
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+                write(&path, &self.confluence.download(download)?)?;
+            }
+            attachments.push(exported);
+        }
+        attachments.sort_by(|a, b| a.title.cmp(&b.title));
+
+        let names: HashSet<String> = attachments.iter().map(|a| file_name(&a.title)).collect();
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries {
+                let entry = entry?;
+                if !names.contains(&*entry.file_name().to_string_lossy()) {
+                    std::fs::remove_file(entry.path())?;
+                }
+            }
+        }
+        Ok(attachments)
+    }
+}
+
+/// The metadata of a previous export of the content in `dir`.
+pub fn read_metadata(dir: &Path) -> Option<ExportedContent> {
+    let json = std::fs::read(dir.join(METADATA_FILE)).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// The name an attachment is stored under.
+pub fn file_name(title: &str) -> String {
+    title.replace(['/', '\\'], "_")
+}
+
+fn write(path: &Path, data: &[u8]) -> Result<()> {
+    std::fs::write(path, data)
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e).into())
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let mut json = serde_json::to_vec_pretty(value)?;
+    json.push(b'\n');
+    write(path, &json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeConfluence;
+    use serde_json::json;
+
+    #[test]
+    fn exports_pages_with_metadata_and_attachments() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let confluence = server.client();
+        let home = server.create_page("DOCS", "Home", None);
+        let guide = server.create_page("DOCS", "Guide", Some(&home));
+        confluence
+            .post(json!([{ "prefix": "global", "name": "howto" }]))
+            .custom_endpoint(&format!("content/{}/label", guide))
+            .execute::<Value>()
+            .unwrap();
+        confluence
+            .post(json!({ "key": "owner", "value": { "team": "docs" } }))
+            .custom_endpoint(&format!("content/{}/property", guide))
+            .execute::<Value>()
+            .unwrap();
+        let part = reqwest::blocking::multipart::Part::bytes(b"png".to_vec())
+            .file_name("setup.png")
+            .mime_str("image/png")
+            .unwrap();
+        confluence
+            .post(())
+            .content()
+            .content_id(&guide)
+            .child()
+            .attachment(reqwest::blocking::multipart::Form::new().part("file", part))
+            .execute::<Value>()
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("export-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let exporter = SpaceExporter::new(&confluence, "DOCS", &dir);
+        let report = exporter.export().unwrap();
+        assert_eq!(report.exported, vec![home.clone(), guide.clone()]);
+
+        let guide_dir = dir.join("page").join(&guide);
+        assert_eq!(
+            std::fs::read_to_string(guide_dir.join(BODY_FILE)).unwrap(),
+            "<p>Guide</p>"
+        );
+        assert_eq!(
+            std::fs::read(guide_dir.join("attachments/setup.png")).unwrap(),
+            b"png"
+        );
+        let metadata = read_metadata(&guide_dir).unwrap();
+        assert_eq!(metadata.title, "Guide");
+        assert_eq!(metadata.version.number, 1);
+        assert_eq!(metadata.version.author.as_deref(), Some("admin"));
+        assert_eq!(metadata.labels, vec!["howto"]);
+        assert_eq!(metadata.properties["owner"], json!({ "team": "docs" }));
+        assert_eq!(metadata.ancestors[0].id, home);
+        assert_eq!(
+            metadata.attachments[0].media_type.as_deref(),
+            Some("image/png")
+        );
+        let space: ExportedSpace =
+            serde_json::from_slice(&std::fs::read(dir.join(SPACE_FILE)).unwrap()).unwrap();
+        assert_eq!(space.name, "Docs");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn re_exports_only_what_changed() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let confluence = server.client();
+        let home = server.create_page("DOCS", "Home", None);
+        let old = server.create_page("DOCS", "Old", None);
+        let dir = std::env::temp_dir().join(format!("reexport-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let exporter = SpaceExporter::new(&confluence, "DOCS", &dir);
+        exporter.export().unwrap();
+        let metadata = std::fs::read(dir.join("page").join(&home).join(METADATA_FILE)).unwrap();
+
+        let update = json!({
+            "type": "page",
+            "title": "Home",
+            "space": { "key": "DOCS" },
+            "body": { "storage": { "value": "<p>New</p>", "representation": "storage" } },
+            "version": { "number": 2 },
+        });
+        confluence
+            .put(update)
+            .content()
+            .content_id(&home)
+            .execute::<Value>()
+            .unwrap();
+        confluence
+            .delete()
+            .content()
+            .content_id(&old)
+            .execute::<Value>()
+            .unwrap();
+
+        let report = exporter.export().unwrap();
+        assert_eq!(report.exported, vec![home.clone()]);
+        assert_eq!(report.removed, vec![old.clone()]);
+        assert!(!dir.join("page").join(&old).exists());
+        let home_dir = dir.join("page").join(&home);
+        assert_eq!(read_metadata(&home_dir).unwrap().version.number, 2);
+        assert_ne!(
+            std::fs::read(home_dir.join(METADATA_FILE)).unwrap(),
+            metadata
+        );
+
+        let report = exporter.export().unwrap();
+        assert_eq!(report.unchanged, vec![home]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_everything_when_the_server_caps_page_sizes() {
+        let server = FakeConfluence::start()
+            .unwrap()
+            .with_space("DOCS", "Docs")
+            .with_max_limit(2);
+        let confluence = server.client();
+        let mut ids: Vec<String> = (0..5)
+            .map(|i| server.create_page("DOCS", &format!("Page {}", i), None))
+            .collect();
+        ids.sort();
+        let dir = std::env::temp_dir().join(format!("export-capped-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let exporter = SpaceExporter::new(&confluence, "DOCS", &dir);
+        let mut report = exporter.export().unwrap();
+        report.exported.sort();
+        assert_eq!(report.exported, ids);
+
+        let mut report = exporter.export().unwrap();
+        report.unchanged.sort();
+        assert_eq!(report.unchanged, ids);
+        assert!(report.removed.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn re_exports_labels_properties_and_attachments_of_unchanged_versions() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let confluence = server.client();
+        let home = server.create_page("DOCS", "Home", None);
+        let dir = std::env::temp_dir().join(format!("reexport-meta-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let exporter = SpaceExporter::new(&confluence, "DOCS", &dir);
+        exporter.export().unwrap();
+
+        confluence
+            .post(json!([{ "prefix": "global", "name": "howto" }]))
+            .custom_endpoint(&format!("content/{}/label", home))
+            .execute::<Value>()
+            .unwrap();
+        confluence
+            .post(json!({ "key": "owner", "value": "docs" }))
+            .custom_endpoint(&format!("content/{}/property", home))
+            .execute::<Value>()
+            .unwrap();
+        let part = reqwest::blocking::multipart::Part::bytes(b"png".to_vec()).file_name("a.png");
+        confluence
+            .post(())
+            .content()
+            .content_id(&home)
+            .child()
+            .attachment(reqwest::blocking::multipart::Form::new().part("file", part))
+            .execute::<Value>()
+            .unwrap();
+        assert_eq!(server.content(&home).unwrap().version, 1);
+
+        let report = exporter.export().unwrap();
+        assert_eq!(report.exported, vec![home.clone()]);
+        let home_dir = dir.join("page").join(&home);
+        let metadata = read_metadata(&home_dir).unwrap();
+        assert_eq!(metadata.labels, vec!["howto"]);
+        assert_eq!(metadata.properties["owner"], "docs");
+        assert_eq!(metadata.attachments[0].title, "a.png");
+        assert_eq!(
+            std::fs::read(home_dir.join("attachments/a.png")).unwrap(),
+            b"png"
+        );
+
+        let report = exporter.export().unwrap();
+        assert_eq!(report.unchanged, vec![home]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1112 @@
+//! An in-process stand-in for a Confluence server, behind the `fake`
+//! feature.
+//!
+//! [`FakeConfluence`] serves a subset of `/rest/api` on a local port from an
+//! in-memory store, so a [`Confluence`] client can be exercised end-to-end
+//! without a network:
+//!
+//! - `content`: listing by `spaceKey`, `title` and `type`, creating, reading,
+//!   updating with version checks and deleting pages and blog posts, and
+//!   creating comments,
+//! - `content/{id}/child`, `child/page`, `child/comment` and
+//!   `child/attachment`, including multipart uploads,
+//!   `child/attachment/{id}/data`, and updates and moves with
+//!   `PUT child/attachment/{id}`,
+//! - `content/{id}/version`, listing attachment versions and deleting
+//!   previous ones,
+//! - `content/{id}/label` and `content/{id}/property`,
+//! - `content/{id}/restriction`, replacing, adding and clearing
+//!   restrictions, and checking, adding and removing single users and
+//!   groups with `restriction/byOperation/{operation}/user` and `group`,
+//! - `content/search` with `title`, `space`, `type`, `label`, `parent` and
+//!   `ancestor` CQL conditions,
+//! - `start` and `limit` pagination with `_links.next`,
+//! - `user` by `key` or `username`,
+//! - `/download/attachments/{id}/{filename}`.
+//!
+//! ```
+//! use confluence::client::Executor;
+//! use confluence::fake::FakeConfluence;
+//! use confluence::model::{Body, CreatePageRequest, Representation, Space, Storage};
+//!
+//! let server = FakeConfluence::start().unwrap().with_space("DOC", "Documentation");
+//! let request = CreatePageRequest::new(
+//!     "page",
+//!     "Home",
+//!     Space::new("DOC"),
+//!     Body::new(Storage::new("<p>Hi</p>", Representation::Storage)),
+//!     None,
+//! );
+//! let (_, status, _) = server
+//!     .client()
+//!     .post(request)
+//!     .content()
+//!     .execute::<serde_json::Value>()
+//!     .unwrap();
+//! assert_eq!(status, 200);
+//! assert_eq!(server.find("DOC", "Home").unwrap().version, 1);
+//! ```
+
+mod http;
+mod store;
+
+pub use store::{Property, StoredContent, StoredRestriction, StoredVersion};
+
+use crate::client::{Confluence, Result};
+use http::{Request, Response};
+use serde_json::{json, Value};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use store::{
+    bad_request, label_json, not_found, property_json, results_json, user_json, version_json,
+    Rejected, Store, Subject,
+};
+
+pub struct FakeConfluence {
+    addr: SocketAddr,
+    store: Arc<Mutex<Store>>,
+    stopped: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl FakeConfluence {
+    /// Starts a server on a free local port. It stops when dropped.
+    pub fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let store = Arc::new(Mutex::new(Store::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let acceptor = {
+            let store = Arc::clone(&store);
+            let stopped = Arc::clone(&stopped);
+            let base = format!("http://{}", addr);
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+                    let store = Arc::clone(&store);
+                    let base = base.clone();
+                    std::thread::spawn(move || {
+                        let _ = http::serve(stream, |request| {
+                            let mut store = store.lock().unwrap();
+                            route(&mut store, request, &base).unwrap_or_else(|r| r)
+                        });
+                    });
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            store,
+            stopped,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// Adds a space content can be created in.
+    pub fn with_space(self, key: &str, name: &str) -> Self {
+        self.add_space(key, name);
+        self
+    }
+
+    pub fn add_space(&self, key: &str, name: &str) {
+        let mut store = self.store.lock().unwrap();
+        store.spaces.insert(key.to_string(), name.to_string());
+    }
+
+    /// Adds a user, found by `user?username=` and `user?key=`.
+    pub fn with_user(self, username: &str, key: &str) -> Self {
+        let mut store = self.store.lock().unwrap();
+        store.users.insert(key.to_string(), username.to_string());
+        drop(store);
+        self
+    }
+
+    /// Caps the `limit` of every listing, as instances do with their own
+    /// maximum page sizes.
+    pub fn with_max_limit(self, max_limit: usize) -> Self {
+        self.store.lock().unwrap().max_limit = Some(max_limit);
+        self
+    }
+
+    /// The REST API root, e.g. `http://127.0.0.1:40123/rest/api`.
+    pub fn url(&self) -> String {
+        format!("http://{}/rest/api", self.addr)
+    }
+
+    /// A client for the server.
+    pub fn client(&self) -> Confluence {
+        Confluence::new(&self.url())
+    }
+
+    pub fn content(&self, id: &str) -> Option<StoredContent> {
+        let store = self.store.lock().unwrap();
+        store.get(id).ok().cloned()
+    }
+
+    /// The page titled `title` in `space_key`.
+    pub fn find(&self, space_key: &str, title: &str) -> Option<StoredContent> {
+        let store = self.store.lock().unwrap();
+        store
+            .contents
+            .iter()
+            .find(|c| c.content_type == "page" && c.space_key == space_key && c.title == title)
+            .cloned()
+    }
+
+    /// Creates a page whose body is its title in a paragraph, returning its
+    /// id. Panics if the page is rejected, e.g. for a missing space.
+    pub fn create_page(&self, space_key: &str, title: &str, parent: Option<&str>) -> String {
+        self.create_page_with_body(space_key, title, &format!("<p>{}</p>", title), parent)
+    }
+
+    /// Like [`create_page`](Self::create_page), with a storage format body.
+    pub fn create_page_with_body(
+        &self,
+        space_key: &str,
+        title: &str,
+        body: &str,
+        parent: Option<&str>,
+    ) -> String {
+        let mut request = json!({
+            "type": "page",
+            "title": title,
+            "space": { "key": space_key },
+            "body": { "storage": { "value": body, "representation": "storage" } },
+        });
+        if let Some(parent) = parent {
+            request["ancestors"] = json!([{ "id": parent }]);
+        }
+        let mut store = self.store.lock().unwrap();
+        match store.create(&request) {
+            Ok(id) => id,
+            Err(_) => panic!("Unable to create {} in {}", title, space_key),
+        }
+    }
+
+    /// All content, in the order it was created.
+    pub fn contents(&self) -> Vec<StoredContent> {
+        self.store.lock().unwrap().contents.clone()
+    }
+}
+
+impl Drop for FakeConfluence {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes the acceptor up so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+fn route(store: &mut Store, request: &Request, base: &str) -> Rejected<Response> {
+    if let Some(rest) = request.path.strip_prefix("/download/attachments/") {
+        return download(store, request, rest);
+    }
+    let path = request
+        .path
+        .strip_prefix("/rest/api/")
+        .ok_or_else(|| Response::error(404, "Not found"))?;
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    let method = request.method.as_str();
+    match (method, segments.as_slice()) {
+        ("GET", ["settings", "systemInfo"]) => Ok(Response::json(
+            200,
+            &json!({ "commitHash": "fake", "baseUrl": base }),
+        )),
+        ("GET", ["space", key]) => {
+            if !store.spaces.contains_key(*key) {
+                return Err(Response::error(
+                    404,
+                    &format!("No space with key : {}", key),
+                ));
+            }
+            Ok(Response::json(200, &store.space_json(key, &api(base))))
+        }
+        ("GET", ["user"]) => {
+            let user = store.users.iter().find(|(key, username)| {
+                request.param("key") == Some(key.as_str())
+                    || request.param("username") == Some(username.as_str())
+            });
+            match user {
+                Some((key, username)) => {
+                    Ok(Response::json(200, &user_json(key, username, &api(base))))
+                }
+                None => Err(Response::error(404, "No user found")),
+            }
+        }
+        ("GET", ["content"]) => list(store, request, base),
+        ("POST", ["content"]) => {
+            let id = store.create(&body(request)?)?;
+            content(store, &id, &expand_created(), base)
+        }
+        ("GET", ["content", "search"]) => search(store, request, base),
+        ("GET", ["content", id]) => content(store, id, &expand(request, ""), base),
+        ("PUT", ["content", id]) => {
+            store.update(id, &body(request)?)?;
+            content(store, id, &expand_created(), base)
+        }
+        ("DELETE", ["content", id]) => {
+            store.delete(id)?;
+            Ok(Response::empty(204))
+        }
+        ("GET", ["content", id, "child"]) => {
+            let page = children(store, request, id, "page", "page.", base)?;
+            Ok(Response::json(
+                200,
+                &json!({
+                    "page": page,
+                    "_links": {
+                        "base": base,
+                        "context": "",
+                        "self": format!("{}/content/{}/child", api(base), id),
+                    },
+                    "_expandable": {
+                        "attachment": format!("/rest/api/content/{}/child/attachment", id),
+                        "comment": format!("/rest/api/content/{}/child/comment", id),
+                    },
+                }),
+            ))
+        }
+        ("GET", ["content", id, "child", child_type]) => Ok(Response::json(
+            200,
+            &children(store, request, id, child_type, "", base)?,
+        )),
+        ("POST", ["content", id, "child", "attachment"]) => upload(store, request, id, false, base),
+        ("PUT", ["content", id, "child", "attachment"]) => upload(store, request, id, true, base),
+        ("POST", ["content", id, "child", "attachment", attachment_id, "data"]) => {
+            let (file, comment) = file_part(request)?;
+            store.update_data(id, attachment_id, &file, comment.as_deref())?;
+            content(store, attachment_id, &[], base)
+        }
+        ("PUT", ["content", id, "child", "attachment", attachment_id]) => {
+            store.update_attachment(id, attachment_id, &body(request)?)?;
+            content(store, attachment_id, &[], base)
+        }
+        ("GET", ["content", id, "version"]) => {
+            let content = store.get(id)?;
+            let mut all = vec![version_json(id, content.version, &content.when, &api(base))];
+            all.extend(
+                content
+                    .versions
+                    .iter()
+                    .rev()
+                    .map(|v| version_json(id, v.number, &v.when, &api(base))),
+            );
+            let self_url = format!("{}/content/{}/version", api(base), id);
+            Ok(Response::json(
+                200,
+                &paginate(store, all, request, 200, self_url, base),
+            ))
+        }
+        ("DELETE", ["content", id, "version", number]) => {
+            let number = number
+                .parse()
+                .map_err(|_| bad_request("The version number must be a number"))?;
+            store.delete_version(id, number)?;
+            Ok(Response::empty(204))
+        }
+        ("GET", ["content", id, "label"]) => labels(store, id, base),
+        ("POST", ["content", id, "label"]) => {
+            let names = match body(request)? {
+                Value::Array(labels) => labels,
+                label => vec![label],
+            };
+            let content = store.get_mut(id)?;
+            for name in names.iter().filter_map(|l| l["name"].as_str()) {
+                if !content.labels.iter().any(|l| l == name) {
+                    content.labels.push(name.to_string());
+                }
+            }
+            labels(store, id, base)
+        }
+        ("DELETE", ["content", id, "label"]) => {
+            let name = request
+                .param("name")
+                .ok_or_else(|| bad_request("The name parameter is required"))?
+                .to_string();
+            remove_label(store, id, &name)
+        }
+        ("DELETE", ["content", id, "label", name]) => remove_label(store, id, name),
+        ("GET", ["content", id, "property"]) => {
+            let content = store.get(id)?;
+            let all: Vec<Value> = content
+                .properties
+                .iter()
+                .map(|p| property_json(id, p, &api(base)))
+                .collect();
+            let self_url = format!("{}/content/{}/property", api(base), id);
+            Ok(Response::json(
+                200,
+                &paginate(store, all, request, 10, self_url, base),
+            ))
+        }
+        ("POST", ["content", id, "property"]) => {
+            let request = body(request)?;
+            let key = request["key"]
+                .as_str()
+                .ok_or_else(|| bad_request("A property needs a key"))?;
+            set_property(store, id, key, &request, true, base)
+        }
+        ("GET", ["content", id, "property", key]) => {
+            let property = store
+                .get(id)?
+                .properties
+                .iter()
+                .find(|p| p.key == *key)
+                .ok_or_else(|| property_not_found(id, key))?;
+            Ok(Response::json(
+                200,
+                &property_json(id, property, &api(base)),
+            ))
+        }
+        ("PUT", ["content", id, "property", key]) => {
+            set_property(store, id, key, &body(request)?, false, base)
+        }
+        ("DELETE", ["content", id, "property", key]) => {
+            let content = store.get_mut(id)?;
+            let before = content.properties.len();
+            content.properties.retain(|p| p.key != *key);
+            if content.properties.len() == before {
+                return Err(property_not_found(id, key));
+            }
+            Ok(Response::empty(204))
+        }
+        ("GET", ["content", id, "restriction"]) => restrictions(store, request, id, base),
+        ("PUT", ["content", id, "restriction"]) => {
+            store.apply_restrictions(id, &body(request)?, true)?;
+            restrictions(store, request, id, base)
+        }
+        ("POST", ["content", id, "restriction"]) => {
+            store.apply_restrictions(id, &body(request)?, false)?;
+            restrictions(store, request, id, base)
+        }
+        ("DELETE", ["content", id, "restriction"]) => {
+            store.get_mut(id)?.restrictions.clear();
+            restrictions(store, request, id, base)
+        }
+        (_, ["content", id, "restriction", "byOperation", operation, "user"]) => {
+            let username = store.username(request.param("userName"), request.param("key"))?;
+            restriction_subject(store, method, id, operation, Subject::User(username))
+        }
+        (_, ["content", id, "restriction", "byOperation", operation, "group", name]) => {
+            let group = Subject::Group(name.to_string());
+            restriction_subject(store, method, id, operation, group)
+        }
+        _ => Err(Response::error(
+            404,
+            &format!("No {} {} on this server", method, request.path),
+        )),
+    }
+}
+
+fn api(base: &str) -> String {
+    format!("{}/rest/api", base)
+}
+
+fn body(request: &Request) -> Rejected<Value> {
+    request
+        .json()
+        .ok_or_else(|| bad_request("The request body isn't valid JSON"))
+}
+
+/// The expansions requested, `prefix` removed, e.g. `page.` of child
+/// listings.
+fn expand(request: &Request, prefix: &str) -> Vec<String> {
+    request
+        .param("expand")
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|e| e.trim().strip_prefix(prefix))
+        .filter(|e| !e.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// What creating or updating content responds with.
+fn expand_created() -> Vec<String> {
+    ["body.storage", "version", "space", "ancestors", "container"]
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+fn content(store: &Store, id: &str, expand: &[String], base: &str) -> Rejected<Response> {
+    let content = store.get(id)?;
+    Ok(Response::json(200, &store.json(content, expand, base)))
+}
+
+/// A `start` and `limit` page of `all`.
+fn paginate(
+    store: &Store,
+    all: Vec<Value>,
+    request: &Request,
+    default_limit: usize,
+    self_url: String,
+    base: &str,
+) -> Value {
+    let start = request
+        .param("start")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    let limit = request
+        .param("limit")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(default_limit)
+        .min(store.max_limit.unwrap_or(usize::MAX))
+        .max(1);
+    let next = (start + limit < all.len()).then(|| {
+        let mut query: Vec<String> = request
+            .query
+            .iter()
+            .filter(|(name, _)| name != "start" && name != "limit")
+            .map(|(name, value)| format!("{}={}", name, crate::util::encode_query(value)))
+            .collect();
+        query.push(format!("limit={}", limit));
+        query.push(format!("start={}", start + limit));
+        format!("{}?{}", request.path, query.join("&"))
+    });
+    let page = all.into_iter().skip(start).take(limit).collect();
+    results_json(page, start, limit, self_url, base, next)
+}
+
+fn list(store: &Store, request: &Request, base: &str) -> Rejected<Response> {
+    let content_type = request.param("type").unwrap_or("page");
+    let expand = expand(request, "");
+    let all = store
+        .contents
+        .iter()
+        .filter(|c| c.content_type == content_type)
+        .filter(|c| request.param("spaceKey").map_or(true, |k| c.space_key == k))
+        .filter(|c| request.param("title").map_or(true, |t| c.title == t))
+        .map(|c| store.json(c, &expand, base))
+        .collect();
+    let self_url = format!("{}/content", api(base));
+    Ok(Response::json(
+        200,
+        &paginate(store, all, request, 25, self_url, base),
+    ))
+}
+
+fn children(
+    store: &Store,
+    request: &Request,
+    id: &str,
+    child_type: &str,
+    prefix: &str,
+    base: &str,
+) -> Rejected<Value> {
+    store.get(id)?;
+    let expand = expand(request, prefix);
+    let filename = request.param("filename");
+    let all = store
+        .children(id, child_type)
+        .filter(|c| filename.map_or(true, |f| c.title == f))
+        .map(|c| store.json(c, &expand, base))
+        .collect();
+    let self_url = format!("{}/content/{}/child/{}", api(base), id, child_type);
+    let limit = if child_type == "attachment" { 50 } else { 25 };
+    Ok(paginate(store, all, request, limit, self_url, base))
+}
+
+fn file_part(request: &Request) -> Rejected<(http::Part, Option<String>)> {
+    if !matches!(
+        request.header("x-atlassian-token"),
+        Some("no-check" | "nocheck")
+    ) {
+        return Err(Response::error(403, "XSRF check failed"));
+    }
+    let parts = http::multipart(request)
+        .ok_or_else(|| bad_request("Expected a multipart/form-data request"))?;
+    let comment = parts
+        .iter()
+        .find(|p| p.name == "comment")
+        .map(|p| String::from_utf8_lossy(&p.data).into_owned());
+    let file = parts
+        .into_iter()
+        .find(|p| p.name == "file")
+        .ok_or_else(|| bad_request("No file part in the request"))?;
+    Ok((file, comment))
+}
+
+fn upload(
+    store: &mut Store,
+    request: &Request,
+    id: &str,
+    replace: bool,
+    base: &str,
+) -> Rejected<Response> {
+    let (file, comment) = file_part(request)?;
+    let attachment_id = store.attach(id, &file, comment.as_deref(), replace)?;
+    let attachment = store.json(store.get(&attachment_id)?, &[], base);
+    let self_url = format!("{}/content/{}/child/attachment", api(base), id);
+    Ok(Response::json(
+        200,
+        &results_json(vec![attachment], 0, 50, self_url, base, None),
+    ))
+}
+
+/// The current version of an attachment, or the `version` asked for.
+fn download(store: &Store, request: &Request, rest: &str) -> Rejected<Response> {
+    let (container, filename) = rest
+        .split_once('/')
+        .ok_or_else(|| Response::error(404, "Not found"))?;
+    let attachment = store
+        .children(container, "attachment")
+        .find(|a| a.title == filename)
+        .ok_or_else(|| not_found(container))?;
+    let version = request.param("version").and_then(|v| v.parse::<u64>().ok());
+    let (data, media_type) = match version {
+        Some(number) if number != attachment.version => attachment
+            .versions
+            .iter()
+            .find(|v| v.number == number)
+            .map(|v| (&v.data, &v.media_type))
+            .ok_or_else(|| Response::error(404, "No such version"))?,
+        _ => (&attachment.data, &attachment.media_type),
+    };
+    let mut response = Response::bytes("application/octet-stream", data.clone());
+    if media_type.as_deref() == Some("image/png") {
+        response.content_type = "image/png";
+    }
+    Ok(response)
+}
+
+fn labels(store: &Store, id: &str, base: &str) -> Rejected<Response> {
+    let labels = store
+        .get(id)?
+        .labels
+        .iter()
+        .map(|l| label_json(l))
+        .collect();
+    let self_url = format!("{}/content/{}/label", api(base), id);
+    Ok(Response::json(
+        200,
+        &results_json(labels, 0, 200, self_url, base, None),
+    ))
+}
+
+fn remove_label(store: &mut Store, id: &str, name: &str) -> Rejected<Response> {
+    let content = store.get_mut(id)?;
+    let before = content.labels.len();
+    content.labels.retain(|l| l != name);
+    if content.labels.len() == before {
+        return Err(Response::error(
+            404,
+            &format!("Label {} not found on content {}", name, id),
+        ));
+    }
+    Ok(Response::empty(204))
+}
+
+/// Creates a property, or updates one with the next version number.
+fn set_property(
+    store: &mut Store,
+    id: &str,
+    key: &str,
+    request: &Value,
+    create: bool,
+    base: &str,
+) -> Rejected<Response> {
+    let property_id = store.property_id();
+    let content = store.get_mut(id)?;
+    let value = request["value"].clone();
+    match content.properties.iter_mut().find(|p| p.key == key) {
+        Some(_) if create => {
+            return Err(Response::error(
+                409,
+                &format!(
+                    "A property with key {} already exists on content {}",
+                    key, id
+                ),
+            ))
+        }
+        Some(property) => {
+            let version = request["version"]["number"].as_u64().unwrap_or(0);
+            if version != property.version + 1 {
+                return Err(Response::error(
+                    409,
+                    &format!(
+                        "Version must be incremented on update. Current version is: {}",
+                        property.version
+                    ),
+                ));
+            }
+            property.value = value;
+            property.version = version;
+        }
+        None => content.properties.push(Property {
+            id: property_id,
+            key: key.to_string(),
+            value,
+            version: 1,
+        }),
+    }
+    let property = content.properties.iter().find(|p| p.key == key).unwrap();
+    Ok(Response::json(
+        200,
+        &property_json(id, property, &api(base)),
+    ))
+}
+
+fn property_not_found(id: &str, key: &str) -> Response {
+    Response::error(
+        404,
+        &format!("Cannot find property {} on content {}", key, id),
+    )
+}
+
+fn restrictions(store: &Store, request: &Request, id: &str, base: &str) -> Rejected<Response> {
+    let all = store.restrictions_json(id, &api(base))?;
+    let self_url = format!("{}/content/{}/restriction", api(base), id);
+    Ok(Response::json(
+        200,
+        &paginate(store, all, request, 100, self_url, base),
+    ))
+}
+
+/// Checks, adds or removes a user or group of a restriction.
+fn restriction_subject(
+    store: &mut Store,
+    method: &str,
+    id: &str,
+    operation: &str,
+    subject: Subject,
+) -> Rejected<Response> {
+    match method {
+        "GET" => {
+            let restricted = store.get(id)?.restrictions.iter().any(|r| {
+                r.operation == operation
+                    && match &subject {
+                        Subject::User(username) => r.usernames.contains(username),
+                        Subject::Group(name) => r.groups.contains(name),
+                    }
+            });
+            if !restricted {
+                return Err(Response::error(404, "Not restricted"));
+            }
+        }
+        "PUT" => store.restrict(id, operation, subject, true)?,
+        "DELETE" => store.restrict(id, operation, subject, false)?,
+        _ => return Err(Response::error(405, "Method not allowed")),
+    }
+    Ok(Response::empty(200))
+}
+
+/// A CQL condition, e.g. `title ~ "release"`.
+struct Condition {
+    field: String,
+    operator: String,
+    value: String,
+}
+
+fn search(store: &Store, request: &Request, base: &str) -> Rejected<Response> {
+    let cql = request
+        .param("cql")
+        .ok_or_else(|| bad_request("The cql parameter is required"))?;
+    let conditions =
+        parse_cql(cql).ok_or_else(|| bad_request(&format!("Could not parse cql : {}", cql)))?;
+    let expand = expand(request, "");
+    let mut all = Vec::new();
+    for content in &store.contents {
+        let mut matched = true;
+        for condition in &conditions {
+            matched &= matches(store, content, condition)
+                .ok_or_else(|| bad_request(&format!("Unsupported cql condition : {}", cql)))?;
+        }
+        if matched {
+            all.push(store.json(content, &expand, base));
+        }
+    }
+    let self_url = format!("{}/content/search", api(base));
+    Ok(Response::json(
+        200,
+        &paginate(store, all, request, 25, self_url, base),
+    ))
+}
+
+/// Conditions joined with `AND`; a trailing `ORDER BY` is ignored.
+fn parse_cql(cql: &str) -> Option<Vec<Condition>> {
+    let mut tokens = Vec::new();
+    let mut chars = cql.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next()? {
+                    '\\' => value.push(chars.next()?),
+                    q if q == c => break,
+                    ch => value.push(ch),
+                }
+            }
+            tokens.push(value);
+        } else if "=!~".contains(c) {
+            let mut operator = String::new();
+            while let Some(&ch) = chars.peek().filter(|ch| "=!~".contains(**ch)) {
+                operator.push(ch);
+                chars.next();
+            }
+            tokens.push(operator);
+        } else {
+            let mut word = String::new();
+            while let Some(&ch) = chars
+                .peek()
+                .filter(|ch| !ch.is_whitespace() && !"=!~\"'".contains(**ch))
+            {
+                word.push(ch);
+                chars.next();
+            }
+            tokens.push(word);
+        }
+    }
+
+    let mut conditions = Vec::new();
+    let mut tokens = tokens.into_iter();
+    loop {
+        let field = tokens.next()?;
+        if field.eq_ignore_ascii_case("order") {
+            break;
+        }
+        conditions.push(Condition {
+            field: field.to_ascii_lowercase(),
+            operator: tokens.next()?,
+            value: tokens.next()?,
+        });
+        match tokens.next() {
+            None => break,
+            Some(and) if and.eq_ignore_ascii_case("and") => continue,
+            Some(order) if order.eq_ignore_ascii_case("order") => break,
+            Some(_) => return None,
+        }
+    }
+    Some(conditions)
+}
+
+fn matches(
+    store: &Store,
+    content: &crate::fake::StoredContent,
+    condition: &Condition,
+) -> Option<bool> {
+    let value = condition.value.as_str();
+    let equal = match condition.field.as_str() {
+        "title" if condition.operator.contains('~') => {
+            let contains = content
+                .title
+                .to_lowercase()
+                .contains(&value.trim_matches('*').to_lowercase());
+            return match condition.operator.as_str() {
+                "~" => Some(contains),
+                "!~" => Some(!contains),
+                _ => None,
+            };
+        }
+        "title" => content.title == value,
+        "space" | "space.key" => content.space_key == value,
+        "type" => content.content_type == value,
+        "id" => content.id == value,
+        "label" => content.labels.iter().any(|l| l == value),
+        "parent" => content.parent_id.as_deref() == Some(value),
+        "ancestor" => store.ancestors(content).iter().any(|a| a.id == value),
+        _ => return None,
+    };
+    match condition.operator.as_str() {
+        "=" => Some(equal),
+        "!=" => Some(!equal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Executor;
+    use crate::content::model::Ancestor;
+    use crate::content::model::{ChildContentServiceResponse, Content, ContentVersion, Results};
+    use crate::deployment::{ContentApi, Deployment, DeploymentClient};
+    use crate::model::{
+        Body, CreatePageRequest, Representation, Space, Storage, UpdateAttachmentPropertiesRequest,
+        UpdatePageRequest, Version,
+    };
+
+    fn create(confluence: &Confluence, title: &str, parent: Option<&str>) -> String {
+        let request = CreatePageRequest::new(
+            "page",
+            title,
+            Space::new("DOC"),
+            Body::new(Storage::new("<p>Hi</p>", Representation::Storage)),
+            parent.map(|p| vec![Ancestor::new(p)]),
+        );
+        let (_, status, d) = confluence
+            .post(request)
+            .content()
+            .execute::<Content>()
+            .unwrap();
+        assert_eq!(status, 200);
+        d.unwrap().id
+    }
+
+    #[test]
+    fn pages_round_trip_with_version_checks() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let confluence = server.client();
+        let home = create(&confluence, "Home", None);
+        let child = create(&confluence, "Child", Some(&home));
+
+        let (_, status, d) = confluence
+            .get()
+            .content()
+            .content_id(&home)
+            .child()
+            .expand("page.version")
+            .execute::<ChildContentServiceResponse>()
+            .unwrap();
+        assert_eq!(status, 200);
+        let page = d.unwrap().page;
+        assert_eq!(page.results[0].id, child);
+        assert_eq!(page.results[0].version.as_ref().unwrap().number, 1);
+
+        let update = |version| {
+            let request = UpdatePageRequest::new(
+                &child,
+                "page",
+                "Renamed",
+                Space::new("DOC"),
+                Body::new(Storage::new("<p>Bye</p>", Representation::Storage)),
+                None,
+                Version::new(version),
+            );
+            let (_, status, _) = confluence
+                .put(request)
+                .content()
+                .content_id(&child)
+                .execute::<serde_json::Value>()
+                .unwrap();
+            status
+        };
+        assert_eq!(update(3), 409);
+        assert_eq!(update(2), 200);
+        let stored = server.content(&child).unwrap();
+        assert_eq!((stored.title.as_str(), stored.version), ("Renamed", 2));
+        assert_eq!(stored.parent_id.as_deref(), Some(home.as_str()));
+
+        let (_, status, d) = confluence
+            .get()
+            .content()
+            .space_key("DOC")
+            .title("Renamed")
+            .expand("version")
+            .execute::<Results<Content>>()
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(d.unwrap().results[0].id, child);
+
+        let (_, status, _) = confluence
+            .delete()
+            .content()
+            .content_id(&home)
+            .execute::<serde_json::Value>()
+            .unwrap();
+        assert_eq!(status, 204);
+        assert_eq!(server.content(&child).unwrap().parent_id, None);
+    }
+
+    #[test]
+    fn attachments_labels_and_properties() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let confluence = server.client();
+        let page = create(&confluence, "Home", None);
+        let client = DeploymentClient::new(&confluence, Deployment::DataCenter);
+        let attachment = client
+            .upload_attachment(&page, "a.txt", b"one".to_vec(), "text/plain")
+            .unwrap();
+        assert_eq!(attachment.file_size, Some(3));
+        assert!(client
+            .upload_attachment(&page, "a.txt", b"two".to_vec(), "text/plain")
+            .is_err());
+        let listed = client.attachments(&page).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, attachment.id);
+        let download = reqwest::blocking::get(format!(
+            "http://{}{}",
+            server.addr,
+            listed[0].download.as_deref().unwrap()
+        ))
+        .unwrap();
+        assert_eq!(download.bytes().unwrap().as_ref(), b"one");
+
+        let (_, status, d) = confluence
+            .post(json!([{ "prefix": "global", "name": "draft" }]))
+            .custom_endpoint(&format!("content/{}/label", page))
+            .execute::<Value>()
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(d.unwrap()["results"][0]["name"], "draft");
+        assert_eq!(server.content(&page).unwrap().labels, vec!["draft"]);
+
+        let property = |version: u64| {
+            let (_, status, _) = confluence
+                .put(json!({ "key": "sync", "value": { "hash": version }, "version": { "number": version } }))
+                .custom_endpoint(&format!("content/{}/property/sync", page))
+                .execute::<Value>()
+                .unwrap();
+            status
+        };
+        assert_eq!(property(1), 200);
+        assert_eq!(property(1), 409);
+        assert_eq!(property(2), 200);
+        let stored = server.content(&page).unwrap().properties;
+        assert_eq!(
+            (stored[0].version, &stored[0].value),
+            (2, &json!({ "hash": 2 }))
+        );
+    }
+
+    #[test]
+    fn attachment_updates_versions_and_moves() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        let confluence = server.client();
+        let home = create(&confluence, "Home", None);
+        let archive = create(&confluence, "Archive", None);
+        let client = DeploymentClient::new(&confluence, Deployment::DataCenter);
+        let attachment = client
+            .upload_attachment(&home, "a.txt", b"one".to_vec(), "text/plain")
+            .unwrap();
+        let part = reqwest::blocking::multipart::Part::bytes(b"two".to_vec())
+            .file_name("a.txt")
+            .mime_str("text/plain")
+            .unwrap();
+        confluence
+            .post(())
+            .content()
+            .content_id(&home)
+            .child()
+            .attachment(reqwest::blocking::multipart::Form::new().part("file", part))
+            .attachment_id(&attachment.id)
+            .data()
+            .execute::<Value>()
+            .unwrap();
+
+        let update = |version, container: &str| {
+            let request = UpdateAttachmentPropertiesRequest::new(
+                &attachment.id,
+                "notes.txt",
+                Version::new(version),
+            )
+            .with_media_type("text/markdown")
+            .with_comment("renamed")
+            .with_container(&archive, "page");
+            confluence
+                .put(request)
+                .content()
+                .content_id(container)
+                .child()
+                .attachment()
+                .attachment_id(&attachment.id)
+                .execute::<Content>()
+                .unwrap()
+        };
+        assert_eq!(update(2, &home).1, 409);
+        let (_, status, d) = update(3, &home);
+        assert_eq!(status, 200);
+        let moved = d.unwrap();
+        assert_eq!(moved.title, "notes.txt");
+        let extensions = moved.extensions.unwrap();
+        assert_eq!(extensions.media_type.as_deref(), Some("text/markdown"));
+        assert_eq!(extensions.comment.as_deref(), Some("renamed"));
+        assert!(client.attachments(&home).unwrap().is_empty());
+        assert_eq!(client.attachments(&archive).unwrap()[0].id, attachment.id);
+
+        let versions = || {
+            let (_, status, d) = confluence
+                .get()
+                .content()
+                .content_id(&attachment.id)
+                .version()
+                .execute::<Results<ContentVersion>>()
+                .unwrap();
+            assert_eq!(status, 200);
+            d.unwrap()
+                .results
+                .iter()
+                .map(|v| v.number)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(versions(), vec![3, 2, 1]);
+        assert_eq!(
+            confluence
+                .download_attachment(&archive, "notes.txt", Some(2))
+                .unwrap(),
+            b"two"
+        );
+        assert_eq!(
+            confluence
+                .download_attachment(&archive, "notes.txt", None)
+                .unwrap(),
+            b"two"
+        );
+
+        let delete = |number: &str| {
+            let (_, status, _) = confluence
+                .delete()
+                .content()
+                .content_id(&attachment.id)
+                .version()
+                .version_number(number)
+                .execute::<Value>()
+                .unwrap();
+            status
+        };
+        assert_eq!(delete("3"), 400);
+        assert_eq!(delete("1"), 204);
+        assert_eq!(versions(), vec![3, 2]);
+        assert!(confluence
+            .download_attachment(&archive, "notes.txt", Some(1))
+            .is_err());
+    }
+
+    #[test]
+    fn cql_search_is_paginated() {
+        let server = FakeConfluence::start().unwrap().with_space("DOC", "Docs");
+        server.add_space("OPS", "Operations");
+        let confluence = server.client();
+        for title in &["Release 1", "Release 2", "Release 3", "Roadmap"] {
+            create(&confluence, title, None);
+        }
+
+        let search = |query: &str| {
+            let (_, status, d) = confluence
+                .get()
+                .custom_endpoint(&format!("content/search?{}", query))
+                .execute::<Value>()
+                .unwrap();
+            (status, d.unwrap())
+        };
+        let cql =
+            crate::util::encode_query(r#"space = "DOC" AND title ~ "release" order by title"#);
+        let (status, first) = search(&format!("cql={}&limit=2", cql));
+        assert_eq!(status, 200);
+        assert_eq!(first["size"], 2);
+        let next = first["_links"]["next"].as_str().unwrap();
+        let (_, second) = search(next.split_once('?').unwrap().1);
+        assert_eq!(second["size"], 1);
+        assert_eq!(second["results"][0]["title"], "Release 3");
+        assert!(second["_links"]["next"].is_null());
+
+        let (_, none) = search(&format!("cql={}", crate::util::encode_query("space = OPS")));
+        assert_eq!(none["size"], 0);
+        let (status, _) = search("cql=title%20%3E%201");
+        assert_eq!(status, 400);
+    }
+}
@@ -0,0 +1,879 @@
+//! The in-memory content of a fake server and its JSON representation.
+
+use super::http::{Part, Response};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A content property.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub id: String,
+    pub key: String,
+    pub value: Value,
+    pub version: u64,
+}
+
+/// The users and groups an operation on content is restricted to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StoredRestriction {
+    /// `read` or `update`.
+    pub operation: String,
+    pub usernames: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+/// A user, by username, or a group to restrict an operation to.
+pub(crate) enum Subject {
+    User(String),
+    Group(String),
+}
+
+/// A previous version of an attachment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredVersion {
+    pub number: u64,
+    pub when: String,
+    pub title: String,
+    pub media_type: Option<String>,
+    pub comment: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// A page, blog post, comment or attachment as stored by the fake server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredContent {
+    pub id: String,
+    /// `page`, `blogpost`, `comment` or `attachment`.
+    pub content_type: String,
+    pub title: String,
+    pub space_key: String,
+    /// The parent of a page or the container of a comment or attachment.
+    pub parent_id: Option<String>,
+    pub version: u64,
+    /// When the current version was created.
+    pub when: String,
+    /// The body in storage format.
+    pub body: String,
+    pub labels: Vec<String>,
+    pub properties: Vec<Property>,
+    pub media_type: Option<String>,
+    pub comment: Option<String>,
+    /// The data of an attachment.
+    pub data: Vec<u8>,
+    /// The previous versions of an attachment, oldest first.
+    pub versions: Vec<StoredVersion>,
+    /// The restricted operations, without empty ones.
+    pub restrictions: Vec<StoredRestriction>,
+}
+
+/// The answer to a request that can't be served.
+pub(crate) type Rejected<T> = std::result::Result<T, Response>;
+
+#[derive(Default)]
+pub(crate) struct Store {
+    next_id: u64,
+    /// Space names by key.
+    pub spaces: BTreeMap<String, String>,
+    /// Usernames by user key.
+    pub users: BTreeMap<String, String>,
+    /// Content in the order it was created.
+    pub contents: Vec<StoredContent>,
+    /// The largest `limit` a listing answers with.
+    pub max_limit: Option<usize>,
+}
+
+impl Store {
+    pub fn new() -> Self {
+        let mut users = BTreeMap::new();
+        users.insert("fake-admin".to_string(), "admin".to_string());
+        Self {
+            next_id: 65_537,
+            users,
+            ..Self::default()
+        }
+    }
+
+    fn id(&mut self) -> String {
+        self.next_id += 1;
+        self.next_id.to_string()
+    }
+
+    pub fn get(&self, id: &str) -> Rejected<&StoredContent> {
+        self.contents
+            .iter()
+            .find(|c| c.id == id)
+            .ok_or_else(|| not_found(id))
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Rejected<&mut StoredContent> {
+        self.contents
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| not_found(id))
+    }
+
+    pub fn children<'s>(
+        &'s self,
+        id: &'s str,
+        content_type: &'s str,
+    ) -> impl Iterator<Item = &'s StoredContent> {
+        self.contents
+            .iter()
+            .filter(move |c| c.parent_id.as_deref() == Some(id) && c.content_type == content_type)
+    }
+
+    /// The ancestors of `content`, the root first.
+    pub fn ancestors(&self, content: &StoredContent) -> Vec<&StoredContent> {
+        let mut ancestors = Vec::new();
+        let mut parent = content.parent_id.as_deref();
+        while let Some(c) = parent.and_then(|id| self.get(id).ok()) {
+            ancestors.insert(0, c);
+            parent = c.parent_id.as_deref();
+        }
+        ancestors
+    }
+
+    fn title_taken(&self, space_key: &str, title: &str, except: &str) -> bool {
+        self.contents.iter().any(|c| {
+            c.content_type == "page"
+                && c.space_key == space_key
+                && c.title == title
+                && c.id != except
+        })
+    }
+
+    /// The parent named by the `ancestors` of a create or update request.
+    fn parent(&self, request: &Value, space_key: &str) -> Rejected<Option<String>> {
+        let id = match request["ancestors"].as_array().and_then(|a| a.last()) {
+            Some(ancestor) => ancestor["id"]
+                .as_str()
+                .map(str::to_string)
+                .or_else(|| ancestor["id"].as_u64().map(|id| id.to_string())),
+            None => return Ok(None),
+        };
+        let id = id.ok_or_else(|| bad_request("Ancestor without id"))?;
+        match self.get(&id) {
+            Ok(parent) if parent.content_type == "page" && parent.space_key == space_key => {
+                Ok(Some(id))
+            }
+            _ => Err(bad_request(&format!(
+                "Can't add a page with a parent {} that isn't a page in space {}",
+                id, space_key
+            ))),
+        }
+    }
+
+    pub fn create(&mut self, request: &Value) -> Rejected<String> {
+        let content_type = request["type"].as_str().unwrap_or("page");
+        if content_type == "comment" {
+            return self.create_comment(request);
+        }
+        if content_type != "page" && content_type != "blogpost" {
+            return Err(bad_request(&format!(
+                "Unsupported content type: {}",
+                content_type
+            )));
+        }
+        let title = request["title"].as_str().unwrap_or("");
+        if title.is_empty() {
+            return Err(bad_request("Title cannot be empty"));
+        }
+        let space_key = request["space"]["key"].as_str().unwrap_or("");
+        if !self.spaces.contains_key(space_key) {
+            return Err(Response::error(
+                404,
+                &format!("No space with key : {}", space_key),
+            ));
+        }
+        if content_type == "page" && self.title_taken(space_key, title, "") {
+            return Err(title_taken(space_key, title));
+        }
+        let parent_id = self.parent(request, space_key)?;
+        let id = self.id();
+        self.contents.push(StoredContent {
+            id: id.clone(),
+            content_type: content_type.to_string(),
+            title: title.to_string(),
+            space_key: space_key.to_string(),
+            parent_id,
+            version: 1,
+            when: now(),
+            body: request["body"]["storage"]["value"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            labels: Vec::new(),
+            properties: Vec::new(),
+            media_type: None,
+            comment: None,
+            data: Vec::new(),
+            versions: Vec::new(),
+            restrictions: Vec::new(),
+        });
+        Ok(id)
+    }
+
+    /// Adds a comment to its `container`. Replies are added to the
+    /// container too, their `ancestors` aren't kept.
+    fn create_comment(&mut self, request: &Value) -> Rejected<String> {
+        let container = &request["container"]["id"];
+        let container_id = container
+            .as_str()
+            .map(str::to_string)
+            .or_else(|| container.as_u64().map(|id| id.to_string()))
+            .ok_or_else(|| bad_request("A comment needs a container"))?;
+        let container = self.get(&container_id)?;
+        if container.content_type != "page" && container.content_type != "blogpost" {
+            return Err(bad_request(&format!(
+                "Can't add a comment to {}",
+                container.content_type
+            )));
+        }
+        let title = format!("Re: {}", container.title);
+        let space_key = container.space_key.clone();
+        let id = self.id();
+        self.contents.push(StoredContent {
+            id: id.clone(),
+            content_type: "comment".to_string(),
+            title,
+            space_key,
+            parent_id: Some(container_id),
+            version: 1,
+            when: now(),
+            body: request["body"]["storage"]["value"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            labels: Vec::new(),
+            properties: Vec::new(),
+            media_type: None,
+            comment: None,
+            data: Vec::new(),
+            versions: Vec::new(),
+            restrictions: Vec::new(),
+        });
+        Ok(id)
+    }
+
+    /// Applies an update, which must carry the next version number.
+    pub fn update(&mut self, id: &str, request: &Value) -> Rejected<()> {
+        let current = self.get(id)?.clone();
+        let version = request["version"]["number"].as_u64().unwrap_or(0);
+        if version != current.version + 1 {
+            return Err(Response::error(
+                409,
+                &format!(
+                    "Version must be incremented on update. Current version is: {}",
+                    current.version
+                ),
+            ));
+        }
+        let title = request["title"].as_str().unwrap_or(&current.title);
+        if title.is_empty() {
+            return Err(bad_request("Title cannot be empty"));
+        }
+        if current.content_type == "page" && self.title_taken(&current.space_key, title, id) {
+            return Err(title_taken(&current.space_key, title));
+        }
+        let parent_id = match self.parent(request, &current.space_key)? {
+            Some(parent) => {
+                let parent_content = self.get(&parent)?;
+                if parent == id || self.ancestors(parent_content).iter().any(|a| a.id == id) {
+                    return Err(bad_request("A page can't be moved below itself"));
+                }
+                Some(parent)
+            }
+            None => current.parent_id.clone(),
+        };
+        let content = self.get_mut(id)?;
+        content.title = title.to_string();
+        content.parent_id = parent_id;
+        if let Some(body) = request["body"]["storage"]["value"].as_str() {
+            content.body = body.to_string();
+        }
+        content.version = version;
+        content.when = now();
+        Ok(())
+    }
+
+    /// Deletes content and the attachments and comments of a page, whose
+    /// child pages move up to its parent.
+    pub fn delete(&mut self, id: &str) -> Rejected<()> {
+        let parent_id = self.get(id)?.parent_id.clone();
+        self.contents.retain(|c| {
+            let contained = matches!(c.content_type.as_str(), "attachment" | "comment");
+            c.id != id && !(contained && c.parent_id.as_deref() == Some(id))
+        });
+        for child in self.contents.iter_mut() {
+            if child.parent_id.as_deref() == Some(id) {
+                child.parent_id = parent_id.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the file in `part` to `container_id`, or a new version of the
+    /// attachment with its name if `replace` is set.
+    pub fn attach(
+        &mut self,
+        container_id: &str,
+        part: &Part,
+        comment: Option<&str>,
+        replace: bool,
+    ) -> Rejected<String> {
+        let container = self.get(container_id)?;
+        let space_key = container.space_key.clone();
+        let filename = part
+            .filename
+            .clone()
+            .ok_or_else(|| bad_request("File part without a file name"))?;
+        let existing = self
+            .children(container_id, "attachment")
+            .find(|a| a.title == filename)
+            .map(|a| a.id.clone());
+        match existing {
+            Some(id) if replace => {
+                self.update_data(container_id, &id, part, comment)?;
+                Ok(id)
+            }
+            Some(_) => Err(bad_request(&format!(
+                "Cannot add a new attachment with same file name as an existing attachment: {}",
+                filename
+            ))),
+            None => {
+                let id = self.id();
+                self.contents.push(StoredContent {
+                    id: id.clone(),
+                    content_type: "attachment".to_string(),
+                    title: filename,
+                    space_key,
+                    parent_id: Some(container_id.to_string()),
+                    version: 1,
+                    when: now(),
+                    body: String::new(),
+                    labels: Vec::new(),
+                    properties: Vec::new(),
+                    media_type: Some(media_type(part)),
+                    comment: comment.map(str::to_string),
+                    data: part.data.clone(),
+                    versions: Vec::new(),
+                    restrictions: Vec::new(),
+                });
+                Ok(id)
+            }
+        }
+    }
+
+    /// Stores a new version of the data of an attachment.
+    pub fn update_data(
+        &mut self,
+        container_id: &str,
+        id: &str,
+        part: &Part,
+        comment: Option<&str>,
+    ) -> Rejected<()> {
+        let attachment = self.attachment_mut(container_id, id)?;
+        attachment.keep_version();
+        if let Some(filename) = &part.filename {
+            attachment.title = filename.clone();
+        }
+        attachment.media_type = Some(media_type(part));
+        attachment.data = part.data.clone();
+        attachment.comment = comment.map(str::to_string);
+        attachment.version += 1;
+        attachment.when = now();
+        Ok(())
+    }
+
+    /// Renames an attachment, changes its media type or comment, or moves
+    /// it to another container. The request must carry the next version
+    /// number.
+    pub fn update_attachment(
+        &mut self,
+        container_id: &str,
+        id: &str,
+        request: &Value,
+    ) -> Rejected<()> {
+        let current = self.attachment_mut(container_id, id)?.clone();
+        let version = request["version"]["number"].as_u64().unwrap_or(0);
+        if version != current.version + 1 {
+            return Err(Response::error(
+                409,
+                &format!(
+                    "Version must be incremented on update. Current version is: {}",
+                    current.version
+                ),
+            ));
+        }
+        let title = request["title"].as_str().unwrap_or(&current.title);
+        if title.is_empty() {
+            return Err(bad_request("Title cannot be empty"));
+        }
+        let container = request["container"]["id"]
+            .as_str()
+            .unwrap_or(container_id)
+            .to_string();
+        match self.get(&container)?.content_type.as_str() {
+            "page" | "blogpost" => {}
+            other => return Err(bad_request(&format!("Can't attach a file to {}", other))),
+        }
+        if self
+            .children(&container, "attachment")
+            .any(|a| a.title == title && a.id != id)
+        {
+            return Err(bad_request(&format!(
+                "Cannot add a new attachment with same file name as an existing attachment: {}",
+                title
+            )));
+        }
+        let space_key = self.get(&container)?.space_key.clone();
+        let attachment = self.get_mut(id)?;
+        attachment.keep_version();
+        attachment.title = title.to_string();
+        attachment.parent_id = Some(container);
+        attachment.space_key = space_key;
+        let metadata = &request["metadata"];
+        if let Some(media_type) = metadata["mediaType"].as_str() {
+            attachment.media_type = Some(media_type.to_string());
+        }
+        if let Some(comment) = metadata["comment"].as_str() {
+            attachment.comment = Some(comment.to_string());
+        }
+        attachment.version = version;
+        attachment.when = now();
+        Ok(())
+    }
+
+    /// Deletes a previous version of an attachment.
+    pub fn delete_version(&mut self, id: &str, number: u64) -> Rejected<()> {
+        let content = self.get_mut(id)?;
+        if number == content.version {
+            return Err(bad_request("Cannot delete the current version"));
+        }
+        let before = content.versions.len();
+        content.versions.retain(|v| v.number != number);
+        if content.versions.len() == before {
+            return Err(Response::error(
+                404,
+                &format!("No version {} of content {}", number, id),
+            ));
+        }
+        Ok(())
+    }
+
+    fn attachment_mut(&mut self, container_id: &str, id: &str) -> Rejected<&mut StoredContent> {
+        let attachment = self.get_mut(id)?;
+        if attachment.content_type != "attachment"
+            || attachment.parent_id.as_deref() != Some(container_id)
+        {
+            return Err(not_found(id));
+        }
+        Ok(attachment)
+    }
+
+    /// The username of a user given by `username` or `key`.
+    pub fn username(&self, username: Option<&str>, key: Option<&str>) -> Rejected<String> {
+        self.users
+            .iter()
+            .find(|(k, u)| username == Some(u.as_str()) || key == Some(k.as_str()))
+            .map(|(_, username)| username.clone())
+            .ok_or_else(|| Response::error(404, "No user found"))
+    }
+
+    /// Adds a user or group to the restriction of `operation`, or removes
+    /// one.
+    pub fn restrict(
+        &mut self,
+        id: &str,
+        operation: &str,
+        subject: Subject,
+        add: bool,
+    ) -> Rejected<()> {
+        if operation != "read" && operation != "update" {
+            return Err(bad_request(&format!("Unknown operation {}", operation)));
+        }
+        let content = self.get_mut(id)?;
+        if !content
+            .restrictions
+            .iter()
+            .any(|r| r.operation == operation)
+        {
+            content.restrictions.push(StoredRestriction {
+                operation: operation.to_string(),
+                ..StoredRestriction::default()
+            });
+        }
+        let restriction = content
+            .restrictions
+            .iter_mut()
+            .find(|r| r.operation == operation)
+            .unwrap();
+        let (subjects, name) = match subject {
+            Subject::User(username) => (&mut restriction.usernames, username),
+            Subject::Group(group) => (&mut restriction.groups, group),
+        };
+        let present = subjects.contains(&name);
+        if add && !present {
+            subjects.push(name);
+        } else if !add {
+            if !present {
+                return Err(Response::error(
+                    404,
+                    &format!("{} is not in the {} restriction of {}", name, operation, id),
+                ));
+            }
+            subjects.retain(|s| *s != name);
+        }
+        content
+            .restrictions
+            .retain(|r| !r.usernames.is_empty() || !r.groups.is_empty());
+        Ok(())
+    }
+
+    /// Applies a list of restrictions as sent to `content/{id}/restriction`,
+    /// after removing all others if `replace`.
+    pub fn apply_restrictions(&mut self, id: &str, request: &Value, replace: bool) -> Rejected<()> {
+        let mut subjects = Vec::new();
+        for restriction in request.as_array().into_iter().flatten() {
+            let operation = restriction["operation"]
+                .as_str()
+                .ok_or_else(|| bad_request("A restriction needs an operation"))?;
+            let restrictions = &restriction["restrictions"];
+            for user in restrictions["user"].as_array().into_iter().flatten() {
+                let username =
+                    self.username(user["username"].as_str(), user["userKey"].as_str())?;
+                subjects.push((operation, Subject::User(username)));
+            }
+            for group in restrictions["group"].as_array().into_iter().flatten() {
+                let name = group["name"]
+                    .as_str()
+                    .ok_or_else(|| bad_request("A group needs a name"))?;
+                subjects.push((operation, Subject::Group(name.to_string())));
+            }
+        }
+        if replace {
+            self.get_mut(id)?.restrictions.clear();
+        }
+        for (operation, subject) in subjects {
+            self.restrict(id, operation, subject, true)?;
+        }
+        Ok(())
+    }
+
+    /// The `read` and `update` restrictions of content, with their users
+    /// and groups expanded.
+    pub fn restrictions_json(&self, id: &str, api: &str) -> Rejected<Vec<Value>> {
+        let content = self.get(id)?;
+        Ok(["read", "update"]
+            .iter()
+            .map(|operation| {
+                let restriction = content
+                    .restrictions
+                    .iter()
+                    .find(|r| r.operation == *operation)
+                    .cloned()
+                    .unwrap_or_default();
+                let users: Vec<Value> = restriction
+                    .usernames
+                    .iter()
+                    .map(|username| {
+                        let key = self
+                            .users
+                            .iter()
+                            .find(|(_, u)| *u == username)
+                            .map_or("", |(k, _)| k.as_str());
+                        user_json(key, username, api)
+                    })
+                    .collect();
+                let groups: Vec<Value> = restriction
+                    .groups
+                    .iter()
+                    .map(|name| json!({ "type": "group", "name": name }))
+                    .collect();
+                json!({
+                    "operation": operation,
+                    "restrictions": {
+                        "user": { "results": users, "start": 0, "limit": 200, "size": users.len() },
+                        "group": { "results": groups, "start": 0, "limit": 200, "size": groups.len() },
+                    },
+                    "_links": {
+                        "self": format!("{}/content/{}/restriction/byOperation/{}", api, id, operation),
+                    },
+                    "_expandable": { "content": format!("/rest/api/content/{}", id) },
+                })
+            })
+            .collect())
+    }
+
+    pub fn property_id(&mut self) -> String {
+        self.id()
+    }
+
+    /// Renders `content` as the REST API does with `expand`.
+    pub fn json(&self, content: &StoredContent, expand: &[String], base: &str) -> Value {
+        let expanded = |name: &str| expand.iter().any(|e| e == name);
+        let api = format!("{}/rest/api", base);
+        let attachment = content.content_type == "attachment";
+        let comment = content.content_type == "comment";
+        let mut json = json!({
+            "id": content.id,
+            "type": content.content_type,
+            "status": "current",
+            "title": content.title,
+        });
+        let mut expandable = Map::new();
+        let mut add = |json: &mut Value, name: &str, value: Option<Value>, path: String| match value
+        {
+            Some(value) => json[name] = value,
+            None => {
+                expandable.insert(name.to_string(), Value::String(path));
+            }
+        };
+
+        let version = (attachment || expanded("version"))
+            .then(|| version_json(&content.id, content.version, &content.when, &api));
+        add(&mut json, "version", version, String::new());
+        let space = expanded("space").then(|| self.space_json(&content.space_key, &api));
+        add(
+            &mut json,
+            "space",
+            space,
+            format!("/rest/api/space/{}", content.space_key),
+        );
+        let body = expanded("body.storage").then(|| {
+            json!({ "storage": {
+                "value": content.body,
+                "representation": "storage",
+                "_expandable": { "content": format!("/rest/api/content/{}", content.id) },
+            }})
+        });
+        add(&mut json, "body", body, String::new());
+        let ancestors = expanded("ancestors").then(|| {
+            if comment {
+                return Value::Array(Vec::new());
+            }
+            Value::Array(
+                self.ancestors(content)
+                    .into_iter()
+                    .map(|a| self.json(a, &[], base))
+                    .collect(),
+            )
+        });
+        add(&mut json, "ancestors", ancestors, String::new());
+        let container = (attachment || expanded("container")).then(|| {
+            match content.parent_id.as_deref().map(|id| self.get(id)) {
+                Some(Ok(parent)) if attachment || comment => self.json(parent, &[], base),
+                _ => self.space_json(&content.space_key, &api),
+            }
+        });
+        add(
+            &mut json,
+            "container",
+            container,
+            format!("/rest/api/space/{}", content.space_key),
+        );
+        for name in &["children", "descendants", "history", "restrictions"] {
+            let path = format!("/rest/api/content/{}/{}", content.id, name);
+            add(&mut json, name, None, path);
+        }
+        add(&mut json, "operations", None, String::new());
+
+        let labels = (attachment || expanded("metadata.labels")).then(|| {
+            results_json(
+                content.labels.iter().map(|l| label_json(l)).collect(),
+                0,
+                200,
+                format!("{}/content/{}/label", api, content.id),
+                base,
+                None,
+            )
+        });
+        match (labels, &content.media_type) {
+            (Some(labels), Some(media_type)) => {
+                json["metadata"] = json!({ "mediaType": media_type, "labels": labels })
+            }
+            (Some(labels), None) => json["metadata"] = json!({ "labels": labels }),
+            (None, _) => add(&mut json, "metadata", None, String::new()),
+        }
+        if attachment {
+            json["extensions"] = json!({
+                "mediaType": content.media_type,
+                "fileSize": content.data.len(),
+                "comment": content.comment.clone().unwrap_or_default(),
+            });
+        }
+
+        let mut links = json!({
+            "webui": format!("/pages/viewpage.action?pageId={}", content.id),
+            "self": format!("{}/content/{}", api, content.id),
+        });
+        if let (true, Some(container)) = (attachment, &content.parent_id) {
+            links["download"] = Value::String(download_path(container, content));
+        }
+        json["_links"] = links;
+        json["_expandable"] = Value::Object(expandable);
+        json
+    }
+
+    pub fn space_json(&self, key: &str, api: &str) -> Value {
+        let id = self.spaces.keys().position(|k| k == key).unwrap_or(0) + 98_305;
+        json!({
+            "id": id,
+            "key": key,
+            "name": self.spaces.get(key).cloned().unwrap_or_default(),
+            "type": if key.starts_with('~') { "personal" } else { "global" },
+            "status": "current",
+            "_links": {
+                "webui": format!("/display/{}", key),
+                "self": format!("{}/space/{}", api, key),
+            },
+            "_expandable": { "homepage": "", "description": "", "metadata": "" },
+        })
+    }
+}
+
+impl StoredContent {
+    /// Adds the current version to the previous ones, before a change.
+    fn keep_version(&mut self) {
+        self.versions.push(StoredVersion {
+            number: self.version,
+            when: self.when.clone(),
+            title: self.title.clone(),
+            media_type: self.media_type.clone(),
+            comment: self.comment.clone(),
+            data: self.data.clone(),
+        });
+    }
+}
+
+pub(crate) fn user_json(key: &str, username: &str, api: &str) -> Value {
+    json!({
+        "type": "known",
+        "username": username,
+        "userKey": key,
+        "displayName": username,
+        "_links": { "self": format!("{}/user?key={}", api, key) },
+        "_expandable": { "status": "" },
+    })
+}
+
+pub(crate) fn download_path(container: &str, attachment: &StoredContent) -> String {
+    format!(
+        "/download/attachments/{}/{}?version={}&api=v2",
+        container,
+        crate::util::encode_query(&attachment.title),
+        attachment.version
+    )
+}
+
+pub(crate) fn results_json(
+    results: Vec<Value>,
+    start: usize,
+    limit: usize,
+    self_url: String,
+    base: &str,
+    next: Option<String>,
+) -> Value {
+    let mut links = json!({ "self": self_url, "base": base, "context": "" });
+    if let Some(next) = next {
+        links["next"] = Value::String(next);
+    }
+    json!({
+        "results": results,
+        "start": start,
+        "limit": limit,
+        "size": results.len(),
+        "_links": links,
+    })
+}
+
+pub(crate) fn label_json(name: &str) -> Value {
+    // Label ids are global per name, so derive them from it.
+    let id = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+    }) % 1_000_000_000;
+    json!({ "prefix": "global", "name": name, "id": id.to_string(), "label": name })
+}
+
+pub(crate) fn property_json(content_id: &str, property: &Property, api: &str) -> Value {
+    json!({
+        "id": property.id,
+        "key": property.key,
+        "value": property.value,
+        "version": { "number": property.version, "minorEdit": false, "hidden": false },
+        "_links": {
+            "self": format!("{}/content/{}/property/{}", api, content_id, property.key),
+        },
+        "_expandable": { "content": format!("/rest/api/content/{}", content_id) },
+    })
+}
+
+pub(crate) fn version_json(id: &str, number: u64, when: &str, api: &str) -> Value {
+    json!({
+        "by": {
+            "type": "known",
+            "username": "admin",
+            "userKey": "fake-admin",
+            "profilePicture": {
+                "path": "/images/icons/profilepics/default.svg",
+                "width": 48,
+                "height": 48,
+                "isDefault": true,
+            },
+            "displayName": "Administrator",
+            "_links": { "self": format!("{}/user?key=fake-admin", api) },
+            "_expandable": { "status": "" },
+        },
+        "when": when,
+        "message": "",
+        "number": number,
+        "minorEdit": false,
+        "hidden": false,
+        "_links": { "self": format!("{}/content/{}/version/{}", api, id, number) },
+        "_expandable": { "content": format!("/rest/api/content/{}", id) },
+    })
+}
+
+fn media_type(part: &Part) -> String {
+    part.content_type
+        .clone()
+        .unwrap_or_else(|| "application/octet-stream".to_string())
+}
+
+fn now() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (y, m, d) = crate::util::civil_from_days(secs.div_euclid(86_400));
+    let t = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        y,
+        m,
+        d,
+        t / 3600,
+        t % 3600 / 60,
+        t % 60,
+        now.subsec_millis()
+    )
+}
+
+pub(crate) fn not_found(id: &str) -> Response {
+    Response::error(
+        404,
+        &format!("No content found with id: ContentId{{id={}}}", id),
+    )
+}
+
+pub(crate) fn bad_request(message: &str) -> Response {
+    Response::error(400, message)
+}
+
+fn title_taken(space_key: &str, title: &str) -> Response {
+    bad_request(&format!(
+        "A page with this title already exists: A page already exists with the title {} in the space with key {}",
+        title, space_key
+    ))
+}
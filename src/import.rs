@@ -0,0 +1,500 @@
+//! Restoring an [`export`](crate::export) into a space, on the same or
+//! another instance.
+//!
+//! Pages are created parent first, below their exported parent or, for the
+//! top of the tree, below an optional parent page. Attachments are uploaded
+//! again and labels and properties set. References in the bodies are
+//! rewritten for the new space and ids: `ri:space-key` attributes,
+//! `ri:content-id` attributes and `pageId=` and `/pages/<id>` links to
+//! imported content. Pages whose links point at content created after them
+//! are updated once all content exists.
+//!
+//! ```no_run
+//! use confluence::client::Confluence;
+//! use confluence::import::SpaceImporter;
+//!
+//! let confluence = Confluence::new("https://staging.example.com/rest/api");
+//! let report = SpaceImporter::new(&confluence, "backup/DOCS", "DOCS2").import().unwrap();
+//! print!("{}", report);
+//! ```
+
+use crate::client::{Confluence, Executor, Result};
+use crate::content::model::Ancestor;
+use crate::deployment::{ContentApi, DeploymentClient};
+use crate::export::{
+    file_name, read_metadata, ExportedContent, ATTACHMENTS_DIR, BODY_FILE, CONTENT_TYPES,
+};
+use crate::model::{
+    Body, CreatePageRequest, Representation, Space, Storage, UpdatePageRequest, Version,
+};
+use crate::util::expect;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+/// An exported id and the id of its imported copy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdMapping {
+    /// `page`, `blogpost` or `attachment`.
+    pub content_type: String,
+    pub title: String,
+    pub old_id: String,
+    pub new_id: String,
+}
+
+/// The ids of the imported content, in the order it was created. Printed
+/// one mapping per line by `Display`.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub mappings: Vec<IdMapping>,
+}
+
+impl ImportReport {
+    /// The new id of exported content.
+    pub fn new_id(&self, old_id: &str) -> Option<&str> {
+        self.mappings
+            .iter()
+            .find(|m| m.old_id == old_id)
+            .map(|m| m.new_id.as_str())
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for m in &self.mappings {
+            writeln!(
+                f,
+                "{} -> {}  {} {}",
+                m.old_id, m.new_id, m.content_type, m.title
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct SpaceImporter {
+    confluence: Confluence,
+    dir: PathBuf,
+    space_key: String,
+    parent: Option<Ancestor>,
+}
+
+/// Exported content with its body, read from the export.
+struct Entry {
+    dir: PathBuf,
+    metadata: ExportedContent,
+    body: String,
+}
+
+impl SpaceImporter {
+    /// Imports the export in `dir` into the space `space_key`, which has to
+    /// exist.
+    pub fn new(confluence: &Confluence, dir: impl Into<PathBuf>, space_key: &str) -> Self {
+        Self {
+            confluence: confluence.clone(),
+            dir: dir.into(),
+            space_key: space_key.to_string(),
+            parent: None,
+        }
+    }
+
+    /// Creates the top pages of the export below `parent` instead of at
+    /// the root of the space.
+    pub fn with_parent(mut self, parent: Ancestor) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn import(&self) -> Result<ImportReport> {
+        let api = DeploymentClient::detect(&self.confluence)?;
+        let entries = self.read()?;
+        let exported: HashSet<&str> = entries
+            .iter()
+            .flat_map(|e| {
+                let attachments = e.metadata.attachments.iter().map(|a| a.id.as_str());
+                std::iter::once(e.metadata.id.as_str()).chain(attachments)
+            })
+            .collect();
+
+        let mut ids = BTreeMap::new();
+        let mut report = ImportReport::default();
+        let mut pending = Vec::new();
+        for entry in &entries {
+            let metadata = &entry.metadata;
+            let parent = metadata
+                .ancestors
+                .last()
+                .filter(|_| metadata.content_type == "page")
+                .map(|a| a.id.as_str());
+            let parent = match parent {
+                Some(parent) if exported.contains(parent) => ids.get(parent).cloned(),
+                _ => self.parent.as_ref().map(|p| p.id.clone()),
+            };
+            let (body, resolved) = self.rewrite(entry, &ids, &exported);
+            let request = CreatePageRequest::new(
+                &metadata.content_type,
+                &metadata.title,
+                Space::new(&self.space_key),
+                Body::new(Storage::new(&body, Representation::Storage)),
+                parent.map(|p| vec![Ancestor::new(&p)]),
+            );
+            let (_, status, d) = self.confluence.post(request).content().execute::<Value>()?;
+            let created = expect(status, d, &format!("create {}", metadata.title))?;
+            let new_id = created["id"]
+                .as_str()
+                .ok_or_else(|| format!("Unable to create {}: no id", metadata.title))?
+                .to_string();
+            ids.insert(metadata.id.clone(), new_id.clone());
+            report.mappings.push(IdMapping {
+                content_type: metadata.content_type.clone(),
+                title: metadata.title.clone(),
+                old_id: metadata.id.clone(),
+                new_id: new_id.clone(),
+            });
+            if !resolved {
+                pending.push((entry, new_id.clone()));
+            }
+
+            for label in &metadata.labels {
+                self.send_json(
+                    self.confluence
+                        .post(json!([{ "prefix": "global", "name": label }]))
+                        .custom_endpoint(&format!("content/{}/label", new_id)),
+                    "add label",
+                )?;
+            }
+            for (key, value) in &metadata.properties {
+                self.send_json(
+                    self.confluence
+                        .post(json!({ "key": key, "value": value }))
+                        .custom_endpoint(&format!("content/{}/property", new_id)),
+                    "set property",
+                )?;
+            }
+            for attachment in &metadata.attachments {
+                let path = entry
+                    .dir
+                    .join(ATTACHMENTS_DIR)
+                    .join(file_name(&attachment.title));
+                let data = std::fs::read(&path)
+                    .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+                let media_type = attachment
+                    .media_type
+                    .as_deref()
+                    .unwrap_or("application/octet-stream");
+                let uploaded =
+                    api.upload_attachment(&new_id, &attachment.title, data, media_type)?;
+                ids.insert(attachment.id.clone(), uploaded.id.clone());
+                report.mappings.push(IdMapping {
+                    content_type: "attachment".to_string(),
+                    title: attachment.title.clone(),
+                    old_id: attachment.id.clone(),
+                    new_id: uploaded.id,
+                });
+            }
+        }
+
+        // Links to content created later can only be rewritten now.
+        for (entry, new_id) in pending {
+            let metadata = &entry.metadata;
+            let (body, _) = self.rewrite(entry, &ids, &exported);
+            let (_, status, current) = self
+                .confluence
+                .get()
+                .custom_endpoint(&format!("content/{}?expand=version,ancestors", new_id))
+                .execute::<Value>()?;
+            let current = expect(status, current, &format!("get {}", metadata.title))?;
+            let version = current["version"]["number"].as_u64().unwrap_or(1) + 1;
+            let mut request = UpdatePageRequest::new(
+                &new_id,
+                &metadata.content_type,
+                &metadata.title,
+                Space::new(&self.space_key),
+                Body::new(Storage::new(&body, Representation::Storage)),
+                None,
+                Version::new(version),
+            );
+            request.ancestors = current["ancestors"]
+                .as_array()
+                .and_then(|a| a.last())
+                .and_then(|a| a["id"].as_str())
+                .map(|id| vec![Ancestor::new(id)]);
+            let (_, status, d) = self
+                .confluence
+                .put(request)
+                .content()
+                .content_id(&new_id)
+                .execute::<Value>()?;
+            expect(status, d, &format!("update {}", metadata.title))?;
+        }
+        Ok(report)
+    }
+
+    /// The exported pages, parents before their children, then blog posts.
+    fn read(&self) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for content_type in CONTENT_TYPES {
+            let type_dir = self.dir.join(content_type);
+            let dirs = match std::fs::read_dir(&type_dir) {
+                Ok(dirs) => dirs,
+                Err(_) => continue,
+            };
+            for dir in dirs {
+                let dir = dir?.path();
+                let metadata = read_metadata(&dir)
+                    .ok_or_else(|| format!("Unable to read the metadata in {}", dir.display()))?;
+                let body = std::fs::read_to_string(dir.join(BODY_FILE)).unwrap_or_default();
+                entries.push(Entry {
+                    dir,
+                    metadata,
+                    body,
+                });
+            }
+        }
+        entries.sort_by(|a, b| {
+            let key = |e: &Entry| {
+                (
+                    e.metadata.content_type != "page",
+                    e.metadata.ancestors.len(),
+                    e.metadata.id.parse::<u64>().unwrap_or(u64::MAX),
+                    e.metadata.id.clone(),
+                )
+            };
+            key(a).cmp(&key(b))
+        });
+        Ok(entries)
+    }
+
+    fn rewrite(
+        &self,
+        entry: &Entry,
+        ids: &BTreeMap<String, String>,
+        exported: &HashSet<&str>,
+    ) -> (String, bool) {
+        rewrite_references(
+            &entry.body,
+            &entry.metadata.space_key,
+            &self.space_key,
+            ids,
+            exported,
+        )
+    }
+
+    fn send_json(&self, query: crate::client::CustomQuery, action: &str) -> Result<()> {
+        let (_, status, d) = query.execute::<Value>()?;
+        expect(status, d, action).map(|_| ())
+    }
+}
+
+/// `body` with references to the space `old_space` and to content in
+/// `ids` rewritten, and whether every reference to content in `known` could
+/// be.
+pub(crate) fn rewrite_references(
+    body: &str,
+    old_space: &str,
+    new_space: &str,
+    ids: &BTreeMap<String, String>,
+    known: &HashSet<&str>,
+) -> (String, bool) {
+    let mut body = body.to_string();
+    if old_space != new_space {
+        for (old, new) in [
+            (
+                format!("ri:space-key=\"{}\"", old_space),
+                format!("ri:space-key=\"{}\"", new_space),
+            ),
+            (
+                format!("/spaces/{}/", old_space),
+                format!("/spaces/{}/", new_space),
+            ),
+        ] {
+            body = body.replace(&old, &new);
+        }
+        body = replace_space_key(&body, old_space, new_space);
+    }
+    let mut resolved = true;
+    for prefix in ["ri:content-id=\"", "pageId=", "/pages/"] {
+        let (rewritten, all) = replace_ids(&body, prefix, ids, known);
+        body = rewritten;
+        resolved &= all;
+    }
+    (body, resolved)
+}
+
+/// Replaces `spaceKey=old_space` parameters, but not those of keys that
+/// merely start with it.
+fn replace_space_key(body: &str, old_space: &str, new_space: &str) -> String {
+    let prefix = format!("spaceKey={}", old_space);
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(at) = rest.find(&prefix) {
+        let (head, tail) = rest.split_at(at + prefix.len());
+        let whole = !tail
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || "_-.@~".contains(c));
+        if whole {
+            out.push_str(&head[..at]);
+            out.push_str("spaceKey=");
+            out.push_str(new_space);
+        } else {
+            out.push_str(head);
+        }
+        rest = tail;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Replaces the ids following `prefix` that are in `ids`, and tells
+/// whether no exported id was left.
+fn replace_ids(
+    body: &str,
+    prefix: &str,
+    ids: &BTreeMap<String, String>,
+    exported: &HashSet<&str>,
+) -> (String, bool) {
+    let mut out = String::with_capacity(body.len());
+    let mut resolved = true;
+    let mut rest = body;
+    while let Some(at) = rest.find(prefix) {
+        let (head, tail) = rest.split_at(at + prefix.len());
+        out.push_str(head);
+        let digits = tail.bytes().take_while(|b| b.is_ascii_digit()).count();
+        let id = &tail[..digits];
+        match ids.get(id) {
+            Some(new) if digits > 0 => out.push_str(new),
+            _ => {
+                resolved &= !exported.contains(id);
+                out.push_str(id);
+            }
+        }
+        rest = &tail[digits..];
+    }
+    out.push_str(rest);
+    (out, resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::SpaceExporter;
+    use crate::fake::FakeConfluence;
+
+    #[test]
+    fn restores_an_export_into_another_space() {
+        let source = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let confluence = source.client();
+        let home = source.create_page("DOCS", "Home", None);
+        let guide = source.create_page_with_body(
+            "DOCS",
+            "Guide",
+            &format!(
+                r#"<p><ac:link><ri:page ri:space-key="DOCS" ri:content-title="Home" /></ac:link> <a href="/pages/viewpage.action?pageId={}">home</a> <a href="/pages/viewpage.action?pageId=42">elsewhere</a></p>"#,
+                home
+            ),
+            Some(&home),
+        );
+        // Home links to its child, which is only created after it.
+        let body = format!(r#"<p><ri:content-entity ri:content-id="{}" /></p>"#, guide);
+        let update = json!({
+            "type": "page", "title": "Home", "space": { "key": "DOCS" },
+            "body": { "storage": { "value": body, "representation": "storage" } },
+            "version": { "number": 2 },
+        });
+        confluence
+            .put(update)
+            .content()
+            .content_id(&home)
+            .execute::<Value>()
+            .unwrap();
+        confluence
+            .post(json!([{ "prefix": "global", "name": "howto" }]))
+            .custom_endpoint(&format!("content/{}/label", guide))
+            .execute::<Value>()
+            .unwrap();
+        confluence
+            .post(json!({ "key": "owner", "value": "docs" }))
+            .custom_endpoint(&format!("content/{}/property", guide))
+            .execute::<Value>()
+            .unwrap();
+        let part = reqwest::blocking::multipart::Part::bytes(b"png".to_vec())
+            .file_name("setup.png")
+            .mime_str("image/png")
+            .unwrap();
+        confluence
+            .post(())
+            .content()
+            .content_id(&guide)
+            .child()
+            .attachment(reqwest::blocking::multipart::Form::new().part("file", part))
+            .execute::<Value>()
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("import-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        SpaceExporter::new(&confluence, "DOCS", &dir)
+            .export()
+            .unwrap();
+
+        let target = FakeConfluence::start().unwrap().with_space("COPY", "Copy");
+        let report = SpaceImporter::new(&target.client(), &dir, "COPY")
+            .import()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let kinds: Vec<&str> = report
+            .mappings
+            .iter()
+            .map(|m| m.content_type.as_str())
+            .collect();
+        assert_eq!(kinds, vec!["page", "page", "attachment"]);
+        let new_home = report.new_id(&home).unwrap();
+        let new_guide = report.new_id(&guide).unwrap();
+        let copied = target.find("COPY", "Guide").unwrap();
+        assert_eq!(copied.id, new_guide);
+        assert_eq!(copied.parent_id.as_deref(), Some(new_home));
+        assert_eq!(copied.labels, vec!["howto"]);
+        assert_eq!(copied.properties[0].value, json!("docs"));
+        assert!(copied.body.contains(r#"ri:space-key="COPY""#));
+        assert!(copied.body.contains(&format!("pageId={}\"", new_home)));
+        assert!(copied.body.contains("pageId=42\""));
+        let copied_home = target.find("COPY", "Home").unwrap();
+        assert!(copied_home
+            .body
+            .contains(&format!(r#"ri:content-id="{}""#, new_guide)));
+        let attachment = target
+            .contents()
+            .into_iter()
+            .find(|c| c.title == "setup.png")
+            .unwrap();
+        assert_eq!(attachment.data, b"png");
+        assert_eq!(attachment.parent_id.as_deref(), Some(new_guide));
+    }
+
+    #[test]
+    fn ids_are_rewritten_when_known() {
+        let ids: BTreeMap<String, String> = [("12".to_string(), "98".to_string())].into();
+        let exported: HashSet<&str> = ["12", "13"].into();
+        assert_eq!(
+            replace_ids("pageId=12&x pageId=123 pageId=", "pageId=", &ids, &exported),
+            ("pageId=98&x pageId=123 pageId=".to_string(), true)
+        );
+        assert_eq!(
+            replace_ids("/pages/13/Guide", "/pages/", &ids, &exported),
+            ("/pages/13/Guide".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn space_keys_are_rewritten_whole() {
+        let body = r#"<a href="/display?spaceKey=DOC&amp;x">a</a> spaceKey=DOCS spaceKey=DOC"#;
+        let (rewritten, _) =
+            rewrite_references(body, "DOC", "NEW", &BTreeMap::new(), &HashSet::new());
+        assert_eq!(
+            rewritten,
+            r#"<a href="/display?spaceKey=NEW&amp;x">a</a> spaceKey=DOCS spaceKey=NEW"#
+        );
+    }
+}
@@ -4,12 +4,33 @@ mod macros;
 #[macro_use]
 extern crate error_chain;
 
+pub mod adf;
+pub mod attachment;
+pub mod builder;
+pub mod cassette;
 pub mod client;
+pub mod config;
 pub mod content;
+pub mod contentbody;
+pub mod deployment;
 pub mod errors;
+pub mod export;
+#[cfg(any(test, feature = "fake"))]
+pub mod fake;
+pub mod import;
+pub mod markdown;
+pub mod migrate;
 pub mod model;
-
-mod util;
+pub mod ratelimit;
+pub mod restriction;
+pub mod retry;
+pub mod storage;
+pub mod sync;
+#[cfg(feature = "tracing")]
+pub mod trace;
+pub mod transport;
+pub mod util;
+pub mod v2;
 
 #[cfg(test)]
 mod tests {
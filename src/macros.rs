@@ -123,33 +123,20 @@ macro_rules! from {
                     _ => Method::GET,
                 };
 
-                let url_str = &c.host.clone();
-                let url = reqwest::Url::parse(url_str).unwrap();
-                let request = Request::new(method, url);
-                let client = c.client.clone();
-
-                let res = client
-                    .execute(request.try_clone().unwrap())
-                    .and_then(|_| Ok((request, HeaderValue::from_str("").unwrap())));
-
-                match res {
-                    Ok((mut req, token)) => {
-                        {
-                            let headers = req.headers_mut();
-                            headers.insert(reqwest::header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
-                            headers.insert(reqwest::header::USER_AGENT, HeaderValue::from_static("bardo-confluence"));
-                            headers.insert(reqwest::header::ACCEPT, HeaderValue::from_static("application/json"));
-                        }
+                match reqwest::Url::parse(&c.base_url()) {
+                    Ok(url) => {
+                        let mut req = Request::new(method, url);
+                        *req.headers_mut() = c.default_headers().clone();
                         Self {
                             request: Ok(RefCell::new(req)),
-                            client: &c.client,
+                            client: c,
                             parameter: None,
                         }
                     }
                     Err(err) => {
                         Self {
                             request: Err(Box::new(err)),
-                            client: &c.client,
+                            client: c,
                             parameter: None,
                         }
                     }
@@ -170,7 +157,7 @@ macro_rules! new_type {
         $(
         pub struct $i<'g> {
             pub(crate) request: Result<RefCell<Request>>,
-            pub(crate) client: &'g Rc<Client>,
+            pub(crate) client: &'g $crate::client::Confluence,
             pub(crate) parameter: Option<String>,
         }
         )*
@@ -185,9 +172,7 @@ macro_rules! exec {
                 T: DeserializeOwned,
             {
                 let req = self.request?.into_inner();
-                // Sometimes this stupid debug hack is useful
-                // println!("result {:?}", req);
-                let res = self.client.execute(req)?;
+                let res = self.client.send(req)?;
                 let headers = res.headers().clone();
                 let status: StatusCode = StatusCode::from(res.status());
                 match res.json() {
@@ -244,18 +229,22 @@ macro_rules! impl_macro {
                     if self.request.is_ok() {
                         // We've checked that this works
                         let mut req = self.request.unwrap();
-                        let builder = self.client.request(
-                            req.get_mut().method().clone(),
-                            req.get_mut().url().clone(),
-                        );
-                        self.request = Ok(
-                            RefCell::new(builder.multipart($e4)
-                                         .header(
-                                             "X-Atlassian-Token",
-                                             reqwest::header::HeaderValue::from_static("nocheck"))
-                                         .build()
-                                         .unwrap())
-                        )
+                        // Keep the default headers, auth included, but let
+                        // the form set the multipart content type.
+                        let mut headers = req.get_mut().headers().clone();
+                        headers.remove(reqwest::header::CONTENT_TYPE);
+                        let built = self.client.client
+                            .request(req.get_mut().method().clone(), req.get_mut().url().clone())
+                            .headers(headers)
+                            .multipart($e4)
+                            .header(
+                                "X-Atlassian-Token",
+                                reqwest::header::HeaderValue::from_static("nocheck"))
+                            .build();
+                        self.request = match built {
+                            Ok(built) => Ok(RefCell::new(built)),
+                            Err(e) => Err(format!("Unable to build the upload request: {}", e).into()),
+                        };
                     }
                     self.into()
                 }
@@ -308,7 +297,7 @@ macro_rules! func_client {
 /// Common imports for every file
 macro_rules! imports {
     () => {
-        use reqwest::blocking::{Client, Request};
+        use reqwest::blocking::Request;
 
         pub use reqwest::{
             header::{HeaderMap, HeaderValue},
@@ -317,7 +306,6 @@ macro_rules! imports {
         use serde::de::DeserializeOwned;
 
         use std::cell::RefCell;
-        use std::rc::Rc;
 
         use $crate::client::Executor;
         use $crate::client::Result;
@@ -0,0 +1,688 @@
+use std::collections::HashMap;
+
+use super::{encode_link, relative_path};
+use crate::client::Result;
+use crate::content::model::ContentServiceBodyStorage;
+use crate::storage::{self, Element, Macro, MacroBody, Node, ResourceIdentifier};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Export {
+    pub markdown: String,
+    /// Names of the attachments the page refers to, in order of appearance.
+    pub attachments: Vec<String>,
+}
+
+/// Converts storage format into GitHub flavoured Markdown.
+///
+/// Headings, lists, tables, code and noformat macros, the info, tip, note,
+/// warning and panel macros, task lists, links and images are mapped to their
+/// Markdown counterparts. Macros without a Markdown equivalent are kept as
+/// fenced `confluence` blocks holding their storage format, along with the
+/// paragraph or other block around them when they appear inline, so nothing
+/// is silently lost and `to_storage` restores them.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownExporter {
+    /// Directory of the exported document relative to the export root.
+    pub document_dir: String,
+    /// Paths of exported pages relative to the export root by page title.
+    /// Links to pages not found here point to `<title>.md`.
+    pub page_paths: HashMap<String, String>,
+    /// Directory holding the attachments, relative to `document_dir`.
+    pub attachment_dir: String,
+}
+
+impl MarkdownExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_document_dir(mut self, document_dir: &str) -> Self {
+        self.document_dir = document_dir.to_string();
+        self
+    }
+
+    pub fn with_page_paths(mut self, page_paths: HashMap<String, String>) -> Self {
+        self.page_paths = page_paths;
+        self
+    }
+
+    pub fn with_attachment_dir(mut self, attachment_dir: &str) -> Self {
+        self.attachment_dir = attachment_dir.to_string();
+        self
+    }
+
+    pub fn export(&self, storage: &str) -> Result<Export> {
+        let doc = storage::parse(storage)?;
+        let mut renderer = Renderer {
+            exporter: self,
+            attachments: Vec::new(),
+            in_table: false,
+        };
+        let blocks = renderer.blocks(&doc.nodes);
+        let mut markdown = join_blocks(&blocks, false);
+        if !markdown.is_empty() {
+            markdown.push('\n');
+        }
+        Ok(Export {
+            markdown,
+            attachments: renderer.attachments,
+        })
+    }
+
+    pub fn export_body(&self, body: &ContentServiceBodyStorage) -> Result<Export> {
+        self.export(&body.value)
+    }
+
+    fn page_link(&self, title: &str) -> String {
+        match self.page_paths.get(title) {
+            Some(path) => relative_path(&self.document_dir, path),
+            None => format!("{}.md", title),
+        }
+    }
+
+    fn attachment_link(&self, filename: &str) -> String {
+        if self.attachment_dir.is_empty() {
+            filename.to_string()
+        } else {
+            format!("{}/{}", self.attachment_dir.trim_end_matches('/'), filename)
+        }
+    }
+}
+
+/// Shorthand for `MarkdownExporter::new().export(storage)`.
+pub fn from_storage(storage: &str) -> Result<Export> {
+    MarkdownExporter::new().export(storage)
+}
+
+struct Block {
+    text: String,
+    list: bool,
+}
+
+/// Joins rendered blocks with blank lines. Inside list items a nested list
+/// directly follows the item text to keep the list tight.
+fn join_blocks(blocks: &[Block], tight: bool) -> String {
+    let mut out = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            let nested = tight && block.list && !blocks[i - 1].list;
+            out.push_str(if nested { "\n" } else { "\n\n" });
+        }
+        out.push_str(&block.text);
+    }
+    out
+}
+
+struct Renderer<'e> {
+    exporter: &'e MarkdownExporter,
+    attachments: Vec<String>,
+    in_table: bool,
+}
+
+impl<'e> Renderer<'e> {
+    /// Renders a sequence of nodes, grouping runs of inline content into
+    /// paragraphs.
+    fn blocks(&mut self, nodes: &[Node]) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut inline = String::new();
+        for node in nodes {
+            if holds_inline_macro(node) {
+                flush(&mut inline, &mut blocks);
+                blocks.push(Block {
+                    text: fence("confluence", &node.to_string()),
+                    list: false,
+                });
+            } else if is_block(node) {
+                flush(&mut inline, &mut blocks);
+                self.block(node, &mut blocks);
+            } else {
+                let text = self.inline(node);
+                inline.push_str(&text);
+            }
+        }
+        flush(&mut inline, &mut blocks);
+        blocks
+    }
+
+    fn block(&mut self, node: &Node, blocks: &mut Vec<Block>) {
+        let text = match node {
+            Node::Macro(m) => self.macro_block(m),
+            Node::Layout(_) | Node::LayoutSection(_) | Node::LayoutCell(_) => {
+                let children = &node.as_element().unwrap().children;
+                blocks.extend(self.blocks(children));
+                return;
+            }
+            Node::TaskList(list) => {
+                let items: Vec<String> = list
+                    .tasks()
+                    .map(|task| {
+                        let done = task.status() == storage::TaskStatus::Complete;
+                        let body = task
+                            .body()
+                            .map(|b| join_blocks(&self.blocks(&b.children), true))
+                            .unwrap_or_default();
+                        list_item(if done { "- [x] " } else { "- [ ] " }, &body)
+                    })
+                    .collect();
+                blocks.push(Block {
+                    text: items.join("\n"),
+                    list: true,
+                });
+                return;
+            }
+            _ => {
+                let e = node.as_element().unwrap();
+                match e.name.as_str() {
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        let level = e.name[1..].parse().unwrap_or(1);
+                        format!("{} {}", "#".repeat(level), self.inline_children(e).trim())
+                    }
+                    "p" => {
+                        let text = self.inline_children(e).trim().to_string();
+                        if text.is_empty() {
+                            return;
+                        }
+                        text
+                    }
+                    "ul" | "ol" => {
+                        blocks.push(Block {
+                            text: self.list(e),
+                            list: true,
+                        });
+                        return;
+                    }
+                    "blockquote" => quote(&join_blocks(&self.blocks(&e.children), false)),
+                    "pre" => fence("", &e.text()),
+                    "hr" => "---".to_string(),
+                    "table" => self.table(e),
+                    _ => {
+                        blocks.extend(self.blocks(&e.children));
+                        return;
+                    }
+                }
+            }
+        };
+        blocks.push(Block { text, list: false });
+    }
+
+    fn macro_block(&mut self, m: &Macro) -> String {
+        let name = m.name().unwrap_or_default();
+        let rich = |r: &mut Self| match m.body() {
+            Some(MacroBody::Rich(nodes)) => join_blocks(&r.blocks(nodes), false),
+            Some(MacroBody::Plain(text)) => text,
+            None => String::new(),
+        };
+        let titled = |body: String| match m.parameter("title") {
+            Some(title) if !title.is_empty() => format!("**{}**\n\n{}", title, body),
+            _ => body,
+        };
+        match name.as_str() {
+            "code" | "noformat" => {
+                let code = match m.body() {
+                    Some(MacroBody::Plain(text)) => text,
+                    _ => String::new(),
+                };
+                fence(&m.parameter("language").unwrap_or_default(), &code)
+            }
+            "info" | "tip" | "note" | "warning" => {
+                let kind = match name.as_str() {
+                    "info" => "NOTE",
+                    "tip" => "TIP",
+                    "note" => "IMPORTANT",
+                    _ => "WARNING",
+                };
+                let body = titled(rich(self));
+                quote(&format!("[!{}]\n{}", kind, body))
+            }
+            "panel" => {
+                let body = titled(rich(self));
+                quote(&body)
+            }
+            _ => fence("confluence", &m.to_string()),
+        }
+    }
+
+    fn list(&mut self, e: &Element) -> String {
+        let ordered = e.name == "ol";
+        let mut number: u64 = e
+            .attribute("start")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+        let mut items = Vec::new();
+        for li in e.child_elements().filter(|c| c.name == "li") {
+            let marker = if ordered {
+                let marker = format!("{}. ", number);
+                number += 1;
+                marker
+            } else {
+                "- ".to_string()
+            };
+            let body = join_blocks(&self.blocks(&li.children), true);
+            items.push(list_item(&marker, &body));
+        }
+        items.join("\n")
+    }
+
+    fn table(&mut self, e: &Element) -> String {
+        let rows: Vec<&Element> = e
+            .child_elements()
+            .flat_map(|c| match c.name.as_str() {
+                "tr" => vec![c],
+                "thead" | "tbody" | "tfoot" => {
+                    c.child_elements().filter(|r| r.name == "tr").collect()
+                }
+                _ => vec![],
+            })
+            .collect();
+        let was_in_table = self.in_table;
+        self.in_table = true;
+        let cells: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| {
+                row.child_elements()
+                    .filter(|c| c.name == "th" || c.name == "td")
+                    .map(|c| {
+                        let blocks = self.blocks(&c.children);
+                        blocks
+                            .iter()
+                            .map(|b| b.text.replace('\n', "<br>"))
+                            .collect::<Vec<_>>()
+                            .join("<br>")
+                            .replace('|', "\\|")
+                    })
+                    .collect()
+            })
+            .collect();
+        self.in_table = was_in_table;
+
+        let columns = cells.iter().map(|r| r.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return String::new();
+        }
+        let line = |row: &[String]| {
+            let mut cols: Vec<&str> = row.iter().map(|s| s.as_str()).collect();
+            cols.resize(columns, "");
+            format!("| {} |", cols.join(" | "))
+        };
+        let mut out = vec![line(&cells[0])];
+        out.push(format!("|{}", " --- |".repeat(columns)));
+        out.extend(cells[1..].iter().map(|r| line(r)));
+        out.join("\n")
+    }
+
+    fn inline_children(&mut self, e: &Element) -> String {
+        e.children.iter().map(|c| self.inline(c)).collect()
+    }
+
+    fn inline(&mut self, node: &Node) -> String {
+        match node {
+            Node::Text(raw) => escape_markdown(&collapse_whitespace(&storage::unescape(raw))),
+            Node::CData(value) => escape_markdown(value),
+            Node::Comment(_) | Node::Declaration(_) => String::new(),
+            Node::Link(link) => {
+                let e = link.as_element();
+                let text = match e.child("ac:link-body") {
+                    Some(body) => self.inline_children(body),
+                    None => escape_markdown(&link.text().unwrap_or_default()),
+                };
+                let anchor = link.anchor().map(|a| format!("#{}", a)).unwrap_or_default();
+                let (target, fallback) = match link.resource().map(|r| r.identifier()) {
+                    Some(ResourceIdentifier::Page { content_title, .. }) => (
+                        self.exporter.page_link(&content_title),
+                        content_title.clone(),
+                    ),
+                    Some(ResourceIdentifier::Attachment { filename, .. }) => {
+                        self.attachment(&filename);
+                        (self.exporter.attachment_link(&filename), filename.clone())
+                    }
+                    Some(ResourceIdentifier::Url { value }) => (value.clone(), value.clone()),
+                    Some(ResourceIdentifier::User {
+                        username,
+                        user_key,
+                        account_id,
+                    }) => {
+                        let name = username.or(account_id).or(user_key).unwrap_or_default();
+                        return format!("@{}", name);
+                    }
+                    Some(ResourceIdentifier::Space { space_key }) => {
+                        return escape_markdown(&space_key)
+                    }
+                    Some(_) => return text,
+                    None => (String::new(), anchor.clone()),
+                };
+                let text = if text.trim().is_empty() {
+                    escape_markdown(&fallback)
+                } else {
+                    text
+                };
+                format!("[{}]({}{})", text, encode_link(&target), anchor)
+            }
+            Node::Image(image) => {
+                let alt = image.alt().unwrap_or_default();
+                let target = match image.resource().map(|r| r.identifier()) {
+                    Some(ResourceIdentifier::Attachment { filename, .. }) => {
+                        self.attachment(&filename);
+                        encode_link(&self.exporter.attachment_link(&filename))
+                    }
+                    Some(ResourceIdentifier::Url { value }) => value,
+                    _ => return String::new(),
+                };
+                format!("![{}]({})", escape_markdown(&alt), target)
+            }
+            Node::Resource(_) => String::new(),
+            _ => {
+                let e = node.as_element().unwrap();
+                let inner = |r: &mut Self| r.inline_children(e);
+                match e.name.as_str() {
+                    "strong" | "b" => wrap("**", &inner(self)),
+                    "em" | "i" => wrap("*", &inner(self)),
+                    "s" | "del" => wrap("~~", &inner(self)),
+                    "span"
+                        if e.attribute("style")
+                            .map(|s| s.contains("line-through"))
+                            .unwrap_or(false) =>
+                    {
+                        wrap("~~", &inner(self))
+                    }
+                    "code" => code_span(&e.text()),
+                    "br" => {
+                        if self.in_table {
+                            "<br>".to_string()
+                        } else {
+                            "\\\n".to_string()
+                        }
+                    }
+                    "a" => {
+                        let href = e.attribute("href").unwrap_or_default();
+                        format!("[{}]({})", inner(self), encode_link(&href))
+                    }
+                    "sub" | "sup" | "u" => {
+                        format!("<{0}>{1}</{0}>", e.name, inner(self))
+                    }
+                    "ac:emoticon" => format!(":{}:", e.attribute("ac:name").unwrap_or_default()),
+                    "time" => e.attribute("datetime").unwrap_or_else(|| e.text()),
+                    "ac:placeholder" => String::new(),
+                    _ => inner(self),
+                }
+            }
+        }
+    }
+
+    fn attachment(&mut self, filename: &str) {
+        if !self.attachments.iter().any(|a| a == filename) {
+            self.attachments.push(filename.to_string());
+        }
+    }
+}
+
+fn is_block(node: &Node) -> bool {
+    match node {
+        Node::Macro(m) => !matches!(m.name().as_deref(), Some("status") | Some("anchor")),
+        Node::Layout(_) | Node::LayoutSection(_) | Node::LayoutCell(_) | Node::TaskList(_) => true,
+        Node::Element(e) => matches!(
+            e.name.as_str(),
+            "h1" | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "p"
+                | "ul"
+                | "ol"
+                | "blockquote"
+                | "pre"
+                | "hr"
+                | "table"
+                | "div"
+                | "ac:rich-text-body"
+        ),
+        _ => false,
+    }
+}
+
+/// Whether `node` holds a macro rendered inline, or a link, that Markdown
+/// has no syntax for. Such nodes are kept whole as storage format, as a
+/// macro on its own in a fenced block would lose its place in the text.
+fn holds_inline_macro(node: &Node) -> bool {
+    match node {
+        Node::Macro(_) if is_block(node) => false,
+        Node::Layout(_) | Node::LayoutSection(_) | Node::LayoutCell(_) => false,
+        _ => has_macro(node),
+    }
+}
+
+fn has_macro(node: &Node) -> bool {
+    match node {
+        Node::Macro(_) => true,
+        Node::Link(link) => matches!(
+            link.resource().map(|r| r.identifier()),
+            Some(
+                ResourceIdentifier::BlogPost { .. }
+                    | ResourceIdentifier::Content { .. }
+                    | ResourceIdentifier::Shortcut { .. }
+                    | ResourceIdentifier::Other { .. }
+            )
+        ),
+        _ => node
+            .as_element()
+            .is_some_and(|e| e.children.iter().any(has_macro)),
+    }
+}
+
+fn flush(inline: &mut String, blocks: &mut Vec<Block>) {
+    let text = inline.trim();
+    if !text.is_empty() {
+        blocks.push(Block {
+            text: text.to_string(),
+            list: false,
+        });
+    }
+    inline.clear();
+}
+
+fn list_item(marker: &str, body: &str) -> String {
+    let indent = " ".repeat(marker.len());
+    let mut out = String::new();
+    for (i, line) in body.lines().enumerate() {
+        if i == 0 {
+            out.push_str(marker);
+        } else {
+            out.push('\n');
+            if !line.is_empty() {
+                out.push_str(&indent);
+            }
+        }
+        out.push_str(line);
+    }
+    if out.is_empty() {
+        out.push_str(marker.trim_end());
+    }
+    out
+}
+
+fn quote(body: &str) -> String {
+    body.lines()
+        .map(|l| {
+            if l.is_empty() {
+                ">".to_string()
+            } else {
+                format!("> {}", l)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A fenced code block, using a fence longer than any backtick run in `code`.
+fn fence(info: &str, code: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        if c == '`' {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!(
+        "{}{}\n{}\n{}",
+        fence,
+        info,
+        code.trim_end_matches('\n'),
+        fence
+    )
+}
+
+fn code_span(code: &str) -> String {
+    if code.contains('`') {
+        format!("`` {} ``", code)
+    } else {
+        format!("`{}`", code)
+    }
+}
+
+/// Wraps inline content in emphasis markers, moving surrounding whitespace
+/// outside the markers where Markdown requires it.
+fn wrap(marker: &str, inner: &str) -> String {
+    let trimmed = inner.trim();
+    if trimmed.is_empty() {
+        return inner.to_string();
+    }
+    let lead = &inner[..inner.len() - inner.trim_start().len()];
+    let trail = &inner[inner.trim_end().len()..];
+    format!("{}{}{}{}{}", lead, marker, trimmed, marker, trail)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+        } else {
+            out.push(c);
+            space = false;
+        }
+    }
+    out
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_and_inlines() {
+        let storage = "<h2>Intro</h2>\n<p>Some <strong>bold</strong> and <em>it_alic</em> \
+                       <code>x</code> <a href=\"https://example.com\">web</a>.</p>\
+                       <ul><li>one<ul><li>nested</li></ul></li><li>two</li></ul>\
+                       <ol start=\"3\"><li><p>three</p></li></ol><hr />";
+        assert_eq!(
+            from_storage(storage).unwrap().markdown,
+            "## Intro\n\nSome **bold** and *it\\_alic* `x` [web](https://example.com).\n\n\
+             - one\n  - nested\n- two\n\n3. three\n\n---\n"
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let storage = "<ac:structured-macro ac:name=\"code\"><ac:parameter ac:name=\"language\">rust\
+                       </ac:parameter><ac:plain-text-body><![CDATA[let a = \"```\";]]></ac:plain-text-body>\
+                       </ac:structured-macro><ac:structured-macro ac:name=\"warning\"><ac:rich-text-body>\
+                       <p>Careful</p></ac:rich-text-body></ac:structured-macro>\
+                       <ac:structured-macro ac:name=\"toc\" />";
+        assert_eq!(
+            from_storage(storage).unwrap().markdown,
+            "````rust\nlet a = \"```\";\n````\n\n> [!WARNING]\n> Careful\n\n\
+             ```confluence\n<ac:structured-macro ac:name=\"toc\" />\n```\n"
+        );
+    }
+
+    #[test]
+    fn inline_macros_keep_their_paragraph() {
+        let heading = "<h2><ac:structured-macro ac:name=\"anchor\"><ac:parameter ac:name=\"\">\
+                       setup</ac:parameter></ac:structured-macro>Setup</h2>";
+        let paragraph =
+            "<p>See <ac:structured-macro ac:name=\"jira\"><ac:parameter ac:name=\"key\">\
+                         DOC-1</ac:parameter></ac:structured-macro> and \
+                         <ac:structured-macro ac:name=\"status\"><ac:parameter ac:name=\"title\">\
+                         DONE</ac:parameter></ac:structured-macro></p>";
+        let storage = format!("<p>Intro</p>{}{}", heading, paragraph);
+        let markdown = from_storage(&storage).unwrap().markdown;
+        assert_eq!(
+            markdown,
+            format!(
+                "Intro\n\n```confluence\n{}\n```\n\n```confluence\n{}\n```\n",
+                heading, paragraph
+            )
+        );
+        assert_eq!(crate::markdown::to_storage(&markdown).storage, storage);
+    }
+
+    #[test]
+    fn tables_and_tasks() {
+        let storage = "<table><tbody><tr><th>a</th><th>b|c</th></tr><tr><td><p>1</p><p>2</p></td>\
+                       <td>x</td></tr></tbody></table><ac:task-list><ac:task><ac:task-id>1</ac:task-id>\
+                       <ac:task-status>complete</ac:task-status><ac:task-body>done</ac:task-body>\
+                       </ac:task></ac:task-list>";
+        assert_eq!(
+            from_storage(storage).unwrap().markdown,
+            "| a | b\\|c |\n| --- | --- |\n| 1<br>2 | x |\n\n- [x] done\n"
+        );
+    }
+
+    #[test]
+    fn links_and_attachments() {
+        let mut paths = HashMap::new();
+        paths.insert("Setting Up".to_string(), "guide/setup.md".to_string());
+        let export = MarkdownExporter::new()
+            .with_document_dir("guide/advanced")
+            .with_page_paths(paths)
+            .with_attachment_dir("attachments")
+            .export(
+                "<p><ac:link ac:anchor=\"install\"><ri:page ri:content-title=\"Setting Up\" />\
+                 <ac:plain-text-link-body><![CDATA[setup]]></ac:plain-text-link-body></ac:link> \
+                 <ac:link><ri:page ri:content-title=\"Other Page\" /></ac:link> \
+                 <ac:image ac:alt=\"Seq\"><ri:attachment ri:filename=\"seq diagram.png\" /></ac:image></p>",
+            )
+            .unwrap();
+        assert_eq!(
+            export.markdown,
+            "[setup](../setup.md#install) [Other Page](Other%20Page.md) \
+             ![Seq](attachments/seq%20diagram.png)\n"
+        );
+        assert_eq!(export.attachments, vec!["seq diagram.png".to_string()]);
+    }
+
+    #[test]
+    fn statuses_and_other_content_links_stay_storage() {
+        let status = "<p>State: <ac:structured-macro ac:name=\"status\">\
+                      <ac:parameter ac:name=\"colour\">Green</ac:parameter>\
+                      <ac:parameter ac:name=\"title\">DONE</ac:parameter></ac:structured-macro></p>";
+        let blog_post = "<p>Read <ac:link><ri:blog-post ri:content-title=\"Release\" \
+                         ri:posting-day=\"2024/05/01\" /></ac:link></p>";
+        let entity = "<p>See <ac:link><ri:content-entity ri:content-id=\"42\" />\
+                      <ac:plain-text-link-body><![CDATA[this]]></ac:plain-text-link-body></ac:link></p>";
+        for storage in [status, blog_post, entity] {
+            let markdown = from_storage(storage).unwrap().markdown;
+            assert_eq!(markdown, format!("```confluence\n{}\n```\n", storage));
+            assert_eq!(crate::markdown::to_storage(&markdown).storage, storage);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Conversion between Markdown and the Confluence storage format, in both
+//! directions.
+//!
+//! ```
+//! use confluence::markdown::MarkdownConverter;
+//!
+//! let conversion = MarkdownConverter::new().convert("# Hello\n\n![diagram](img/seq.png)");
+//! assert_eq!(conversion.title.as_deref(), Some("Hello"));
+//! assert_eq!(conversion.assets[0].filename, "seq.png");
+//! ```
+
+mod from_storage;
+mod to_storage;
+
+pub use from_storage::*;
+pub use to_storage::*;
+
+/// Joins a relative link target onto the directory of the document it
+/// appears in and resolves `.` and `..` segments, giving a path relative to
+/// the documentation root.
+pub(crate) fn resolve_path(document_dir: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let joined = if target.starts_with('/') {
+        target.to_string()
+    } else {
+        format!("{}/{}", document_dir, target)
+    };
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    segments.join("/")
+}
+
+/// The path of `target` relative to the directory `from_dir`, both relative
+/// to the same root.
+pub(crate) fn relative_path(from_dir: &str, target: &str) -> String {
+    let from: Vec<&str> = from_dir.split('/').filter(|s| !s.is_empty()).collect();
+    let to: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut segments = vec![".."; from.len() - common];
+    segments.extend(&to[common..]);
+    segments.join("/")
+}
+
+/// Escapes the characters that can't appear in a Markdown link destination.
+pub(crate) fn encode_link(target: &str) -> String {
+    let mut out = String::with_capacity(target.len());
+    for c in target.chars() {
+        match c {
+            ' ' => out.push_str("%20"),
+            '(' => out.push_str("%28"),
+            ')' => out.push_str("%29"),
+            '<' => out.push_str("%3C"),
+            '>' => out.push_str("%3E"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decodes `%XX` escapes in a link target.
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Whether a link target points to another site rather than a local file.
+pub(crate) fn is_external(target: &str) -> bool {
+    target.contains("://") || target.starts_with("mailto:") || target.starts_with("tel:")
+}
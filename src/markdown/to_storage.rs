@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{
+    BlockQuoteKind, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd,
+};
+
+use super::{is_external, percent_decode, resolve_path};
+use crate::storage::{cdata, Element, Node};
+
+/// A local file referenced by a converted document that has to be uploaded
+/// as an attachment of the page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Asset {
+    /// Path of the file relative to the documentation root.
+    pub path: String,
+    /// Attachment name the storage format refers to.
+    pub filename: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversion {
+    /// The storage format body, ready for `model::Storage::new(&storage, Representation::Storage)`.
+    pub storage: String,
+    /// Text of the first level one heading, if any.
+    pub title: Option<String>,
+    pub assets: Vec<Asset>,
+}
+
+/// Converts CommonMark with the GitHub extensions into storage format.
+///
+/// Fenced code becomes the `code` macro, except `confluence` blocks whose
+/// storage format is inserted as is, GitHub alerts (`> [!NOTE]`) become
+/// the info, tip, note and warning macros, task lists become Confluence
+/// tasks, links to other Markdown files become page links and local images
+/// and files become attachments.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownConverter {
+    /// Directory of the converted document relative to the documentation
+    /// root. Relative links and images are resolved against it.
+    pub document_dir: String,
+    /// Page titles of linked documents by their path relative to the
+    /// documentation root. Documents not found here are linked by their file
+    /// name without extension.
+    pub page_titles: HashMap<String, String>,
+    /// Space of the linked pages, if not the space of the converted page.
+    pub space_key: Option<String>,
+}
+
+impl MarkdownConverter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_document_dir(mut self, document_dir: &str) -> Self {
+        self.document_dir = document_dir.to_string();
+        self
+    }
+
+    pub fn with_page_titles(mut self, page_titles: HashMap<String, String>) -> Self {
+        self.page_titles = page_titles;
+        self
+    }
+
+    pub fn with_space_key(mut self, space_key: &str) -> Self {
+        self.space_key = Some(space_key.to_string());
+        self
+    }
+
+    pub fn convert(&self, markdown: &str) -> Conversion {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_GFM
+            | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+        let mut writer = Writer {
+            converter: self,
+            nodes: Vec::new(),
+            stack: Vec::new(),
+            assets: Vec::new(),
+            title: None,
+            heading: None,
+            code: None,
+            in_table_head: false,
+            in_metadata: false,
+            task_id: 0,
+        };
+        for event in Parser::new_ext(markdown, options) {
+            writer.event(event);
+        }
+        Conversion {
+            storage: writer.nodes.iter().map(|n| n.to_string()).collect(),
+            title: writer.title,
+            assets: writer.assets,
+        }
+    }
+
+    fn page_title(&self, path: &str) -> String {
+        match self.page_titles.get(path) {
+            Some(title) => title.clone(),
+            None => {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                name.rsplit_once('.')
+                    .map(|(stem, _)| stem)
+                    .unwrap_or(name)
+                    .to_string()
+            }
+        }
+    }
+}
+
+/// Shorthand for `MarkdownConverter::new().convert(markdown)`.
+pub fn to_storage(markdown: &str) -> Conversion {
+    MarkdownConverter::new().convert(markdown)
+}
+
+/// A block or inline element whose end event hasn't been seen yet.
+enum Open {
+    Element(Element),
+    /// Images collect their alt text instead of children.
+    Image(Element, String),
+    /// Nothing to write, e.g. the header of a table.
+    Transparent,
+}
+
+struct Writer<'c> {
+    converter: &'c MarkdownConverter,
+    nodes: Vec<Node>,
+    stack: Vec<Open>,
+    assets: Vec<Asset>,
+    title: Option<String>,
+    /// Text of the level one heading being written.
+    heading: Option<String>,
+    /// Language and text of the fenced code block being written.
+    code: Option<(String, String)>,
+    in_table_head: bool,
+    in_metadata: bool,
+    task_id: u64,
+}
+
+impl<'c> Writer<'c> {
+    fn event(&mut self, event: Event<'_>) {
+        if self.in_metadata {
+            if let Event::End(TagEnd::MetadataBlock(_)) = event {
+                self.in_metadata = false;
+            }
+            return;
+        }
+        if let Some((_, code)) = &mut self.code {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => self.end_code_block(),
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => self.append(Element::new("code").with_text(&code)),
+            Event::Html(html) | Event::InlineHtml(html) => {
+                self.append(Node::Text(html.to_string()))
+            }
+            Event::SoftBreak => self.text("\n"),
+            Event::HardBreak => self.append(Element::new("br")),
+            Event::Rule => self.append(Element::new("hr")),
+            Event::TaskListMarker(checked) => {
+                let status = if checked { "complete" } else { "incomplete" };
+                self.append(Element::new("ac:task-status").with_text(status))
+            }
+            Event::FootnoteReference(_) | Event::InlineMath(_) | Event::DisplayMath(_) => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        let open = match tag {
+            Tag::Paragraph => Open::Element(Element::new("p")),
+            Tag::Heading { level, .. } => {
+                if level == HeadingLevel::H1 && self.title.is_none() {
+                    self.heading = Some(String::new());
+                }
+                Open::Element(Element::new(&format!("h{}", level as usize)))
+            }
+            Tag::BlockQuote(None) => Open::Element(Element::new("blockquote")),
+            Tag::BlockQuote(Some(kind)) => {
+                let name = match kind {
+                    BlockQuoteKind::Note => "info",
+                    BlockQuoteKind::Tip => "tip",
+                    BlockQuoteKind::Important => "note",
+                    BlockQuoteKind::Warning | BlockQuoteKind::Caution => "warning",
+                };
+                Open::Element(
+                    Element::new("ac:structured-macro")
+                        .with_attribute("ac:name", name)
+                        .with_attribute("ac:schema-version", "1")
+                        .with_child(Element::new("ac:rich-text-body")),
+                )
+            }
+            Tag::CodeBlock(kind) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((language, String::new()));
+                return;
+            }
+            Tag::HtmlBlock => Open::Transparent,
+            Tag::List(Some(1)) => Open::Element(Element::new("ol")),
+            Tag::List(Some(start)) => {
+                Open::Element(Element::new("ol").with_attribute("start", &start.to_string()))
+            }
+            Tag::List(None) => Open::Element(Element::new("ul")),
+            Tag::Item => Open::Element(Element::new("li")),
+            Tag::Table(_) => Open::Element(Element::new("table")),
+            Tag::TableHead => {
+                self.in_table_head = true;
+                Open::Element(Element::new("tr"))
+            }
+            Tag::TableRow => Open::Element(Element::new("tr")),
+            Tag::TableCell => {
+                Open::Element(Element::new(if self.in_table_head { "th" } else { "td" }))
+            }
+            Tag::Emphasis => Open::Element(Element::new("em")),
+            Tag::Strong => Open::Element(Element::new("strong")),
+            Tag::Strikethrough => Open::Element(
+                Element::new("span").with_attribute("style", "text-decoration: line-through;"),
+            ),
+            Tag::Superscript => Open::Element(Element::new("sup")),
+            Tag::Subscript => Open::Element(Element::new("sub")),
+            Tag::Link {
+                dest_url, title, ..
+            } => Open::Element(self.link(&dest_url, &title)),
+            Tag::Image { dest_url, .. } => Open::Image(self.image(&dest_url), String::new()),
+            Tag::MetadataBlock(_) => {
+                self.in_metadata = true;
+                return;
+            }
+            Tag::FootnoteDefinition(_)
+            | Tag::DefinitionList
+            | Tag::DefinitionListTitle
+            | Tag::DefinitionListDefinition => Open::Transparent,
+        };
+        self.stack.push(open);
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        let open = match self.stack.pop() {
+            Some(open) => open,
+            None => return,
+        };
+        match tag {
+            TagEnd::Heading(_) => {
+                if let Some(heading) = self.heading.take() {
+                    self.title = Some(heading.trim().to_string());
+                }
+            }
+            TagEnd::TableHead => self.in_table_head = false,
+            _ => {}
+        }
+        match open {
+            Open::Element(mut element) => {
+                match tag {
+                    TagEnd::Table => {
+                        // Confluence expects rows inside a tbody.
+                        let rows = std::mem::take(&mut element.children);
+                        let mut tbody = Element::new("tbody");
+                        tbody.children = rows;
+                        element.push(tbody);
+                    }
+                    TagEnd::List(_) => element = self.task_list(element),
+                    _ => {}
+                }
+                self.append(element);
+            }
+            Open::Image(mut element, alt) => {
+                if !alt.is_empty() {
+                    element.set_attribute("ac:alt", &alt);
+                }
+                self.append(element);
+            }
+            Open::Transparent => {}
+        }
+    }
+
+    fn end_code_block(&mut self) {
+        let (language, code) = self.code.take().unwrap_or_default();
+        if language == "confluence" {
+            // Storage format kept by the exporter for what Markdown cannot
+            // express.
+            let code = code.strip_suffix('\n').unwrap_or(&code);
+            self.append(Node::Text(code.to_string()));
+            return;
+        }
+        let mut element = Element::new("ac:structured-macro")
+            .with_attribute("ac:name", "code")
+            .with_attribute("ac:schema-version", "1");
+        if !language.is_empty() {
+            element.push(
+                Element::new("ac:parameter")
+                    .with_attribute("ac:name", "language")
+                    .with_text(code_language(&language)),
+            );
+        }
+        let code = code.strip_suffix('\n').unwrap_or(&code);
+        let mut body = Element::new("ac:plain-text-body");
+        body.children = cdata(code);
+        element.push(body);
+        self.append(element);
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(heading) = &mut self.heading {
+            heading.push_str(text);
+        }
+        if let Some(Open::Image(_, alt)) = self.stack.last_mut() {
+            alt.push_str(text);
+            return;
+        }
+        self.append(Node::text(text));
+    }
+
+    /// Adds a node to the innermost open element, or to the document.
+    fn append(&mut self, node: impl Into<Node>) {
+        let node = node.into();
+        for open in self.stack.iter_mut().rev() {
+            match open {
+                Open::Element(element) => {
+                    let parent = match element.name.as_str() {
+                        "ac:structured-macro" => element.child_mut("ac:rich-text-body"),
+                        "ac:link" => element.child_mut("ac:link-body"),
+                        _ => None,
+                    };
+                    match parent {
+                        Some(p) => p.push(node),
+                        None => element.push(node),
+                    }
+                    return;
+                }
+                Open::Image(..) => return,
+                Open::Transparent => continue,
+            }
+        }
+        self.nodes.push(node);
+    }
+
+    fn link(&mut self, dest: &str, title: &str) -> Element {
+        if is_external(dest) {
+            let mut a = Element::new("a").with_attribute("href", dest);
+            if !title.is_empty() {
+                a.set_attribute("title", title);
+            }
+            return a;
+        }
+        let (path, anchor) = match dest.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (dest, None),
+        };
+        let mut link = Element::new("ac:link");
+        if let Some(anchor) = anchor {
+            link.set_attribute("ac:anchor", &percent_decode(anchor));
+        }
+        if !path.is_empty() {
+            let path = resolve_path(&self.converter.document_dir, &percent_decode(path));
+            let lower = path.to_lowercase();
+            if lower.ends_with(".md") || lower.ends_with(".markdown") {
+                let mut page = Element::empty("ri:page");
+                if let Some(space_key) = &self.converter.space_key {
+                    page.set_attribute("ri:space-key", space_key);
+                }
+                page.set_attribute("ri:content-title", &self.converter.page_title(&path));
+                link.push(page);
+            } else {
+                let filename = self.asset(path);
+                link.push(Element::empty("ri:attachment").with_attribute("ri:filename", &filename));
+            }
+        }
+        link.with_child(Element::new("ac:link-body"))
+    }
+
+    fn image(&mut self, dest: &str) -> Element {
+        let image = Element::new("ac:image");
+        if is_external(dest) {
+            return image.with_child(Element::empty("ri:url").with_attribute("ri:value", dest));
+        }
+        let path = resolve_path(&self.converter.document_dir, &percent_decode(dest));
+        let filename = self.asset(path);
+        image.with_child(Element::empty("ri:attachment").with_attribute("ri:filename", &filename))
+    }
+
+    /// Records a local file as asset and returns its attachment name: its
+    /// file name, or its path with `-` for `/` when another file of the
+    /// document already has that name.
+    fn asset(&mut self, path: String) -> String {
+        if let Some(asset) = self.assets.iter().find(|a| a.path == path) {
+            return asset.filename.clone();
+        }
+        let taken = |name: &str| self.assets.iter().any(|a| a.filename == name);
+        let basename = path.rsplit('/').next().unwrap_or(&path);
+        let mut filename = basename.to_string();
+        let flattened = path.replace('/', "-");
+        let mut n = 1;
+        while taken(&filename) {
+            filename = match n {
+                1 => flattened.clone(),
+                _ => format!("{}-{}", n, flattened),
+            };
+            n += 1;
+        }
+        self.assets.push(Asset {
+            path,
+            filename: filename.clone(),
+        });
+        filename
+    }
+
+    /// Turns a list whose items all start with a checkbox into a task list.
+    fn task_list(&mut self, list: Element) -> Element {
+        let is_task = |c: &Node| {
+            c.as_element()
+                .and_then(|li| li.child_elements().next())
+                .map(|e| e.name == "ac:task-status")
+                .unwrap_or(false)
+        };
+        let items: Vec<&Node> = list
+            .children
+            .iter()
+            .filter(|c| c.as_element().is_some())
+            .collect();
+        if items.is_empty() || !items.iter().all(|c| is_task(c)) {
+            return list;
+        }
+        let mut task_list = Element::new("ac:task-list");
+        for item in list.children {
+            let mut li = match item {
+                Node::Element(li) => li,
+                _ => continue,
+            };
+            let position = li
+                .children
+                .iter()
+                .position(|c| c.as_element().is_some())
+                .unwrap_or(0);
+            let status = li.children.remove(position);
+            self.task_id += 1;
+            let mut body = Element::new("ac:task-body");
+            body.children = trim_start(li.children);
+            task_list.push(
+                Element::new("ac:task")
+                    .with_child(Element::new("ac:task-id").with_text(&self.task_id.to_string()))
+                    .with_child(status)
+                    .with_child(body),
+            );
+        }
+        task_list
+    }
+}
+
+/// Drops the space pulldown-cmark leaves between a checkbox and the text.
+fn trim_start(mut nodes: Vec<Node>) -> Vec<Node> {
+    if let Some(Node::Text(text)) = nodes.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    nodes
+}
+
+/// Maps common fence info strings to the names the code macro knows.
+fn code_language(language: &str) -> &str {
+    match language {
+        "sh" | "shell" | "zsh" => "bash",
+        "js" => "javascript",
+        "ts" => "typescript",
+        "py" => "python",
+        "yml" => "yaml",
+        "rs" => "rust",
+        "c++" => "cpp",
+        "cs" => "csharp",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_and_inlines() {
+        let md = "# Guide\n\nSome *em* and **strong** `code` ~~gone~~.\n\n---\n\n1. one\n2. two\n";
+        let c = to_storage(md);
+        assert_eq!(c.title.as_deref(), Some("Guide"));
+        assert_eq!(
+            c.storage,
+            "<h1>Guide</h1><p>Some <em>em</em> and <strong>strong</strong> <code>code</code> \
+             <span style=\"text-decoration: line-through;\">gone</span>.</p><hr />\
+             <ol><li>one</li><li>two</li></ol>"
+        );
+    }
+
+    #[test]
+    fn code_blocks_become_code_macros() {
+        let c = to_storage("```sh\necho \"]]>\" && ls\n```\n");
+        assert_eq!(
+            c.storage,
+            "<ac:structured-macro ac:name=\"code\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"language\">bash</ac:parameter>\
+             <ac:plain-text-body><![CDATA[echo \"]]]]><![CDATA[>\" && ls]]></ac:plain-text-body>\
+             </ac:structured-macro>"
+        );
+    }
+
+    #[test]
+    fn confluence_blocks_are_inserted_as_is() {
+        let c = to_storage(
+            "```confluence\n<p><ac:structured-macro ac:name=\"anchor\" /> Top</p>\n```\n",
+        );
+        assert_eq!(
+            c.storage,
+            "<p><ac:structured-macro ac:name=\"anchor\" /> Top</p>"
+        );
+    }
+
+    #[test]
+    fn alerts_become_admonition_macros() {
+        let c = to_storage("> [!WARNING]\n> Mind the gap\n");
+        assert_eq!(
+            c.storage,
+            "<ac:structured-macro ac:name=\"warning\" ac:schema-version=\"1\">\
+             <ac:rich-text-body><p>Mind the gap</p></ac:rich-text-body></ac:structured-macro>"
+        );
+    }
+
+    #[test]
+    fn tables_and_tasks() {
+        let c = to_storage("| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo\n");
+        assert_eq!(
+            c.storage,
+            "<table><tbody><tr><th>a</th><th>b</th></tr><tr><td>1</td><td>2</td></tr></tbody></table>\
+             <ac:task-list><ac:task><ac:task-id>1</ac:task-id><ac:task-status>complete</ac:task-status>\
+             <ac:task-body>done</ac:task-body></ac:task><ac:task><ac:task-id>2</ac:task-id>\
+             <ac:task-status>incomplete</ac:task-status><ac:task-body>todo</ac:task-body></ac:task>\
+             </ac:task-list>"
+        );
+        let parsed = crate::storage::parse(&c.storage).unwrap();
+        assert_eq!(parsed.to_string(), c.storage);
+    }
+
+    #[test]
+    fn links_and_images() {
+        let mut titles = HashMap::new();
+        titles.insert("guide/setup.md".to_string(), "Setting Up".to_string());
+        let c = MarkdownConverter::new()
+            .with_document_dir("guide/advanced")
+            .with_page_titles(titles)
+            .convert(
+                "[setup](../setup.md#install) [other](faq.md) [web](https://example.com) \
+                 [spec](files/spec%20v1.pdf) ![Seq](../img/seq.png)",
+            );
+        assert_eq!(
+            c.storage,
+            "<p><ac:link ac:anchor=\"install\"><ri:page ri:content-title=\"Setting Up\" />\
+             <ac:link-body>setup</ac:link-body></ac:link> \
+             <ac:link><ri:page ri:content-title=\"faq\" /><ac:link-body>other</ac:link-body></ac:link> \
+             <a href=\"https://example.com\">web</a> \
+             <ac:link><ri:attachment ri:filename=\"spec v1.pdf\" /><ac:link-body>spec</ac:link-body></ac:link> \
+             <ac:image ac:alt=\"Seq\"><ri:attachment ri:filename=\"seq.png\" /></ac:image></p>"
+        );
+        assert_eq!(
+            c.assets,
+            vec![
+                Asset {
+                    path: "guide/advanced/files/spec v1.pdf".to_string(),
+                    filename: "spec v1.pdf".to_string(),
+                },
+                Asset {
+                    path: "guide/img/seq.png".to_string(),
+                    filename: "seq.png".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn files_of_the_same_name_get_distinct_attachments() {
+        let c = to_storage("![a](a/diagram.png) ![b](b/diagram.png) ![again](a/diagram.png)");
+        let filenames: Vec<&str> = c.assets.iter().map(|a| a.filename.as_str()).collect();
+        assert_eq!(filenames, vec!["diagram.png", "b-diagram.png"]);
+        assert!(c.storage.contains("ri:filename=\"b-diagram.png\""));
+        assert_eq!(c.storage.matches("ri:filename=\"diagram.png\"").count(), 2);
+    }
+}
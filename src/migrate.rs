@@ -0,0 +1,954 @@
+//! Migrating a space from one instance to another.
+//!
+//! [`SpaceMigration`] copies the pages and blog posts of a source space to
+//! a target space, one at a time, with their labels, properties,
+//! attachments and comments. Pages are created parent first, and the top of
+//! the tree below an optional parent page. Bodies are rewritten like an
+//! [`import`](crate::import): space keys and ids of copied content, and user
+//! mentions by username or user key become mentions by the account id a
+//! [`UserMap`] gives for the username. Versions, authors and dates aren't
+//! kept.
+//!
+//! With a checkpoint file the ids of everything copied are saved as the
+//! migration goes, so a migration that was interrupted picks up where it
+//! stopped when run again. Once done, the source and the target are
+//! compared and the differences reported.
+//!
+//! ```no_run
+//! use confluence::client::Confluence;
+//! use confluence::migrate::{SpaceMigration, UserMap};
+//!
+//! let source = Confluence::new("https://wiki.example.com/rest/api");
+//! let target = Confluence::new("https://example.atlassian.net/wiki/rest/api");
+//! let report = SpaceMigration::new(&source, "DOCS", &target, "DOCS")
+//!     .with_users(UserMap::load("users.csv").unwrap())
+//!     .with_checkpoint("docs-migration.json")
+//!     .migrate()
+//!     .unwrap();
+//! print!("{}", report);
+//! ```
+
+use crate::attachment::AttachmentUpload;
+use crate::client::{Confluence, Executor, Result};
+use crate::content::model::Ancestor;
+use crate::deployment::{ContentApi, DeploymentClient};
+use crate::import::{rewrite_references, IdMapping};
+use crate::model::{
+    Body, CreatePageRequest, Representation, Space, Storage, UpdatePageRequest, Version,
+};
+use crate::util::{encode_query, expect, get_all, get_json};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The account ids of users on the target, by their username on the
+/// source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserMap {
+    accounts: HashMap<String, String>,
+}
+
+impl UserMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_user(mut self, username: &str, account_id: &str) -> Self {
+        self.accounts
+            .insert(username.to_string(), account_id.to_string());
+        self
+    }
+
+    /// Reads `username,accountId` lines. Blank lines, lines starting with
+    /// `#` and a `username,accountId` header are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut users = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (username, account_id) = line
+                .split_once(',')
+                .map(|(u, a)| (u.trim(), a.trim()))
+                .filter(|(u, a)| !u.is_empty() && !a.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "Unable to parse the user map: line {}: expected username,accountId",
+                        number + 1
+                    )
+                })?;
+            if username.eq_ignore_ascii_case("username")
+                && account_id.eq_ignore_ascii_case("accountid")
+            {
+                continue;
+            }
+            users = users.with_user(username, account_id);
+        }
+        Ok(users)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+
+    pub fn account_id(&self, username: &str) -> Option<&str> {
+        self.accounts.get(username).map(String::as_str)
+    }
+}
+
+/// What was copied so far, saved after every copy.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// The ids of copied content on the target, by their id on the source.
+    pub ids: BTreeMap<String, String>,
+    /// Pages and blog posts copied along with everything they contain.
+    pub done: BTreeSet<String>,
+    /// Copied content whose body links to content not copied yet.
+    pub pending: BTreeSet<String>,
+}
+
+impl Checkpoint {
+    /// The checkpoint in `path`, or an empty one if there is no file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|e| format!("Unable to parse {}: {}", path.display(), e).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Unable to read {}: {}", path.display(), e).into()),
+        }
+    }
+
+    /// Writes the checkpoint to a temporary file first, so an interruption
+    /// leaves the previous one whole.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, json)
+            .and_then(|_| std::fs::rename(&partial, path))
+            .map_err(|e| format!("Unable to write {}: {}", path.display(), e).into())
+    }
+}
+
+/// A difference between source content and its copy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub source_id: String,
+    pub title: String,
+    pub message: String,
+}
+
+/// The comparison of the source and the target after a migration.
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// The number of pages and blog posts compared.
+    pub checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// The content copied by this run, in the order it was created.
+    pub copied: Vec<IdMapping>,
+    /// The pages and blog posts copied by a previous run.
+    pub resumed: usize,
+    /// Source usernames and user keys mentioned without an account id.
+    pub unmapped_users: BTreeSet<String>,
+    pub verification: Verification,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} copied, {} copied before",
+            self.copied.len(),
+            self.resumed
+        )?;
+        for user in &self.unmapped_users {
+            writeln!(f, "no account id for user {}", user)?;
+        }
+        let verification = &self.verification;
+        writeln!(
+            f,
+            "{} pages and blog posts verified, {} problems",
+            verification.checked,
+            verification.problems.len()
+        )?;
+        for p in &verification.problems {
+            writeln!(f, "  {} {}: {}", p.source_id, p.title, p.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// A page or blog post of the source space.
+struct Listed {
+    id: String,
+    content_type: String,
+    title: String,
+    parent: Option<String>,
+    depth: usize,
+}
+
+/// What is compared by [`SpaceMigration::verify`].
+#[derive(PartialEq)]
+struct Summary {
+    title: String,
+    labels: Vec<String>,
+    properties: Vec<String>,
+    attachments: Vec<(String, Option<u64>)>,
+    comments: usize,
+}
+
+pub struct SpaceMigration {
+    source: Confluence,
+    source_space: String,
+    target: Confluence,
+    target_space: String,
+    parent: Option<Ancestor>,
+    users: UserMap,
+    checkpoint: Option<PathBuf>,
+}
+
+/// The state of a run: what was copied and the user lookups done.
+struct Run {
+    checkpoint: Checkpoint,
+    report: MigrationReport,
+    /// Source usernames by user key.
+    usernames: HashMap<String, Option<String>>,
+}
+
+impl SpaceMigration {
+    /// Migrates `source_space` of `source` to `target_space` of `target`,
+    /// which has to exist.
+    pub fn new(
+        source: &Confluence,
+        source_space: &str,
+        target: &Confluence,
+        target_space: &str,
+    ) -> Self {
+        Self {
+            source: source.clone(),
+            source_space: source_space.to_string(),
+            target: target.clone(),
+            target_space: target_space.to_string(),
+            parent: None,
+            users: UserMap::new(),
+            checkpoint: None,
+        }
+    }
+
+    /// Creates the top pages below `parent` instead of at the root of the
+    /// target space.
+    pub fn with_parent(mut self, parent: Ancestor) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn with_users(mut self, users: UserMap) -> Self {
+        self.users = users;
+        self
+    }
+
+    /// Saves what was copied to `path`, and resumes from it.
+    pub fn with_checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    pub fn migrate(&self) -> Result<MigrationReport> {
+        get_json(
+            &self.target,
+            &format!("space/{}", encode_query(&self.target_space)),
+            "get the target space",
+        )?;
+        let api = DeploymentClient::detect(&self.target)?;
+        let checkpoint = match &self.checkpoint {
+            Some(path) => Checkpoint::load(path)?,
+            None => Checkpoint::default(),
+        };
+        let mut run = Run {
+            checkpoint,
+            report: MigrationReport::default(),
+            usernames: HashMap::new(),
+        };
+
+        let listed = self.list()?;
+        let known: HashSet<&str> = listed.iter().map(|l| l.id.as_str()).collect();
+        for content in &listed {
+            if run.checkpoint.done.contains(&content.id) {
+                run.report.resumed += 1;
+                continue;
+            }
+            self.copy(&api, content, &known, &mut run)?;
+        }
+
+        // Links to content copied later can only be rewritten now.
+        let pending: Vec<String> = run.checkpoint.pending.iter().cloned().collect();
+        for id in pending {
+            self.relink(&id, &known, &mut run)?;
+            run.checkpoint.pending.remove(&id);
+            self.save(&run.checkpoint)?;
+        }
+
+        run.report.verification = self.verify(&run.checkpoint)?;
+        Ok(run.report)
+    }
+
+    /// Compares every page and blog post of the source with its copy.
+    pub fn verify(&self, checkpoint: &Checkpoint) -> Result<Verification> {
+        let mut verification = Verification::default();
+        for content in self.list()? {
+            verification.checked += 1;
+            let mut problem = |message: String| {
+                verification.problems.push(Problem {
+                    source_id: content.id.clone(),
+                    title: content.title.clone(),
+                    message,
+                })
+            };
+            let copy = match checkpoint.ids.get(&content.id) {
+                Some(copy) => copy,
+                None => {
+                    problem("not copied".to_string());
+                    continue;
+                }
+            };
+            let source = summary(&self.source, &content.id)?;
+            let target = match summary(&self.target, copy) {
+                Ok(target) => target,
+                Err(_) => {
+                    problem(format!("copy {} not found", copy));
+                    continue;
+                }
+            };
+            if source.title != target.title {
+                problem(format!("the copy is titled {}", target.title));
+            }
+            if source.labels != target.labels {
+                problem(format!(
+                    "labels {:?} on the source, {:?} on the target",
+                    source.labels, target.labels
+                ));
+            }
+            if source.properties != target.properties {
+                problem(format!(
+                    "properties {:?} on the source, {:?} on the target",
+                    source.properties, target.properties
+                ));
+            }
+            if source.attachments != target.attachments {
+                let names = |s: &Summary| -> Vec<String> {
+                    s.attachments
+                        .iter()
+                        .map(|(title, size)| match size {
+                            Some(size) => format!("{} ({} bytes)", title, size),
+                            None => title.clone(),
+                        })
+                        .collect()
+                };
+                problem(format!(
+                    "attachments {:?} on the source, {:?} on the target",
+                    names(&source),
+                    names(&target)
+                ));
+            }
+            if source.comments != target.comments {
+                problem(format!(
+                    "{} comments on the source, {} on the target",
+                    source.comments, target.comments
+                ));
+            }
+        }
+        Ok(verification)
+    }
+
+    /// The pages of the source space, parents before their children, then
+    /// its blog posts.
+    fn list(&self) -> Result<Vec<Listed>> {
+        let mut listed = Vec::new();
+        for content_type in ["page", "blogpost"] {
+            let endpoint = format!(
+                "content?spaceKey={}&type={}&expand=ancestors",
+                encode_query(&self.source_space),
+                content_type
+            );
+            let mut contents: Vec<Listed> = get_all(&self.source, &endpoint, "list content")?
+                .iter()
+                .map(|c| {
+                    let ancestors = c["ancestors"].as_array();
+                    Listed {
+                        id: text(&c["id"]),
+                        content_type: text(&c["type"]),
+                        title: text(&c["title"]),
+                        parent: ancestors.and_then(|a| a.last()).map(|a| text(&a["id"])),
+                        depth: ancestors.map_or(0, Vec::len),
+                    }
+                })
+                .collect();
+            contents.sort_by_key(|c| c.depth);
+            listed.extend(contents);
+        }
+        Ok(listed)
+    }
+
+    /// Copies a page or blog post and what it contains, skipping what a
+    /// previous run copied.
+    fn copy(
+        &self,
+        api: &DeploymentClient,
+        content: &Listed,
+        known: &HashSet<&str>,
+        run: &mut Run,
+    ) -> Result<()> {
+        let source = get_json(
+            &self.source,
+            &format!("content/{}?expand=body.storage,metadata.labels", content.id),
+            &format!("get {}", content.title),
+        )?;
+        let new_id = match run.checkpoint.ids.get(&content.id) {
+            Some(new_id) => new_id.clone(),
+            None => {
+                let parent = match &content.parent {
+                    Some(parent) if known.contains(parent.as_str()) => {
+                        run.checkpoint.ids.get(parent).cloned()
+                    }
+                    _ => self.parent.as_ref().map(|p| p.id.clone()),
+                };
+                let (body, resolved) = self.rewrite(&source, known, run)?;
+                // Created by a previous run that was interrupted before its
+                // checkpoint was saved.
+                let new_id = match self.find_copy(content)? {
+                    Some(new_id) => new_id,
+                    None => {
+                        let request = CreatePageRequest::new(
+                            &content.content_type,
+                            &content.title,
+                            Space::new(&self.target_space),
+                            Body::new(Storage::new(&body, Representation::Storage)),
+                            parent.map(|p| vec![Ancestor::new(&p)]),
+                        );
+                        let (_, status, d) =
+                            self.target.post(request).content().execute::<Value>()?;
+                        let created = expect(status, d, &format!("create {}", content.title))?;
+                        text(&created["id"])
+                    }
+                };
+                if !resolved {
+                    run.checkpoint.pending.insert(content.id.clone());
+                }
+                let copy = IdMapping {
+                    content_type: content.content_type.clone(),
+                    title: content.title.clone(),
+                    old_id: content.id.clone(),
+                    new_id: new_id.clone(),
+                };
+                self.copied(run, copy)?;
+                new_id
+            }
+        };
+
+        let labels: Vec<Value> = source["metadata"]["labels"]["results"]
+            .as_array()
+            .map(|labels| {
+                labels
+                    .iter()
+                    .map(|l| json!({ "prefix": "global", "name": l["name"] }))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !labels.is_empty() {
+            let (_, status, d) = self
+                .target
+                .post(Value::Array(labels))
+                .custom_endpoint(&format!("content/{}/label", new_id))
+                .execute::<Value>()?;
+            expect(status, d, "add labels")?;
+        }
+
+        for property in get_all(
+            &self.source,
+            &format!("content/{}/property", content.id),
+            "list properties",
+        )? {
+            let (_, status, d) = self
+                .target
+                .post(json!({ "key": property["key"], "value": property["value"] }))
+                .custom_endpoint(&format!("content/{}/property", new_id))
+                .execute::<Value>()?;
+            // Set by a previous run that was interrupted.
+            if status != StatusCode::CONFLICT {
+                expect(status, d, "set property")?;
+            }
+        }
+
+        let endpoint = format!("content/{}/child/attachment", content.id);
+        let mut copies = None;
+        for attachment in get_all(&self.source, &endpoint, "list attachments")? {
+            let id = text(&attachment["id"]);
+            if run.checkpoint.ids.contains_key(&id) {
+                continue;
+            }
+            let title = text(&attachment["title"]);
+            let copies = match &mut copies {
+                Some(copies) => copies,
+                None => copies.insert(api.attachments(&new_id)?),
+            };
+            let copy = match copies.iter().find(|a| a.title == title) {
+                Some(copy) => copy.id.clone(),
+                None => {
+                    let download = attachment["_links"]["download"]
+                        .as_str()
+                        .ok_or_else(|| format!("Unable to download {}: no download link", title))?;
+                    let media_type = attachment["extensions"]["mediaType"]
+                        .as_str()
+                        .or_else(|| attachment["metadata"]["mediaType"].as_str())
+                        .unwrap_or("application/octet-stream");
+                    // Streamed, so large attachments are never held in memory.
+                    let download = self.source.download_stream(download)?;
+                    let size = download
+                        .content_length()
+                        .or_else(|| attachment["extensions"]["fileSize"].as_u64())
+                        .ok_or_else(|| format!("Unable to download {}: unknown size", title))?;
+                    AttachmentUpload::from_reader(&self.target, &new_id, &title, download, size)
+                        .with_media_type(media_type)
+                        .create()?
+                        .id
+                }
+            };
+            let copy = IdMapping {
+                content_type: "attachment".to_string(),
+                title,
+                old_id: id,
+                new_id: copy,
+            };
+            self.copied(run, copy)?;
+        }
+
+        let endpoint = format!(
+            "content/{}/child/comment?expand=body.storage,ancestors&depth=all",
+            content.id
+        );
+        for comment in get_all(&self.source, &endpoint, "list comments")? {
+            let id = text(&comment["id"]);
+            if run.checkpoint.ids.contains_key(&id) {
+                continue;
+            }
+            let (body, _) = self.rewrite(&comment, known, run)?;
+            let mut request = json!({
+                "type": "comment",
+                "container": { "id": new_id, "type": content.content_type },
+                "body": { "storage": { "value": body, "representation": "storage" } },
+            });
+            let reply_to = comment["ancestors"]
+                .as_array()
+                .and_then(|a| a.last())
+                .and_then(|a| run.checkpoint.ids.get(&text(&a["id"])));
+            if let Some(reply_to) = reply_to {
+                request["ancestors"] = json!([{ "id": reply_to }]);
+            }
+            let (_, status, d) = self.target.post(request).content().execute::<Value>()?;
+            let created = expect(status, d, &format!("copy comment {}", id))?;
+            let copy = IdMapping {
+                content_type: "comment".to_string(),
+                title: text(&comment["title"]),
+                old_id: id,
+                new_id: text(&created["id"]),
+            };
+            self.copied(run, copy)?;
+        }
+
+        run.checkpoint.done.insert(content.id.clone());
+        self.save(&run.checkpoint)
+    }
+
+    /// Updates the copy of `id` once everything it links to is copied.
+    fn relink(&self, id: &str, known: &HashSet<&str>, run: &mut Run) -> Result<()> {
+        let new_id = match run.checkpoint.ids.get(id) {
+            Some(new_id) => new_id.clone(),
+            None => return Ok(()),
+        };
+        let source = get_json(
+            &self.source,
+            &format!("content/{}?expand=body.storage", id),
+            &format!("get {}", id),
+        )?;
+        let (body, _) = self.rewrite(&source, known, run)?;
+        let current = get_json(
+            &self.target,
+            &format!("content/{}?expand=version,ancestors", new_id),
+            &format!("get {}", new_id),
+        )?;
+        let mut request = UpdatePageRequest::new(
+            &new_id,
+            &text(&current["type"]),
+            &text(&current["title"]),
+            Space::new(&self.target_space),
+            Body::new(Storage::new(&body, Representation::Storage)),
+            None,
+            Version::new(current["version"]["number"].as_u64().unwrap_or(1) + 1),
+        );
+        request.ancestors = current["ancestors"]
+            .as_array()
+            .and_then(|a| a.last())
+            .map(|a| vec![Ancestor::new(&text(&a["id"]))]);
+        let (_, status, d) = self
+            .target
+            .put(request)
+            .content()
+            .content_id(&new_id)
+            .execute::<Value>()?;
+        expect(status, d, &format!("update {}", new_id)).map(|_| ())
+    }
+
+    /// The storage body of `content` for the target, and whether every
+    /// link to content of the space could be rewritten.
+    /// The id of the content of the target space with the type and title
+    /// of `content`, if any.
+    fn find_copy(&self, content: &Listed) -> Result<Option<String>> {
+        let endpoint = format!(
+            "content?type={}&spaceKey={}&title={}",
+            content.content_type,
+            encode_query(&self.target_space),
+            encode_query(&content.title)
+        );
+        let found = get_json(&self.target, &endpoint, &format!("find {}", content.title))?;
+        Ok(found["results"]
+            .as_array()
+            .and_then(|r| r.first())
+            .map(|c| text(&c["id"])))
+    }
+
+    fn rewrite(
+        &self,
+        content: &Value,
+        known: &HashSet<&str>,
+        run: &mut Run,
+    ) -> Result<(String, bool)> {
+        let body = content["body"]["storage"]["value"]
+            .as_str()
+            .unwrap_or_default();
+        let body = self.map_users(body, run)?;
+        Ok(rewrite_references(
+            &body,
+            &self.source_space,
+            &self.target_space,
+            &run.checkpoint.ids,
+            known,
+        ))
+    }
+
+    /// Replaces `ri:username` and `ri:userkey` attributes with the
+    /// `ri:account-id` of the user on the target.
+    fn map_users(&self, body: &str, run: &mut Run) -> Result<String> {
+        let mut body = body.to_string();
+        for attribute in ["ri:username=\"", "ri:userkey=\""] {
+            let mut out = String::with_capacity(body.len());
+            let mut rest = body.as_str();
+            while let Some(at) = rest.find(attribute) {
+                let (head, tail) = rest.split_at(at);
+                out.push_str(head);
+                let value_at = attribute.len();
+                let end = match tail[value_at..].find('"') {
+                    Some(end) => value_at + end,
+                    None => {
+                        rest = tail;
+                        break;
+                    }
+                };
+                let value = &tail[value_at..end];
+                let username = if attribute == "ri:username=\"" {
+                    Some(value.to_string())
+                } else {
+                    self.username(value, run)?
+                };
+                match username.as_deref().and_then(|u| self.users.account_id(u)) {
+                    Some(account_id) => out.push_str(&format!("ri:account-id=\"{}", account_id)),
+                    None => {
+                        run.report
+                            .unmapped_users
+                            .insert(username.unwrap_or_else(|| value.to_string()));
+                        out.push_str(&tail[..end]);
+                    }
+                }
+                rest = &tail[end..];
+            }
+            out.push_str(rest);
+            body = out;
+        }
+        Ok(body)
+    }
+
+    /// The source username of the user with `key`, looked up once.
+    fn username(&self, key: &str, run: &mut Run) -> Result<Option<String>> {
+        if let Some(username) = run.usernames.get(key) {
+            return Ok(username.clone());
+        }
+        let (_, status, d) = self
+            .source
+            .get()
+            .custom_endpoint(&format!("user?key={}", encode_query(key)))
+            .execute::<Value>()?;
+        let username = match d {
+            Some(user) if status.is_success() => user["username"].as_str().map(str::to_string),
+            _ => None,
+        };
+        run.usernames.insert(key.to_string(), username.clone());
+        Ok(username)
+    }
+
+    /// Records a copy and saves the checkpoint.
+    fn copied(&self, run: &mut Run, copy: IdMapping) -> Result<()> {
+        run.checkpoint
+            .ids
+            .insert(copy.old_id.clone(), copy.new_id.clone());
+        run.report.copied.push(copy);
+        self.save(&run.checkpoint)
+    }
+
+    fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        match &self.checkpoint {
+            Some(path) => checkpoint.save(path),
+            None => Ok(()),
+        }
+    }
+}
+
+fn summary(confluence: &Confluence, id: &str) -> Result<Summary> {
+    let content = get_json(
+        confluence,
+        &format!("content/{}?expand=metadata.labels", id),
+        &format!("get {}", id),
+    )?;
+    let mut labels: Vec<String> = content["metadata"]["labels"]["results"]
+        .as_array()
+        .map(|labels| labels.iter().map(|l| text(&l["name"])).collect())
+        .unwrap_or_default();
+    labels.sort();
+    let mut properties: Vec<String> = get_all(
+        confluence,
+        &format!("content/{}/property", id),
+        "list properties",
+    )?
+    .iter()
+    .map(|p| text(&p["key"]))
+    .collect();
+    properties.sort();
+    let mut attachments: Vec<(String, Option<u64>)> = get_all(
+        confluence,
+        &format!("content/{}/child/attachment", id),
+        "list attachments",
+    )?
+    .iter()
+    .map(|a| (text(&a["title"]), a["extensions"]["fileSize"].as_u64()))
+    .collect();
+    attachments.sort();
+    let comments = get_all(
+        confluence,
+        &format!("content/{}/child/comment?depth=all", id),
+        "list comments",
+    )?
+    .len();
+    Ok(Summary {
+        title: text(&content["title"]),
+        labels,
+        properties,
+        attachments,
+        comments,
+    })
+}
+
+fn text(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeConfluence;
+
+    fn source() -> (FakeConfluence, String, String) {
+        let server = FakeConfluence::start()
+            .unwrap()
+            .with_space("OLD", "Old")
+            .with_user("jdoe", "8a7f0001");
+        let confluence = server.client();
+        let home = server.create_page("OLD", "Home", None);
+        let guide = server.create_page_with_body(
+            "OLD",
+            "Guide",
+            &format!(
+                r#"<p><ac:link><ri:user ri:userkey="8a7f0001" /></ac:link> <a href="/pages/viewpage.action?pageId={}">home</a></p>"#,
+                home
+            ),
+            Some(&home),
+        );
+        confluence
+            .post(json!([{ "prefix": "global", "name": "howto" }]))
+            .custom_endpoint(&format!("content/{}/label", guide))
+            .execute::<Value>()
+            .unwrap();
+        confluence
+            .post(json!({ "key": "owner", "value": "docs" }))
+            .custom_endpoint(&format!("content/{}/property", guide))
+            .execute::<Value>()
+            .unwrap();
+        DeploymentClient::detect(&confluence)
+            .unwrap()
+            .upload_attachment(&guide, "setup.png", b"png".to_vec(), "image/png")
+            .unwrap();
+        confluence
+            .post(json!({
+                "type": "comment",
+                "container": { "id": guide, "type": "page" },
+                "body": { "storage": {
+                    "value": r#"<p>Thanks <ri:user ri:username="asmith" /></p>"#,
+                    "representation": "storage",
+                } },
+            }))
+            .content()
+            .execute::<Value>()
+            .unwrap();
+        (server, home, guide)
+    }
+
+    #[test]
+    fn copies_a_space_to_another_instance() {
+        let (source, _, _) = source();
+        let target = FakeConfluence::start().unwrap().with_space("NEW", "New");
+        let users = UserMap::parse("username,accountId\njdoe,5b10ac8d82e05b22cc7d4ef5\n").unwrap();
+        let report = SpaceMigration::new(&source.client(), "OLD", &target.client(), "NEW")
+            .with_users(users)
+            .migrate()
+            .unwrap();
+
+        let kinds: Vec<&str> = report
+            .copied
+            .iter()
+            .map(|m| m.content_type.as_str())
+            .collect();
+        assert_eq!(kinds, vec!["page", "page", "attachment", "comment"]);
+        assert!(report.verification.is_ok(), "{}", report);
+        assert_eq!(report.verification.checked, 2);
+        assert_eq!(
+            report.unmapped_users.iter().collect::<Vec<_>>(),
+            vec!["asmith"]
+        );
+
+        let new_home = target.find("NEW", "Home").unwrap().id;
+        let copy = target.find("NEW", "Guide").unwrap();
+        assert_eq!(copy.parent_id.as_deref(), Some(new_home.as_str()));
+        assert!(copy
+            .body
+            .contains(r#"<ri:user ri:account-id="5b10ac8d82e05b22cc7d4ef5" />"#));
+        assert!(copy.body.contains(&format!("pageId={}\"", new_home)));
+        let comment = target
+            .contents()
+            .into_iter()
+            .find(|c| c.content_type == "comment")
+            .unwrap();
+        assert_eq!(comment.parent_id.as_deref(), Some(copy.id.as_str()));
+        assert!(comment.body.contains(r#"ri:username="asmith""#));
+    }
+
+    #[test]
+    fn resumes_from_the_checkpoint() {
+        let (source, home, guide) = source();
+        let target = FakeConfluence::start().unwrap().with_space("NEW", "New");
+        let path = std::env::temp_dir().join(format!("migrate-{}.json", std::process::id()));
+        let migration = SpaceMigration::new(&source.client(), "OLD", &target.client(), "NEW")
+            .with_checkpoint(&path);
+
+        // As if the first run stopped after creating the guide, before its
+        // attachment and comment.
+        let new_home = target.create_page("NEW", "Home", None);
+        let new_guide = target.create_page("NEW", "Guide", Some(&new_home));
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.ids.insert(home.clone(), new_home.clone());
+        checkpoint.ids.insert(guide.clone(), new_guide.clone());
+        checkpoint.done.insert(home);
+        checkpoint.save(&path).unwrap();
+
+        let report = migration.migrate().unwrap();
+        let saved = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.resumed, 1);
+        let kinds: Vec<&str> = report
+            .copied
+            .iter()
+            .map(|m| m.content_type.as_str())
+            .collect();
+        assert_eq!(kinds, vec!["attachment", "comment"]);
+        assert!(report.verification.is_ok(), "{}", report);
+        assert!(saved.done.contains(&guide));
+        assert_eq!(saved.ids.len(), 4);
+        assert_eq!(target.contents().len(), 4);
+    }
+
+    #[test]
+    fn adopts_content_created_before_the_checkpoint_was_saved() {
+        let (source, home, guide) = source();
+        let target = FakeConfluence::start().unwrap().with_space("NEW", "New");
+        let path = std::env::temp_dir().join(format!("migrate-adopt-{}.json", std::process::id()));
+        let migration = SpaceMigration::new(&source.client(), "OLD", &target.client(), "NEW")
+            .with_checkpoint(&path);
+
+        // As if the first run stopped right after creating the guide.
+        let new_home = target.create_page("NEW", "Home", None);
+        let new_guide = target.create_page("NEW", "Guide", Some(&new_home));
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.ids.insert(home.clone(), new_home);
+        checkpoint.done.insert(home);
+        checkpoint.save(&path).unwrap();
+
+        let report = migration.migrate().unwrap();
+        let saved = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(report.verification.is_ok(), "{}", report);
+        assert_eq!(saved.ids.get(&guide), Some(&new_guide));
+        assert_eq!(target.contents().len(), 4);
+    }
+
+    #[test]
+    fn unclosed_user_attributes_are_kept_once() {
+        let confluence = Confluence::new("https://wiki.example.com/rest/api");
+        let users = UserMap::parse("username,accountId\njdoe,5b10ac8d\n").unwrap();
+        let migration =
+            SpaceMigration::new(&confluence, "OLD", &confluence, "NEW").with_users(users);
+        let mut run = Run {
+            checkpoint: Checkpoint::default(),
+            report: MigrationReport::default(),
+            usernames: HashMap::new(),
+        };
+        let body = r#"<ri:user ri:username="jdoe" /> <ri:user ri:username="asmith"#;
+        assert_eq!(
+            migration.map_users(body, &mut run).unwrap(),
+            r#"<ri:user ri:account-id="5b10ac8d" /> <ri:user ri:username="asmith"#
+        );
+    }
+
+    #[test]
+    fn user_maps_skip_comments_and_the_header() {
+        let users = UserMap::parse("# exported 2024-05-01\nusername,accountId\n\njdoe, 5b10ac8d\n")
+            .unwrap();
+        assert_eq!(users, UserMap::new().with_user("jdoe", "5b10ac8d"));
+        assert!(UserMap::parse("jdoe").is_err());
+    }
+}
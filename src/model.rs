@@ -16,32 +16,150 @@ impl Space {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A content body. It is sent keyed by its representation, e.g.
+/// `{"storage": {...}}` or `{"atlas_doc_format": {...}}`.
+#[derive(Debug)]
 pub struct Body {
     pub storage: Storage,
 }
 
+impl Serialize for Body {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.storage.representation.as_str(), &self.storage)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Body {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let mut map = serde_json::Map::<String, serde_json::Value>::deserialize(deserializer)?;
+        // Known representations first, then any other but `_expandable`
+        // and the like.
+        let key = Representation::KNOWN
+            .iter()
+            .map(Representation::as_str)
+            .find(|key| map.contains_key(*key))
+            .map(str::to_string)
+            .or_else(|| map.keys().find(|key| !key.starts_with('_')).cloned())
+            .ok_or_else(|| D::Error::custom("empty body"))?;
+        let storage = map.remove(&key).unwrap_or_default();
+        serde_json::from_value(storage)
+            .map(Body::new)
+            .map_err(D::Error::custom)
+    }
+}
+
 impl Body {
     pub fn new(storage: Storage) -> Self {
         Self { storage }
     }
 }
 
+/// The formats Confluence can represent a content body in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Representation {
+    Storage,
+    Editor,
+    View,
+    ExportView,
+    StyledView,
+    Wiki,
+    AtlasDocFormat,
+    /// A representation this crate doesn't know of, by name.
+    Other(String),
+}
+
+impl Representation {
+    const KNOWN: [Representation; 7] = [
+        Representation::Storage,
+        Representation::AtlasDocFormat,
+        Representation::Editor,
+        Representation::View,
+        Representation::ExportView,
+        Representation::StyledView,
+        Representation::Wiki,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Representation::Storage => "storage",
+            Representation::Editor => "editor",
+            Representation::View => "view",
+            Representation::ExportView => "export_view",
+            Representation::StyledView => "styled_view",
+            Representation::Wiki => "wiki",
+            Representation::AtlasDocFormat => "atlas_doc_format",
+            Representation::Other(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for Representation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Only known representations parse, so that typos don't pass for
+/// [`Representation::Other`].
+impl std::str::FromStr for Representation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Representation::KNOWN
+            .iter()
+            .find(|r| r.as_str() == s)
+            .cloned()
+            .ok_or_else(|| format!("Unknown representation {}", s))
+    }
+}
+
+impl Serialize for Representation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Representation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(name.parse().unwrap_or(Representation::Other(name)))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Storage {
     pub value: String,
-    pub representation: String,
+    pub representation: Representation,
 }
 
 impl Storage {
-    pub fn new(value: &str, representation: &str) -> Self {
+    pub fn new(value: &str, representation: Representation) -> Self {
         Self {
             value: value.to_string(),
-            representation: representation.to_string(),
+            representation,
         }
     }
 }
 
+impl From<&crate::storage::Document> for Storage {
+    fn from(doc: &crate::storage::Document) -> Self {
+        Self::new(&doc.to_string(), Representation::Storage)
+    }
+}
+
+impl From<&crate::adf::Document> for Storage {
+    fn from(doc: &crate::adf::Document) -> Self {
+        Self::new(
+            &serde_json::to_string(doc).expect("ADF documents serialize"),
+            Representation::AtlasDocFormat,
+        )
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Version {
     pub number: u64,
@@ -90,7 +208,7 @@ impl Clone for CreatePageRequest {
             space: Space::new(&self.space.key.clone()),
             body: Body::new(Storage::new(
                 &self.body.storage.value.clone(),
-                &self.body.storage.representation.clone(),
+                self.body.storage.representation.clone(),
             )),
             ancestors: match &self.ancestors {
                 Some(v) => {
@@ -233,6 +351,69 @@ impl UpdateAttachmentRequest {
     }
 }
 
+/// The name, media type and comment of an attachment, updated with
+/// `put(request).content().content_id(page).child().attachment().attachment_id(id)`.
+/// Naming another page as the container moves the attachment there.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateAttachmentPropertiesRequest {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub title: String,
+    pub version: Version,
+    pub metadata: AttachmentMetadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+}
+
+impl UpdateAttachmentPropertiesRequest {
+    /// `version` is the current version of the attachment plus one.
+    pub fn new(id: &str, title: &str, version: Version) -> Self {
+        Self {
+            id: id.to_string(),
+            type_name: "attachment".to_string(),
+            title: title.to_string(),
+            version,
+            metadata: AttachmentMetadata::default(),
+            container: None,
+        }
+    }
+
+    pub fn with_media_type(mut self, media_type: &str) -> Self {
+        self.metadata.media_type = Some(media_type.to_string());
+        self
+    }
+
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.metadata.comment = Some(comment.to_string());
+        self
+    }
+
+    /// Moves the attachment to the page or blog post `id`.
+    pub fn with_container(mut self, id: &str, type_name: &str) -> Self {
+        self.container = Some(Container {
+            id: id.to_string(),
+            type_name: type_name.to_string(),
+        });
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AttachmentMetadata {
+    #[serde(rename = "mediaType", skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Container {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ContentData {
     pub id: String,
@@ -256,8 +437,8 @@ impl From<&Content> for ContentData {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AttachmentData {
-    id: String,
-    title: String,
+    pub id: String,
+    pub title: String,
 }
 
 impl From<&Content> for AttachmentData {
@@ -268,3 +449,36 @@ impl From<&Content> for AttachmentData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bodies_skip_expandable_keys() {
+        let body: Body = serde_json::from_value(json!({
+            "_expandable": { "view": "" },
+            "storage": { "value": "<p>Hi</p>", "representation": "storage" },
+        }))
+        .unwrap();
+        assert_eq!(body.storage.value, "<p>Hi</p>");
+        assert_eq!(body.storage.representation, Representation::Storage);
+    }
+
+    #[test]
+    fn unknown_representations_are_kept() {
+        let body: Body = serde_json::from_value(json!({
+            "_expandable": {},
+            "anonymous_export_view": { "value": "<p>Hi</p>", "representation": "anonymous_export_view" },
+        }))
+        .unwrap();
+        let other = Representation::Other("anonymous_export_view".to_string());
+        assert_eq!(body.storage.representation, other);
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({ "anonymous_export_view": { "value": "<p>Hi</p>", "representation": "anonymous_export_view" } })
+        );
+        assert!("anonymous_export_view".parse::<Representation>().is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Client side throttling.
+//!
+//! A [`RateLimiter`] set with `Confluence::with_rate_limiter` is consulted
+//! before every request, including retries. It combines a token bucket,
+//! limiting the request rate while allowing short bursts, with a cap on the
+//! requests in flight. The limiter is shared through an `Arc`, so one limiter
+//! can throttle the clients of several threads together.
+//!
+//! ```
+//! use confluence::client::Confluence;
+//! use confluence::ratelimit::RateLimiter;
+//! use std::sync::Arc;
+//!
+//! let limiter = Arc::new(RateLimiter::new().with_rate(10.0, 5).with_max_in_flight(4));
+//! let confluence = Confluence::new("https://confluence.example.com/rest/api")
+//!     .with_rate_limiter(Arc::clone(&limiter));
+//! assert_eq!(limiter.metrics().requests, 0);
+//! ```
+
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Counters of the requests a limiter let through.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateLimitMetrics {
+    pub requests: u64,
+    /// Requests that had to wait.
+    pub throttled: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    refilled: Instant,
+    in_flight: usize,
+    metrics: RateLimitMetrics,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Requests per second and bucket size.
+    rate: Option<(f64, u32)>,
+    max_in_flight: Option<usize>,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    /// A limiter that lets every request through until configured.
+    pub fn new() -> Self {
+        Self {
+            rate: None,
+            max_in_flight: None,
+            state: Mutex::new(State {
+                tokens: 0.0,
+                refilled: Instant::now(),
+                in_flight: 0,
+                metrics: RateLimitMetrics::default(),
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Allows `requests_per_second` on average and bursts of up to `burst`
+    /// requests. Panics unless `requests_per_second` is finite and above 0.
+    pub fn with_rate(mut self, requests_per_second: f64, burst: u32) -> Self {
+        assert!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "the rate must be finite and above 0, not {}",
+            requests_per_second
+        );
+        let burst = burst.max(1);
+        self.rate = Some((requests_per_second, burst));
+        self.state.get_mut().unwrap().tokens = burst as f64;
+        self
+    }
+
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Blocks until a request may be sent. The request counts as in flight
+    /// until the permit is dropped.
+    pub fn acquire(&self) -> Permit<'_> {
+        let start = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let mut wait = None;
+            if let Some((rate, burst)) = self.rate {
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.refilled).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * rate).min(burst as f64);
+                state.refilled = now;
+                if state.tokens < 1.0 {
+                    wait = Some(Duration::from_secs_f64((1.0 - state.tokens) / rate));
+                }
+            }
+            let full = self.max_in_flight.is_some_and(|max| state.in_flight >= max);
+            state = match wait {
+                // Woken when a permit is released.
+                _ if full => self.released.wait(state).unwrap(),
+                Some(wait) => self.released.wait_timeout(state, wait).unwrap().0,
+                None => break,
+            };
+        }
+        if self.rate.is_some() {
+            state.tokens -= 1.0;
+        }
+        state.in_flight += 1;
+        let waited = start.elapsed();
+        let metrics = &mut state.metrics;
+        metrics.requests += 1;
+        if waited > Duration::from_millis(1) {
+            metrics.throttled += 1;
+            metrics.total_wait += waited;
+            metrics.max_wait = metrics.max_wait.max(waited);
+        }
+        Permit { limiter: self }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    pub fn metrics(&self) -> RateLimitMetrics {
+        self.state.lock().unwrap().metrics.clone()
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().in_flight -= 1;
+        self.released.notify_all();
+    }
+}
+
+/// Permission to send one request, see [`RateLimiter::acquire`].
+pub struct Permit<'a> {
+    limiter: &'a RateLimiter,
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn token_bucket_allows_bursts_then_throttles() {
+        let limiter = RateLimiter::new().with_rate(20.0, 2);
+        let start = Instant::now();
+        for _ in 0..3 {
+            drop(limiter.acquire());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(45));
+        let metrics = limiter.metrics();
+        assert_eq!(metrics.requests, 3);
+        assert_eq!(metrics.throttled, 1);
+        assert!(metrics.total_wait >= Duration::from_millis(45));
+    }
+
+    #[test]
+    fn rejects_rates_that_never_refill() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let limiter = std::panic::catch_unwind(|| RateLimiter::new().with_rate(rate, 1));
+            assert!(limiter.is_err(), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn caps_requests_in_flight() {
+        let limiter = Arc::new(RateLimiter::new().with_max_in_flight(1));
+        let permit = limiter.acquire();
+        let other = {
+            let limiter = Arc::clone(&limiter);
+            std::thread::spawn(move || {
+                let _permit = limiter.acquire();
+                limiter.in_flight()
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(limiter.in_flight(), 1);
+        drop(permit);
+        assert_eq!(other.join().unwrap(), 1);
+        assert_eq!(limiter.in_flight(), 0);
+        assert!(limiter.metrics().max_wait >= Duration::from_millis(45));
+    }
+}
@@ -0,0 +1,436 @@
+//! Reading and changing who may view or edit content.
+//!
+//! A page or blog post can be restricted per operation, `read` or
+//! `update`, to users and groups. [`ContentRestrictions`] reads them from
+//! `content/{id}/restriction` and replaces, adds or removes them, so a
+//! page can be restricted right after it is created:
+//!
+//! ```no_run
+//! use confluence::client::Confluence;
+//! use confluence::restriction::{ContentRestrictions, Operation, Restriction, UserRef};
+//!
+//! let confluence = Confluence::new("https://wiki.example.com/rest/api");
+//! let restrictions = ContentRestrictions::new(&confluence, "123456");
+//! restrictions
+//!     .replace(&[
+//!         Restriction::new(Operation::Read).with_group("finance"),
+//!         Restriction::new(Operation::Update).with_user(UserRef::username("jo")),
+//!     ])
+//!     .unwrap();
+//! for group in restrictions.get_operation(Operation::Read).unwrap().groups() {
+//!     println!("visible to {}", group.name);
+//! }
+//! ```
+//!
+//! Users are given by username or user key on Server and Data Center and
+//! by account id on Cloud.
+
+use crate::client::{Confluence, Executor, Result};
+use crate::content::model::Results;
+use crate::util::{check, encode_query, parse};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// An operation content can be restricted for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Read,
+    Update,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Read => "read",
+            Operation::Update => "update",
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Operation::Read),
+            "update" => Ok(Operation::Update),
+            _ => Err(format!("Unknown operation {}", s)),
+        }
+    }
+}
+
+/// A user to restrict an operation to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UserRef {
+    Username(String),
+    Key(String),
+    AccountId(String),
+}
+
+impl UserRef {
+    pub fn username(username: &str) -> Self {
+        UserRef::Username(username.to_string())
+    }
+
+    pub fn key(key: &str) -> Self {
+        UserRef::Key(key.to_string())
+    }
+
+    pub fn account_id(account_id: &str) -> Self {
+        UserRef::AccountId(account_id.to_string())
+    }
+
+    /// The query parameter selecting the user.
+    fn query(&self) -> String {
+        match self {
+            UserRef::Username(username) => format!("userName={}", encode_query(username)),
+            UserRef::Key(key) => format!("key={}", encode_query(key)),
+            UserRef::AccountId(id) => format!("accountId={}", encode_query(id)),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            UserRef::Username(username) => json!({ "type": "known", "username": username }),
+            UserRef::Key(key) => json!({ "type": "known", "userKey": key }),
+            UserRef::AccountId(id) => json!({ "type": "known", "accountId": id }),
+        }
+    }
+}
+
+/// The users and groups to restrict an operation to, as sent to
+/// [`ContentRestrictions::replace`] and [`ContentRestrictions::add`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Restriction {
+    pub operation: Operation,
+    pub users: Vec<UserRef>,
+    pub groups: Vec<String>,
+}
+
+impl Restriction {
+    pub fn new(operation: Operation) -> Self {
+        Self {
+            operation,
+            users: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    pub fn with_user(mut self, user: UserRef) -> Self {
+        self.users.push(user);
+        self
+    }
+
+    pub fn with_group(mut self, name: &str) -> Self {
+        self.groups.push(name.to_string());
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "operation": self.operation,
+            "restrictions": {
+                "user": self.users.iter().map(UserRef::to_json).collect::<Vec<_>>(),
+                "group": self
+                    .groups
+                    .iter()
+                    .map(|name| json!({ "type": "group", "name": name }))
+                    .collect::<Vec<_>>(),
+            },
+        })
+    }
+}
+
+/// The restriction of one operation, as read from Confluence.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContentRestriction {
+    pub operation: Operation,
+    #[serde(default)]
+    pub restrictions: RestrictionSubjects,
+}
+
+impl ContentRestriction {
+    pub fn users(&self) -> &[RestrictedUser] {
+        &self.restrictions.user.results
+    }
+
+    pub fn groups(&self) -> &[RestrictedGroup] {
+        &self.restrictions.group.results
+    }
+
+    /// Whether the operation is open to everyone with space permission.
+    pub fn is_empty(&self) -> bool {
+        self.users().is_empty() && self.groups().is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RestrictionSubjects {
+    #[serde(default)]
+    pub user: SubjectList<RestrictedUser>,
+    #[serde(default)]
+    pub group: SubjectList<RestrictedGroup>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SubjectList<T> {
+    #[serde(default)]
+    pub results: Vec<T>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RestrictedUser {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(rename = "userKey", default)]
+    pub user_key: Option<String>,
+    #[serde(rename = "accountId", default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(rename = "displayName", default)]
+    pub display_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RestrictedGroup {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+/// The restrictions of a page or blog post.
+pub struct ContentRestrictions {
+    confluence: Confluence,
+    content_id: String,
+}
+
+impl ContentRestrictions {
+    pub fn new(confluence: &Confluence, content_id: &str) -> Self {
+        Self {
+            confluence: confluence.clone(),
+            content_id: content_id.to_string(),
+        }
+    }
+
+    /// The restrictions of every operation, with their users and groups.
+    pub fn get(&self) -> Result<Vec<ContentRestriction>> {
+        let endpoint = format!(
+            "{}?expand=restrictions.user,restrictions.group",
+            self.endpoint()
+        );
+        let (_, status, d) = self
+            .confluence
+            .get()
+            .custom_endpoint(&endpoint)
+            .execute::<Results<ContentRestriction>>()?;
+        Ok(parse(status, d, "get restrictions")?.results)
+    }
+
+    /// The restriction of `operation`, empty if there is none.
+    pub fn get_operation(&self, operation: Operation) -> Result<ContentRestriction> {
+        Ok(self
+            .get()?
+            .into_iter()
+            .find(|r| r.operation == operation)
+            .unwrap_or(ContentRestriction {
+                operation,
+                restrictions: RestrictionSubjects::default(),
+            }))
+    }
+
+    /// Replaces all restrictions with `restrictions`.
+    pub fn replace(&self, restrictions: &[Restriction]) -> Result<Vec<ContentRestriction>> {
+        let (_, status, d) = self
+            .confluence
+            .put(body(restrictions))
+            .custom_endpoint(&self.endpoint())
+            .execute::<Results<ContentRestriction>>()?;
+        Ok(parse(status, d, "replace restrictions")?.results)
+    }
+
+    /// Adds the users and groups of `restrictions` to the current ones.
+    pub fn add(&self, restrictions: &[Restriction]) -> Result<Vec<ContentRestriction>> {
+        let (_, status, d) = self
+            .confluence
+            .post(body(restrictions))
+            .custom_endpoint(&self.endpoint())
+            .execute::<Results<ContentRestriction>>()?;
+        Ok(parse(status, d, "add restrictions")?.results)
+    }
+
+    /// Removes all restrictions.
+    pub fn clear(&self) -> Result<()> {
+        let (_, status, _) = self
+            .confluence
+            .delete()
+            .custom_endpoint(&self.endpoint())
+            .execute::<Value>()?;
+        check(status, "clear restrictions")
+    }
+
+    pub fn add_user(&self, operation: Operation, user: &UserRef) -> Result<()> {
+        let endpoint = format!("{}/user?{}", self.by_operation(operation), user.query());
+        let (_, status, _) = self
+            .confluence
+            .put(())
+            .custom_endpoint(&endpoint)
+            .execute::<Value>()?;
+        check(status, "add user restriction")
+    }
+
+    pub fn remove_user(&self, operation: Operation, user: &UserRef) -> Result<()> {
+        let endpoint = format!("{}/user?{}", self.by_operation(operation), user.query());
+        let (_, status, _) = self
+            .confluence
+            .delete()
+            .custom_endpoint(&endpoint)
+            .execute::<Value>()?;
+        check(status, "remove user restriction")
+    }
+
+    pub fn add_group(&self, operation: Operation, name: &str) -> Result<()> {
+        let endpoint = format!(
+            "{}/group/{}",
+            self.by_operation(operation),
+            encode_query(name)
+        );
+        let (_, status, _) = self
+            .confluence
+            .put(())
+            .custom_endpoint(&endpoint)
+            .execute::<Value>()?;
+        check(status, "add group restriction")
+    }
+
+    pub fn remove_group(&self, operation: Operation, name: &str) -> Result<()> {
+        let endpoint = format!(
+            "{}/group/{}",
+            self.by_operation(operation),
+            encode_query(name)
+        );
+        let (_, status, _) = self
+            .confluence
+            .delete()
+            .custom_endpoint(&endpoint)
+            .execute::<Value>()?;
+        check(status, "remove group restriction")
+    }
+
+    fn endpoint(&self) -> String {
+        format!("content/{}/restriction", self.content_id)
+    }
+
+    fn by_operation(&self, operation: Operation) -> String {
+        format!("{}/byOperation/{}", self.endpoint(), operation)
+    }
+}
+
+fn body(restrictions: &[Restriction]) -> Value {
+    Value::Array(restrictions.iter().map(Restriction::to_json).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeConfluence;
+
+    fn names(restriction: &ContentRestriction) -> (Vec<String>, Vec<String>) {
+        (
+            restriction
+                .users()
+                .iter()
+                .filter_map(|u| u.username.clone())
+                .collect(),
+            restriction
+                .groups()
+                .iter()
+                .map(|g| g.name.clone())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn restrictions_are_replaced_added_and_cleared() {
+        let server = FakeConfluence::start()
+            .unwrap()
+            .with_space("DOC", "Docs")
+            .with_user("jo", "key-jo");
+        let page = server.create_page("DOC", "Budget", None);
+        let restrictions = ContentRestrictions::new(&server.client(), &page);
+        assert!(restrictions.get().unwrap().iter().all(|r| r.is_empty()));
+
+        let replaced = restrictions
+            .replace(&[
+                Restriction::new(Operation::Read).with_group("finance"),
+                Restriction::new(Operation::Update).with_user(UserRef::username("jo")),
+            ])
+            .unwrap();
+        assert_eq!(replaced.len(), 2);
+        restrictions
+            .add(&[Restriction::new(Operation::Read).with_user(UserRef::key("key-jo"))])
+            .unwrap();
+        let read = restrictions.get_operation(Operation::Read).unwrap();
+        assert_eq!(
+            names(&read),
+            (vec!["jo".to_string()], vec!["finance".to_string()])
+        );
+        assert_eq!(read.users()[0].user_key.as_deref(), Some("key-jo"));
+        let update = restrictions.get_operation(Operation::Update).unwrap();
+        assert_eq!(names(&update), (vec!["jo".to_string()], vec![]));
+
+        restrictions
+            .replace(&[Restriction::new(Operation::Update).with_group("finance")])
+            .unwrap();
+        assert!(restrictions
+            .get_operation(Operation::Read)
+            .unwrap()
+            .is_empty());
+        restrictions.clear().unwrap();
+        assert!(server.content(&page).unwrap().restrictions.is_empty());
+    }
+
+    #[test]
+    fn single_users_and_groups_are_added_and_removed() {
+        let server = FakeConfluence::start()
+            .unwrap()
+            .with_space("DOC", "Docs")
+            .with_user("jo", "key-jo");
+        let page = server.create_page("DOC", "Budget", None);
+        let restrictions = ContentRestrictions::new(&server.client(), &page);
+        let jo = UserRef::username("jo");
+
+        restrictions
+            .add_group(Operation::Read, "finance team")
+            .unwrap();
+        restrictions.add_user(Operation::Read, &jo).unwrap();
+        // Adding again changes nothing.
+        restrictions.add_user(Operation::Read, &jo).unwrap();
+        let read = restrictions.get_operation(Operation::Read).unwrap();
+        assert_eq!(
+            names(&read),
+            (vec!["jo".to_string()], vec!["finance team".to_string()])
+        );
+
+        restrictions.remove_user(Operation::Read, &jo).unwrap();
+        restrictions
+            .remove_group(Operation::Read, "finance team")
+            .unwrap();
+        assert!(restrictions
+            .get_operation(Operation::Read)
+            .unwrap()
+            .is_empty());
+        let error = restrictions
+            .remove_group(Operation::Read, "finance team")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unable to remove group restriction: 404 Not Found"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Retrying requests that failed for transient reasons.
+//!
+//! A [`RetryPolicy`] configured with `Confluence::with_retry_policy` applies
+//! to every executor. Rate limited (429) requests are retried after the delay
+//! the server asks for in `Retry-After` or `X-RateLimit-Reset`; 502, 503 and
+//! 504 responses, timeouts and connection failures are retried with
+//! exponential backoff. Requests that aren't idempotent, such as attachment
+//! uploads, are only retried when they can't have been processed yet.
+
+use crate::client::Result;
+use reqwest::blocking::{Request, Response};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use std::cell::Cell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Randomizes each backoff between half and all of its length.
+    pub jitter: bool,
+    /// The longest server requested delay to wait for; the response is
+    /// returned as is if the server asks for more.
+    pub max_retry_after: Duration,
+    /// Also retries requests that aren't idempotent after 502, 503 and 504
+    /// responses and timeouts, which may duplicate them.
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            max_retry_after: Duration::from_secs(300),
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Sends every request once.
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+
+    pub fn with_retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// Whether a `method` request answered with `status` may be sent again.
+    pub fn retries_status(&self, method: &Method, status: StatusCode) -> bool {
+        match status {
+            // Rate limited requests are rejected before they're processed.
+            StatusCode::TOO_MANY_REQUESTS => true,
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => self.retry_non_idempotent || is_idempotent(method),
+            _ => false,
+        }
+    }
+
+    /// Whether a `method` request that failed with `err` may be sent again.
+    pub fn retries_error(&self, method: &Method, err: &reqwest::Error) -> bool {
+        err.is_connect()
+            || (err.is_timeout() && (self.retry_non_idempotent || is_idempotent(method)))
+    }
+
+    /// The delay before retry number `retry` (starting at 1), or `None` if
+    /// the server asks to wait longer than `max_retry_after`.
+    pub fn delay(&self, retry: u32, headers: Option<&HeaderMap>) -> Option<Duration> {
+        if let Some(wait) = headers.and_then(|h| server_delay(h, SystemTime::now())) {
+            return if wait <= self.max_retry_after {
+                Some(wait)
+            } else {
+                None
+            };
+        }
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        if self.jitter {
+            let half = backoff / 2;
+            Some(half + half.mul_f64(random_fraction()))
+        } else {
+            Some(backoff)
+        }
+    }
+
+    /// Sends `request` with `send`, retrying as the policy allows.
+    pub(crate) fn execute<F>(&self, mut request: Request, send: F) -> Result<Response>
+    where
+        F: Fn(Request) -> Result<Response>,
+    {
+        let mut retry = 1;
+        loop {
+            let method = request.method().clone();
+            // Streamed bodies can't be cloned, those requests are sent once.
+            let next = if retry < self.max_attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+            let wait = match send(request) {
+                Ok(res) => match next {
+                    Some(next) if self.retries_status(&method, res.status()) => {
+                        match self.delay(retry, Some(res.headers())) {
+                            Some(wait) => (next, wait),
+                            None => return Ok(res),
+                        }
+                    }
+                    _ => return Ok(res),
+                },
+                Err(err) => match (next, err.downcast_ref::<reqwest::Error>()) {
+                    (Some(next), Some(e)) if self.retries_error(&method, e) => {
+                        (next, self.delay(retry, None).unwrap_or_default())
+                    }
+                    _ => return Err(err),
+                },
+            };
+            request = wait.0;
+            std::thread::sleep(wait.1);
+            retry += 1;
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE
+    )
+}
+
+/// The wait the server asks for: `Retry-After` in seconds or as an HTTP
+/// date, else Cloud's `X-RateLimit-Reset` timestamp.
+fn server_delay(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+    };
+    let until = |epoch_secs: i64| {
+        let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        Some(Duration::from_secs((epoch_secs - now).max(0) as u64))
+    };
+    if let Some(value) = header(RETRY_AFTER.as_str()) {
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        if let Some(at) = parse_http_date(value) {
+            return until(at);
+        }
+    }
+    let reset = header("x-ratelimit-reset")?;
+    match reset.parse::<i64>() {
+        Ok(at) => until(at),
+        Err(_) => until(parse_iso_date(reset)?),
+    }
+}
+
+/// Seconds since the epoch of an IMF-fixdate, e.g.
+/// `Wed, 21 Oct 2015 07:28:00 GMT`.
+fn parse_http_date(value: &str) -> Option<i64> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 6 {
+        return None;
+    }
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let day: i64 = parts[1].parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == parts[2])? as i64 + 1;
+    let year: i64 = parts[3].parse().ok()?;
+    Some(crate::util::days_from_civil(year, month, day) * 86_400 + parse_time(parts[4])?)
+}
+
+/// Seconds since the epoch of a UTC ISO 8601 timestamp, e.g.
+/// `2024-03-01T10:00:00.000Z`.
+fn parse_iso_date(value: &str) -> Option<i64> {
+    let (date, time) = value.split_once('T')?;
+    let mut ymd = date.split('-');
+    let year: i64 = ymd.next()?.parse().ok()?;
+    let month: i64 = ymd.next()?.parse().ok()?;
+    let day: i64 = ymd.next()?.parse().ok()?;
+    Some(crate::util::days_from_civil(year, month, day) * 86_400 + parse_time(time.get(..8)?)?)
+}
+
+fn parse_time(value: &str) -> Option<i64> {
+    let mut hms = value.split(':');
+    let h: i64 = hms.next()?.parse().ok()?;
+    let m: i64 = hms.next()?.parse().ok()?;
+    let s: i64 = hms.next()?.parse().ok()?;
+    Some(h * 3600 + m * 60 + s)
+}
+
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0x2545_f491_4f6c_dd1d)
+            | 1,
+    );
+}
+
+/// A number in `[0, 1)` from a xorshift generator, good enough for jitter.
+fn random_fraction() -> f64 {
+    SEED.with(|seed| {
+        let mut x = seed.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        seed.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn idempotency_decides_what_is_retried() {
+        let policy = RetryPolicy::default();
+        assert!(policy.retries_status(&Method::GET, StatusCode::SERVICE_UNAVAILABLE));
+        assert!(policy.retries_status(&Method::POST, StatusCode::TOO_MANY_REQUESTS));
+        assert!(!policy.retries_status(&Method::POST, StatusCode::BAD_GATEWAY));
+        assert!(!policy.retries_status(&Method::GET, StatusCode::NOT_FOUND));
+        assert!(policy
+            .with_retry_non_idempotent(true)
+            .retries_status(&Method::POST, StatusCode::BAD_GATEWAY));
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::new(5)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(300))
+            .with_jitter(false);
+        let delays: Vec<_> = (1..=4)
+            .map(|r| policy.delay(r, None).unwrap().as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 300, 300]);
+
+        let jittered = policy.with_jitter(true).delay(2, None).unwrap();
+        assert!(jittered >= Duration::from_millis(100) && jittered <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn server_requested_delays() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_470);
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(server_delay(&headers, now), Some(Duration::from_secs(7)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(server_delay(&headers, now), Some(Duration::from_secs(10)));
+
+        headers.remove(RETRY_AFTER);
+        headers.insert(
+            "x-ratelimit-reset",
+            HeaderValue::from_static("2015-10-21T07:28:30.000Z"),
+        );
+        assert_eq!(server_delay(&headers, now), Some(Duration::from_secs(40)));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3600"));
+        assert_eq!(RetryPolicy::default().delay(1, Some(&headers)), None);
+    }
+}
@@ -0,0 +1,933 @@
+//! Builders for the structured macros most pages use.
+//!
+//! Every builder turns into a [`Macro`] with `build()` and can be pushed into
+//! an [`Element`] or written out with `to_string()`:
+//!
+//! ```
+//! use confluence::model::{Representation, Storage};
+//! use confluence::storage::builders::CodeMacro;
+//!
+//! let code = CodeMacro::new("fn main() {}").language("rust").line_numbers(true);
+//! let storage = Storage::new(&code.to_string(), Representation::Storage);
+//! assert!(storage.value.contains("<![CDATA[fn main() {}]]>"));
+//! ```
+
+use std::fmt;
+
+use super::{Element, Macro, Node, Resource, ResourceIdentifier};
+
+/// Implements `Display` and the conversions into a node for a builder with a
+/// `build(&self) -> Macro` function.
+macro_rules! macro_builder {
+    ($($i: ident)*) => (
+        $(
+        impl fmt::Display for $i {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.build())
+            }
+        }
+
+        impl From<$i> for Macro {
+            fn from(b: $i) -> Self {
+                b.build()
+            }
+        }
+
+        impl From<$i> for Node {
+            fn from(b: $i) -> Self {
+                Node::Macro(b.build())
+            }
+        }
+        )*
+    );
+}
+
+macro_builder!(
+    CodeMacro
+    AdmonitionMacro
+    ExpandMacro
+    TocMacro
+    ChildrenMacro
+    StatusMacro
+    PanelMacro
+    JiraMacro
+    IncludeMacro
+    ExcerptMacro
+    AnchorMacro
+    AttachmentsMacro
+    DiagramMacro
+);
+
+fn set_opt(m: &mut Macro, name: &str, value: &Option<String>) {
+    if let Some(v) = value {
+        m.set_parameter(name, v);
+    }
+}
+
+fn set_flag(m: &mut Macro, name: &str, value: Option<bool>) {
+    if let Some(v) = value {
+        m.set_parameter(name, if v { "true" } else { "false" });
+    }
+}
+
+/// A paragraph holding `text`, for bodies given as plain text.
+fn paragraph(text: &str) -> Vec<Node> {
+    vec![Element::new("p").with_text(text).into()]
+}
+
+/// `ac:link` to a page, as used by the include and children macros.
+fn page_link(space_key: &Option<String>, title: &str) -> Node {
+    let page = Resource::from_identifier(&ResourceIdentifier::Page {
+        space_key: space_key.clone(),
+        content_title: title.to_string(),
+    });
+    Element::new("ac:link").with_child(page).into()
+}
+
+/// The `code` macro.
+#[derive(Clone, Debug, Default)]
+pub struct CodeMacro {
+    pub code: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub theme: Option<String>,
+    pub line_numbers: Option<bool>,
+    pub first_line: Option<u64>,
+    pub collapse: Option<bool>,
+}
+
+impl CodeMacro {
+    pub fn new(code: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn theme(mut self, theme: &str) -> Self {
+        self.theme = Some(theme.to_string());
+        self
+    }
+
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = Some(line_numbers);
+        self
+    }
+
+    pub fn first_line(mut self, first_line: u64) -> Self {
+        self.first_line = Some(first_line);
+        self
+    }
+
+    pub fn collapse(mut self, collapse: bool) -> Self {
+        self.collapse = Some(collapse);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("code");
+        set_opt(&mut m, "language", &self.language);
+        set_opt(&mut m, "title", &self.title);
+        set_opt(&mut m, "theme", &self.theme);
+        set_flag(&mut m, "linenumbers", self.line_numbers);
+        set_opt(&mut m, "firstline", &self.first_line.map(|n| n.to_string()));
+        set_flag(&mut m, "collapse", self.collapse);
+        m.set_plain_body(&self.code);
+        m
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdmonitionKind {
+    Info,
+    Note,
+    Warning,
+    Tip,
+}
+
+impl AdmonitionKind {
+    pub fn macro_name(self) -> &'static str {
+        match self {
+            AdmonitionKind::Info => "info",
+            AdmonitionKind::Note => "note",
+            AdmonitionKind::Warning => "warning",
+            AdmonitionKind::Tip => "tip",
+        }
+    }
+}
+
+/// The `info`, `note`, `warning` and `tip` macros.
+#[derive(Clone, Debug)]
+pub struct AdmonitionMacro {
+    pub kind: AdmonitionKind,
+    pub title: Option<String>,
+    pub icon: Option<bool>,
+    pub body: Vec<Node>,
+}
+
+impl AdmonitionMacro {
+    pub fn new(kind: AdmonitionKind, body: Vec<Node>) -> Self {
+        Self {
+            kind,
+            title: None,
+            icon: None,
+            body,
+        }
+    }
+
+    pub fn info(text: &str) -> Self {
+        Self::new(AdmonitionKind::Info, paragraph(text))
+    }
+
+    pub fn note(text: &str) -> Self {
+        Self::new(AdmonitionKind::Note, paragraph(text))
+    }
+
+    pub fn warning(text: &str) -> Self {
+        Self::new(AdmonitionKind::Warning, paragraph(text))
+    }
+
+    pub fn tip(text: &str) -> Self {
+        Self::new(AdmonitionKind::Tip, paragraph(text))
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn icon(mut self, icon: bool) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named(self.kind.macro_name());
+        set_opt(&mut m, "title", &self.title);
+        set_flag(&mut m, "icon", self.icon);
+        m.set_rich_body(self.body.clone());
+        m
+    }
+}
+
+/// The `expand` macro.
+#[derive(Clone, Debug)]
+pub struct ExpandMacro {
+    pub title: Option<String>,
+    pub body: Vec<Node>,
+}
+
+impl ExpandMacro {
+    pub fn new(body: Vec<Node>) -> Self {
+        Self { title: None, body }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("expand");
+        set_opt(&mut m, "title", &self.title);
+        m.set_rich_body(self.body.clone());
+        m
+    }
+}
+
+/// The `toc` macro.
+#[derive(Clone, Debug, Default)]
+pub struct TocMacro {
+    pub min_level: Option<u8>,
+    pub max_level: Option<u8>,
+    /// `list` or `flat`.
+    pub outline_type: Option<String>,
+    /// Bullet style, e.g. `disc` or `none`.
+    pub style: Option<String>,
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+    pub printable: Option<bool>,
+}
+
+impl TocMacro {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn levels(mut self, min: u8, max: u8) -> Self {
+        self.min_level = Some(min);
+        self.max_level = Some(max);
+        self
+    }
+
+    pub fn outline_type(mut self, outline_type: &str) -> Self {
+        self.outline_type = Some(outline_type.to_string());
+        self
+    }
+
+    pub fn style(mut self, style: &str) -> Self {
+        self.style = Some(style.to_string());
+        self
+    }
+
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include = Some(pattern.to_string());
+        self
+    }
+
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude = Some(pattern.to_string());
+        self
+    }
+
+    pub fn printable(mut self, printable: bool) -> Self {
+        self.printable = Some(printable);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("toc");
+        set_opt(&mut m, "minLevel", &self.min_level.map(|l| l.to_string()));
+        set_opt(&mut m, "maxLevel", &self.max_level.map(|l| l.to_string()));
+        set_opt(&mut m, "type", &self.outline_type);
+        set_opt(&mut m, "style", &self.style);
+        set_opt(&mut m, "include", &self.include);
+        set_opt(&mut m, "exclude", &self.exclude);
+        set_flag(&mut m, "printable", self.printable);
+        m
+    }
+}
+
+/// The `children` macro listing child pages.
+#[derive(Clone, Debug, Default)]
+pub struct ChildrenMacro {
+    /// Parent page as space key and title; the current page if not set.
+    pub page: Option<(Option<String>, String)>,
+    pub all: Option<bool>,
+    pub depth: Option<u32>,
+    /// `title`, `creation` or `modified`.
+    pub sort: Option<String>,
+    pub reverse: Option<bool>,
+    /// Heading style, e.g. `h3`.
+    pub style: Option<String>,
+    pub excerpt: Option<bool>,
+}
+
+impl ChildrenMacro {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn page(mut self, space_key: Option<&str>, title: &str) -> Self {
+        self.page = Some((space_key.map(|s| s.to_string()), title.to_string()));
+        self
+    }
+
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = Some(all);
+        self
+    }
+
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn sort(mut self, sort: &str, reverse: bool) -> Self {
+        self.sort = Some(sort.to_string());
+        self.reverse = Some(reverse);
+        self
+    }
+
+    pub fn style(mut self, style: &str) -> Self {
+        self.style = Some(style.to_string());
+        self
+    }
+
+    pub fn excerpt(mut self, excerpt: bool) -> Self {
+        self.excerpt = Some(excerpt);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("children");
+        if let Some((space_key, title)) = &self.page {
+            m.set_parameter_nodes("page", vec![page_link(space_key, title)]);
+        }
+        set_flag(&mut m, "all", self.all);
+        set_opt(&mut m, "depth", &self.depth.map(|d| d.to_string()));
+        set_opt(&mut m, "sort", &self.sort);
+        set_flag(&mut m, "reverse", self.reverse);
+        set_opt(&mut m, "style", &self.style);
+        set_flag(&mut m, "excerpt", self.excerpt);
+        m
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusColour {
+    Grey,
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl StatusColour {
+    fn parameter(self) -> &'static str {
+        match self {
+            StatusColour::Grey => "Grey",
+            StatusColour::Red => "Red",
+            StatusColour::Yellow => "Yellow",
+            StatusColour::Green => "Green",
+            StatusColour::Blue => "Blue",
+            StatusColour::Purple => "Purple",
+        }
+    }
+}
+
+/// The `status` lozenge.
+#[derive(Clone, Debug)]
+pub struct StatusMacro {
+    pub title: String,
+    pub colour: StatusColour,
+    pub subtle: bool,
+}
+
+impl StatusMacro {
+    pub fn new(title: &str, colour: StatusColour) -> Self {
+        Self {
+            title: title.to_string(),
+            colour,
+            subtle: false,
+        }
+    }
+
+    pub fn subtle(mut self, subtle: bool) -> Self {
+        self.subtle = subtle;
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("status");
+        if self.colour != StatusColour::Grey {
+            m.set_parameter("colour", self.colour.parameter());
+        }
+        m.set_parameter("title", &self.title);
+        if self.subtle {
+            m.set_parameter("subtle", "true");
+        }
+        m
+    }
+}
+
+/// The `panel` macro.
+#[derive(Clone, Debug)]
+pub struct PanelMacro {
+    pub title: Option<String>,
+    /// `solid`, `dashed` or `none`.
+    pub border_style: Option<String>,
+    pub border_colour: Option<String>,
+    pub background_colour: Option<String>,
+    pub title_background_colour: Option<String>,
+    pub title_colour: Option<String>,
+    pub body: Vec<Node>,
+}
+
+impl PanelMacro {
+    pub fn new(body: Vec<Node>) -> Self {
+        Self {
+            title: None,
+            border_style: None,
+            border_colour: None,
+            background_colour: None,
+            title_background_colour: None,
+            title_colour: None,
+            body,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn border(mut self, style: &str, colour: &str) -> Self {
+        self.border_style = Some(style.to_string());
+        self.border_colour = Some(colour.to_string());
+        self
+    }
+
+    pub fn background_colour(mut self, colour: &str) -> Self {
+        self.background_colour = Some(colour.to_string());
+        self
+    }
+
+    pub fn title_colours(mut self, colour: &str, background: &str) -> Self {
+        self.title_colour = Some(colour.to_string());
+        self.title_background_colour = Some(background.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("panel");
+        set_opt(&mut m, "title", &self.title);
+        set_opt(&mut m, "borderStyle", &self.border_style);
+        set_opt(&mut m, "borderColor", &self.border_colour);
+        set_opt(&mut m, "bgColor", &self.background_colour);
+        set_opt(&mut m, "titleBGColor", &self.title_background_colour);
+        set_opt(&mut m, "titleColor", &self.title_colour);
+        m.set_rich_body(self.body.clone());
+        m
+    }
+}
+
+/// The `jira` macro, showing a single issue or the result of a JQL query.
+#[derive(Clone, Debug, Default)]
+pub struct JiraMacro {
+    pub key: Option<String>,
+    pub jql: Option<String>,
+    pub server: Option<String>,
+    pub server_id: Option<String>,
+    /// Comma separated list of columns for JQL results.
+    pub columns: Option<String>,
+    pub maximum_issues: Option<u32>,
+}
+
+impl JiraMacro {
+    pub fn issue(key: &str) -> Self {
+        Self {
+            key: Some(key.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn query(jql: &str) -> Self {
+        Self {
+            jql: Some(jql.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// The application link of the Jira instance, by name and id.
+    pub fn server(mut self, server: &str, server_id: &str) -> Self {
+        self.server = Some(server.to_string());
+        self.server_id = Some(server_id.to_string());
+        self
+    }
+
+    pub fn columns(mut self, columns: &str) -> Self {
+        self.columns = Some(columns.to_string());
+        self
+    }
+
+    pub fn maximum_issues(mut self, maximum_issues: u32) -> Self {
+        self.maximum_issues = Some(maximum_issues);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("jira");
+        set_opt(&mut m, "server", &self.server);
+        set_opt(&mut m, "serverId", &self.server_id);
+        set_opt(&mut m, "key", &self.key);
+        set_opt(&mut m, "jqlQuery", &self.jql);
+        set_opt(&mut m, "columns", &self.columns);
+        set_opt(
+            &mut m,
+            "maximumIssues",
+            &self.maximum_issues.map(|n| n.to_string()),
+        );
+        m
+    }
+}
+
+/// The `include` macro, embedding another page.
+#[derive(Clone, Debug)]
+pub struct IncludeMacro {
+    pub space_key: Option<String>,
+    pub title: String,
+}
+
+impl IncludeMacro {
+    pub fn new(space_key: Option<&str>, title: &str) -> Self {
+        Self {
+            space_key: space_key.map(|s| s.to_string()),
+            title: title.to_string(),
+        }
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("include");
+        m.set_parameter_nodes("", vec![page_link(&self.space_key, &self.title)]);
+        m
+    }
+}
+
+/// The `excerpt` macro.
+#[derive(Clone, Debug)]
+pub struct ExcerptMacro {
+    pub hidden: Option<bool>,
+    /// Render the excerpt inline (`INLINE`) or as a block (`BLOCK`).
+    pub output_type: Option<String>,
+    pub body: Vec<Node>,
+}
+
+impl ExcerptMacro {
+    pub fn new(body: Vec<Node>) -> Self {
+        Self {
+            hidden: None,
+            output_type: None,
+            body,
+        }
+    }
+
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = Some(hidden);
+        self
+    }
+
+    pub fn inline(mut self, inline: bool) -> Self {
+        self.output_type = Some(if inline { "INLINE" } else { "BLOCK" }.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("excerpt");
+        set_flag(&mut m, "hidden", self.hidden);
+        set_opt(&mut m, "atlassian-macro-output-type", &self.output_type);
+        m.set_rich_body(self.body.clone());
+        m
+    }
+}
+
+/// The `anchor` macro.
+#[derive(Clone, Debug)]
+pub struct AnchorMacro {
+    pub name: String,
+}
+
+impl AnchorMacro {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+
+    pub fn build(&self) -> Macro {
+        Macro::named("anchor").with_parameter("", &self.name)
+    }
+}
+
+/// The `attachments` macro listing the attachments of the page.
+#[derive(Clone, Debug, Default)]
+pub struct AttachmentsMacro {
+    /// Comma separated file name patterns, e.g. `.*\.pdf`.
+    pub patterns: Option<String>,
+    /// `date`, `size`, `name` or `createddate`.
+    pub sort_by: Option<String>,
+    pub sort_order: Option<String>,
+    pub upload: Option<bool>,
+    pub old: Option<bool>,
+    pub preview: Option<bool>,
+}
+
+impl AttachmentsMacro {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn patterns(mut self, patterns: &str) -> Self {
+        self.patterns = Some(patterns.to_string());
+        self
+    }
+
+    pub fn sort(mut self, sort_by: &str, ascending: bool) -> Self {
+        self.sort_by = Some(sort_by.to_string());
+        self.sort_order = Some(if ascending { "ascending" } else { "descending" }.to_string());
+        self
+    }
+
+    pub fn upload(mut self, upload: bool) -> Self {
+        self.upload = Some(upload);
+        self
+    }
+
+    pub fn old(mut self, old: bool) -> Self {
+        self.old = Some(old);
+        self
+    }
+
+    pub fn preview(mut self, preview: bool) -> Self {
+        self.preview = Some(preview);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let mut m = Macro::named("attachments");
+        set_opt(&mut m, "patterns", &self.patterns);
+        set_opt(&mut m, "sortBy", &self.sort_by);
+        set_opt(&mut m, "sortOrder", &self.sort_order);
+        set_flag(&mut m, "upload", self.upload);
+        set_flag(&mut m, "old", self.old);
+        set_flag(&mut m, "preview", self.preview);
+        m
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagramKind {
+    Drawio,
+    Gliffy,
+}
+
+/// A draw.io or Gliffy diagram. The diagram itself is an attachment of the
+/// page called `name`; the macro renders it once that attachment exists.
+#[derive(Clone, Debug)]
+pub struct DiagramMacro {
+    pub kind: DiagramKind,
+    pub name: String,
+    pub width: Option<u32>,
+    pub revision: Option<u64>,
+}
+
+impl DiagramMacro {
+    pub fn drawio(name: &str) -> Self {
+        Self {
+            kind: DiagramKind::Drawio,
+            name: name.to_string(),
+            width: None,
+            revision: None,
+        }
+    }
+
+    pub fn gliffy(name: &str) -> Self {
+        Self {
+            kind: DiagramKind::Gliffy,
+            ..Self::drawio(name)
+        }
+    }
+
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn revision(mut self, revision: u64) -> Self {
+        self.revision = Some(revision);
+        self
+    }
+
+    pub fn build(&self) -> Macro {
+        let width = self.width.map(|w| w.to_string());
+        let revision = self.revision.map(|r| r.to_string());
+        match self.kind {
+            DiagramKind::Drawio => {
+                let mut m = Macro::named("drawio").with_parameter("diagramName", &self.name);
+                m.set_parameter("simple", "0");
+                set_opt(&mut m, "width", &width);
+                set_opt(&mut m, "revision", &revision);
+                m
+            }
+            DiagramKind::Gliffy => {
+                let mut m = Macro::named("gliffy").with_parameter("name", &self.name);
+                m.set_parameter("displayName", &self.name);
+                set_opt(&mut m, "width", &width);
+                set_opt(&mut m, "version", &revision);
+                m
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{parse, MacroBody};
+
+    #[test]
+    fn code_macro_escapes_body_and_parameters() {
+        let code = CodeMacro::new("if a < b { \"]]>\" }")
+            .language("rust")
+            .title("A & B")
+            .line_numbers(true);
+        assert_eq!(
+            code.to_string(),
+            "<ac:structured-macro ac:name=\"code\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"language\">rust</ac:parameter>\
+             <ac:parameter ac:name=\"title\">A &amp; B</ac:parameter>\
+             <ac:parameter ac:name=\"linenumbers\">true</ac:parameter>\
+             <ac:plain-text-body><![CDATA[if a < b { \"]]]]><![CDATA[>\" }]]></ac:plain-text-body>\
+             </ac:structured-macro>"
+        );
+        let doc = parse(&code.to_string()).unwrap();
+        assert_eq!(
+            doc.macros()[0].body(),
+            Some(MacroBody::Plain("if a < b { \"]]>\" }".to_string()))
+        );
+    }
+
+    #[test]
+    fn rich_body_macros() {
+        let info = AdmonitionMacro::warning("Don't <panic>").title("Careful");
+        assert_eq!(
+            info.to_string(),
+            "<ac:structured-macro ac:name=\"warning\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"title\">Careful</ac:parameter>\
+             <ac:rich-text-body><p>Don't &lt;panic&gt;</p></ac:rich-text-body>\
+             </ac:structured-macro>"
+        );
+        let expand = ExpandMacro::new(vec![info.into()]).title("More");
+        assert_eq!(parse(&expand.to_string()).unwrap().macros().len(), 2);
+    }
+
+    #[test]
+    fn link_parameters() {
+        assert_eq!(
+            IncludeMacro::new(Some("ICF"), "Shared \"Footer\"").to_string(),
+            "<ac:structured-macro ac:name=\"include\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"\"><ac:link><ri:page ri:space-key=\"ICF\" \
+             ri:content-title=\"Shared &quot;Footer&quot;\" /></ac:link></ac:parameter>\
+             </ac:structured-macro>"
+        );
+        assert_eq!(
+            StatusMacro::new("DONE", StatusColour::Green).to_string(),
+            "<ac:structured-macro ac:name=\"status\" ac:schema-version=\"1\">\
+             <ac:parameter ac:name=\"colour\">Green</ac:parameter>\
+             <ac:parameter ac:name=\"title\">DONE</ac:parameter></ac:structured-macro>"
+        );
+    }
+
+    /// Parses `storage`, checks it writes back unchanged and returns its
+    /// first macro.
+    fn round_trip(storage: String) -> Macro {
+        let doc = parse(&storage).unwrap();
+        assert_eq!(doc.to_string(), storage);
+        doc.macros()[0].clone()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn navigation_macros_round_trip() {
+        let toc = round_trip(
+            TocMacro::new()
+                .levels(2, 3)
+                .outline_type("flat")
+                .to_string(),
+        );
+        assert_eq!(toc.name().as_deref(), Some("toc"));
+        assert_eq!(
+            toc.parameters(),
+            pairs(&[("minLevel", "2"), ("maxLevel", "3"), ("type", "flat")])
+        );
+
+        let children = ChildrenMacro::new()
+            .page(Some("DOC"), "Guides")
+            .depth(2)
+            .sort("title", true);
+        let children = round_trip(children.to_string());
+        let page = children.parameter_element("page").unwrap();
+        assert_eq!(
+            page.child("ac:link")
+                .unwrap()
+                .child("ri:page")
+                .unwrap()
+                .attribute("ri:content-title"),
+            Some("Guides".to_string())
+        );
+        assert_eq!(children.parameter("depth").as_deref(), Some("2"));
+        assert_eq!(children.parameter("reverse").as_deref(), Some("true"));
+
+        let anchor = round_trip(AnchorMacro::new("setup").to_string());
+        assert_eq!(anchor.parameters(), pairs(&[("", "setup")]));
+
+        let attachments = AttachmentsMacro::new()
+            .patterns(".*\\.pdf")
+            .sort("name", false)
+            .upload(false);
+        assert_eq!(
+            round_trip(attachments.to_string()).parameters(),
+            pairs(&[
+                ("patterns", ".*\\.pdf"),
+                ("sortBy", "name"),
+                ("sortOrder", "descending"),
+                ("upload", "false"),
+            ])
+        );
+    }
+
+    #[test]
+    fn content_macros_round_trip() {
+        let panel = PanelMacro::new(paragraph("Inside"))
+            .title("Notes")
+            .border("dashed", "#ccc");
+        let panel = round_trip(panel.to_string());
+        assert_eq!(
+            panel.parameters(),
+            pairs(&[
+                ("title", "Notes"),
+                ("borderStyle", "dashed"),
+                ("borderColor", "#ccc")
+            ])
+        );
+        assert!(matches!(panel.body(), Some(MacroBody::Rich(nodes)) if nodes.len() == 1));
+
+        let jira = JiraMacro::query("project = DOC")
+            .server("Jira", "144880e9")
+            .maximum_issues(20);
+        assert_eq!(
+            round_trip(jira.to_string()).parameters(),
+            pairs(&[
+                ("server", "Jira"),
+                ("serverId", "144880e9"),
+                ("jqlQuery", "project = DOC"),
+                ("maximumIssues", "20"),
+            ])
+        );
+
+        let excerpt = round_trip(
+            ExcerptMacro::new(paragraph("Summary"))
+                .hidden(true)
+                .inline(true)
+                .to_string(),
+        );
+        assert_eq!(
+            excerpt.parameters(),
+            pairs(&[
+                ("hidden", "true"),
+                ("atlassian-macro-output-type", "INLINE")
+            ])
+        );
+        assert!(matches!(excerpt.body(), Some(MacroBody::Rich(_))));
+
+        let drawio = round_trip(DiagramMacro::drawio("flow").width(600).to_string());
+        assert_eq!(drawio.name().as_deref(), Some("drawio"));
+        assert_eq!(
+            drawio.parameters(),
+            pairs(&[("diagramName", "flow"), ("simple", "0"), ("width", "600")])
+        );
+        let gliffy = round_trip(DiagramMacro::gliffy("flow").revision(3).to_string());
+        assert_eq!(gliffy.name().as_deref(), Some("gliffy"));
+        assert_eq!(
+            gliffy.parameters(),
+            pairs(&[("name", "flow"), ("displayName", "flow"), ("version", "3")])
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Confluence storage format.
+//!
+//! The storage format is the XHTML based representation Confluence keeps page
+//! bodies in (`representation: "storage"`). [`parse`] turns a body into a
+//! [`Document`] whose `Display` implementation writes it back; a body that was
+//! parsed and not modified is written back byte for byte.
+//!
+//! ```
+//! let doc = confluence::storage::parse(
+//!     r#"<p>See <ac:link><ri:page ri:content-title="Home" /></ac:link></p>"#,
+//! )
+//! .unwrap();
+//! assert_eq!(doc.resources().len(), 1);
+//! ```
+
+pub mod builders;
+pub mod model;
+mod parser;
+
+pub use model::*;
+
+use crate::client::Result;
+
+/// Parses a storage format body.
+pub fn parse(body: &str) -> Result<Document> {
+    parser::Parser::new(body).parse()
+}
+
+/// CDATA sections holding `value`. A `]]>` inside the value is split across
+/// two sections since it can't be escaped.
+pub fn cdata(value: &str) -> Vec<Node> {
+    let parts: Vec<&str> = value.split("]]>").collect();
+    let last = parts.len() - 1;
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let mut data = String::new();
+            if i > 0 {
+                data.push('>');
+            }
+            data.push_str(part);
+            if i < last {
+                data.push_str("]]");
+            }
+            Node::CData(data)
+        })
+        .collect()
+}
+
+/// Escapes text content. Quotes are left alone as Confluence does.
+pub fn escape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes a double quoted attribute value.
+pub fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+/// Expands the XML entities plus the HTML named entities Confluence emits.
+/// Unknown entities are left untouched.
+pub fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest
+            .find(';')
+            .filter(|&end| end <= 12)
+            .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(num) = entity.strip_prefix('#') {
+        let code = match num.strip_prefix('x').or_else(|| num.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return std::char::from_u32(code);
+    }
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "hellip" => '\u{2026}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "bull" => '\u{2022}',
+        "middot" => '\u{b7}',
+        "copy" => '\u{a9}',
+        "reg" => '\u{ae}',
+        "trade" => '\u{2122}',
+        "euro" => '\u{20ac}',
+        "rarr" => '\u{2192}',
+        "larr" => '\u{2190}',
+        "times" => '\u{d7}',
+        "auml" => '\u{e4}',
+        "ouml" => '\u{f6}',
+        "uuml" => '\u{fc}',
+        "Auml" => '\u{c4}',
+        "Ouml" => '\u{d6}',
+        "Uuml" => '\u{dc}',
+        "szlig" => '\u{df}',
+        _ => return None,
+    };
+    Some(c)
+}
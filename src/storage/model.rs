@@ -0,0 +1,816 @@
+use std::fmt;
+
+use super::{escape_attribute, escape_text, unescape};
+
+/// Element names that may appear without a closing tag or a trailing slash.
+/// Confluence always writes them self closed (`<br />`), but hand written
+/// bodies sometimes don't.
+pub(crate) const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+    pub nodes: Vec<Node>,
+}
+
+impl Document {
+    pub fn new(nodes: Vec<Node>) -> Self {
+        Self { nodes }
+    }
+
+    /// Every node of the document in document order, depth first.
+    pub fn descendants(&self) -> Vec<&Node> {
+        let mut out = Vec::new();
+        for node in &self.nodes {
+            node.collect(&mut out);
+        }
+        out
+    }
+
+    /// All structured macros of the document, including nested ones.
+    pub fn macros(&self) -> Vec<&Macro> {
+        self.descendants()
+            .into_iter()
+            .filter_map(|n| match n {
+                Node::Macro(m) => Some(m),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// All resource identifiers (`ri:*`) of the document.
+    pub fn resources(&self) -> Vec<&Resource> {
+        self.descendants()
+            .into_iter()
+            .filter_map(|n| match n {
+                Node::Resource(r) => Some(r),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            write!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+/// A node of a storage format document. Elements Confluence gives a special
+/// meaning are parsed into their own variant; everything else is kept as a
+/// plain `Element`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    /// Character data exactly as found in the source, entities unexpanded.
+    Text(String),
+    CData(String),
+    Comment(String),
+    /// `<!DOCTYPE ...>` or `<?...?>`, kept verbatim.
+    Declaration(String),
+    Element(Element),
+    Macro(Macro),
+    Link(Link),
+    Image(Image),
+    Resource(Resource),
+    Layout(Layout),
+    LayoutSection(LayoutSection),
+    LayoutCell(LayoutCell),
+    TaskList(TaskList),
+    Task(Task),
+}
+
+impl Node {
+    /// Creates a text node, escaping `value`.
+    pub fn text(value: &str) -> Self {
+        Node::Text(escape_text(value))
+    }
+
+    /// Wraps an element into the matching typed variant.
+    pub fn from_element(element: Element) -> Self {
+        match element.name.as_str() {
+            "ac:structured-macro" | "ac:macro" => Node::Macro(Macro { element }),
+            "ac:link" => Node::Link(Link { element }),
+            "ac:image" => Node::Image(Image { element }),
+            "ac:layout" => Node::Layout(Layout { element }),
+            "ac:layout-section" => Node::LayoutSection(LayoutSection { element }),
+            "ac:layout-cell" => Node::LayoutCell(LayoutCell { element }),
+            "ac:task-list" => Node::TaskList(TaskList { element }),
+            "ac:task" => Node::Task(Task { element }),
+            name if name.starts_with("ri:") => Node::Resource(Resource { element }),
+            _ => Node::Element(element),
+        }
+    }
+
+    pub fn as_element(&self) -> Option<&Element> {
+        match self {
+            Node::Element(e) => Some(e),
+            Node::Macro(n) => Some(&n.element),
+            Node::Link(n) => Some(&n.element),
+            Node::Image(n) => Some(&n.element),
+            Node::Resource(n) => Some(&n.element),
+            Node::Layout(n) => Some(&n.element),
+            Node::LayoutSection(n) => Some(&n.element),
+            Node::LayoutCell(n) => Some(&n.element),
+            Node::TaskList(n) => Some(&n.element),
+            Node::Task(n) => Some(&n.element),
+            _ => None,
+        }
+    }
+
+    pub fn as_element_mut(&mut self) -> Option<&mut Element> {
+        match self {
+            Node::Element(e) => Some(e),
+            Node::Macro(n) => Some(&mut n.element),
+            Node::Link(n) => Some(&mut n.element),
+            Node::Image(n) => Some(&mut n.element),
+            Node::Resource(n) => Some(&mut n.element),
+            Node::Layout(n) => Some(&mut n.element),
+            Node::LayoutSection(n) => Some(&mut n.element),
+            Node::LayoutCell(n) => Some(&mut n.element),
+            Node::TaskList(n) => Some(&mut n.element),
+            Node::Task(n) => Some(&mut n.element),
+            _ => None,
+        }
+    }
+
+    /// The unescaped text content of this node and its descendants.
+    pub fn text_content(&self) -> String {
+        match self {
+            Node::Text(raw) => unescape(raw),
+            Node::CData(value) => value.clone(),
+            Node::Comment(_) | Node::Declaration(_) => String::new(),
+            _ => self.as_element().map(|e| e.text()).unwrap_or_default(),
+        }
+    }
+
+    fn collect<'a>(&'a self, out: &mut Vec<&'a Node>) {
+        out.push(self);
+        if let Some(e) = self.as_element() {
+            for child in &e.children {
+                child.collect(out);
+            }
+        }
+    }
+}
+
+impl From<Element> for Node {
+    fn from(element: Element) -> Self {
+        Node::from_element(element)
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Text(raw) => write!(f, "{}", raw),
+            Node::CData(value) => write!(f, "<![CDATA[{}]]>", value),
+            Node::Comment(value) => write!(f, "<!--{}-->", value),
+            Node::Declaration(raw) => write!(f, "{}", raw),
+            _ => write!(f, "{}", self.as_element().unwrap()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    /// The value as written in the source, entities unexpanded.
+    pub raw: String,
+    pub(crate) quote: char,
+    pub(crate) leading: String,
+}
+
+impl Attribute {
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            raw: escape_attribute(value),
+            quote: '"',
+            leading: " ".to_string(),
+        }
+    }
+
+    pub fn value(&self) -> String {
+        unescape(&self.raw)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TagEnd {
+    /// `<p>...</p>`
+    Explicit,
+    /// `<br/>` or `<br />`
+    SelfClosing,
+    /// `<br>`
+    Void,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Node>,
+    pub(crate) closing: TagEnd,
+    /// Whitespace between the last attribute and `>` or `/>`.
+    pub(crate) trailing: String,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        let closing = if VOID_ELEMENTS.contains(&name) {
+            TagEnd::SelfClosing
+        } else {
+            TagEnd::Explicit
+        };
+        let trailing = if closing == TagEnd::SelfClosing {
+            " ".to_string()
+        } else {
+            String::new()
+        };
+        Self {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            closing,
+            trailing,
+        }
+    }
+
+    /// An element written as `<name ... />` when it has no children.
+    pub fn empty(name: &str) -> Self {
+        Self {
+            closing: TagEnd::SelfClosing,
+            trailing: " ".to_string(),
+            ..Self::new(name)
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &str, value: &str) -> Self {
+        self.set_attribute(name, value);
+        self
+    }
+
+    pub fn with_child(mut self, child: impl Into<Node>) -> Self {
+        self.push(child);
+        self
+    }
+
+    pub fn with_text(self, text: &str) -> Self {
+        self.with_child(Node::text(text))
+    }
+
+    pub fn push(&mut self, child: impl Into<Node>) {
+        if self.closing != TagEnd::Explicit {
+            self.closing = TagEnd::Explicit;
+            self.trailing.clear();
+        }
+        self.children.push(child.into());
+    }
+
+    /// Namespace prefix, e.g. `ac` for `ac:link`.
+    pub fn prefix(&self) -> Option<&str> {
+        self.name.find(':').map(|i| &self.name[..i])
+    }
+
+    /// Name without namespace prefix, e.g. `link` for `ac:link`.
+    pub fn local_name(&self) -> &str {
+        match self.name.find(':') {
+            Some(i) => &self.name[i + 1..],
+            None => &self.name,
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.value())
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        match self.attributes.iter_mut().find(|a| a.name == name) {
+            Some(a) => a.raw = escape_attribute(value),
+            None => self.attributes.push(Attribute::new(name, value)),
+        }
+    }
+
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        let pos = self.attributes.iter().position(|a| a.name == name)?;
+        Some(self.attributes.remove(pos).value())
+    }
+
+    /// Direct child elements, regardless of their typed variant.
+    pub fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|c| c.as_element())
+    }
+
+    /// First direct child element called `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.child_elements().find(|e| e.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children
+            .iter_mut()
+            .filter_map(|c| c.as_element_mut())
+            .find(|e| e.name == name)
+    }
+
+    /// The unescaped text content of all descendants.
+    pub fn text(&self) -> String {
+        self.children.iter().map(|c| c.text_content()).collect()
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}", self.name)?;
+        for a in &self.attributes {
+            write!(f, "{}{}={}{}{}", a.leading, a.name, a.quote, a.raw, a.quote)?;
+        }
+        write!(f, "{}", self.trailing)?;
+        match self.closing {
+            TagEnd::SelfClosing => write!(f, "/>"),
+            TagEnd::Void => write!(f, ">"),
+            TagEnd::Explicit => {
+                write!(f, ">")?;
+                for child in &self.children {
+                    write!(f, "{}", child)?;
+                }
+                write!(f, "</{}>", self.name)
+            }
+        }
+    }
+}
+
+/// Generates a typed wrapper around an `Element` for the storage format
+/// elements Confluence gives a special meaning. Wrappers given an element
+/// name also get a constructor for an empty element of that name.
+macro_rules! typed_element {
+    ($($(#[$m: meta])* $i: ident $(= $name: expr)?)*) => (
+        $(
+        $(#[$m])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $i {
+            element: Element,
+        }
+
+        impl $i {
+            pub fn as_element(&self) -> &Element {
+                &self.element
+            }
+
+            pub fn as_element_mut(&mut self) -> &mut Element {
+                &mut self.element
+            }
+
+            pub fn into_element(self) -> Element {
+                self.element
+            }
+        }
+
+        $(
+        impl $i {
+            pub fn new() -> Self {
+                Self { element: Element::new($name) }
+            }
+        }
+
+        impl Default for $i {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+        )?
+
+        impl From<$i> for Node {
+            fn from(n: $i) -> Self {
+                Node::$i(n)
+            }
+        }
+
+        impl fmt::Display for $i {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.element)
+            }
+        }
+        )*
+    );
+}
+
+typed_element!(
+    /// `<ac:structured-macro>`
+    Macro
+    /// `<ac:link>`
+    Link = "ac:link"
+    /// `<ac:image>`
+    Image = "ac:image"
+    /// Any `<ri:*>` resource identifier.
+    Resource
+    /// `<ac:layout>`
+    Layout = "ac:layout"
+    /// `<ac:layout-section>`
+    LayoutSection = "ac:layout-section"
+    /// `<ac:layout-cell>`
+    LayoutCell = "ac:layout-cell"
+    /// `<ac:task-list>`
+    TaskList = "ac:task-list"
+    /// `<ac:task>`
+    Task = "ac:task"
+);
+
+/// Typed children of a wrapped element, e.g. the sections of a layout.
+macro_rules! typed_children {
+    ($e: expr, $v: ident) => {
+        $e.children.iter().filter_map(|c| match c {
+            Node::$v(n) => Some(n),
+            _ => None,
+        })
+    };
+}
+
+/// The body of a macro.
+#[derive(Debug, PartialEq)]
+pub enum MacroBody<'a> {
+    /// `<ac:rich-text-body>`, holding storage format.
+    Rich(&'a [Node]),
+    /// `<ac:plain-text-body>`, usually a CDATA section.
+    Plain(String),
+}
+
+impl Macro {
+    pub fn named(name: &str) -> Self {
+        Self {
+            element: Element::new("ac:structured-macro")
+                .with_attribute("ac:name", name)
+                .with_attribute("ac:schema-version", "1"),
+        }
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.element.attribute("ac:name")
+    }
+
+    pub fn macro_id(&self) -> Option<String> {
+        self.element.attribute("ac:macro-id")
+    }
+
+    /// All `ac:parameter` values by name. The unnamed default parameter
+    /// has an empty name.
+    pub fn parameters(&self) -> Vec<(String, String)> {
+        self.element
+            .child_elements()
+            .filter(|e| e.name == "ac:parameter")
+            .map(|e| (e.attribute("ac:name").unwrap_or_default(), e.text()))
+            .collect()
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        self.parameter_element(name).map(|e| e.text())
+    }
+
+    /// The `ac:parameter` element called `name`, for parameters holding
+    /// markup such as `ri:user`.
+    pub fn parameter_element(&self, name: &str) -> Option<&Element> {
+        self.element.child_elements().find(|e| {
+            e.name == "ac:parameter" && e.attribute("ac:name").unwrap_or_default() == name
+        })
+    }
+
+    /// Sets a parameter to a text value. New parameters are inserted
+    /// after the existing ones so they come before the macro body.
+    pub fn set_parameter(&mut self, name: &str, value: &str) {
+        self.set_parameter_nodes(name, vec![Node::text(value)]);
+    }
+
+    /// Sets a parameter holding markup, e.g. an `ac:link` or `ri:user`.
+    pub fn set_parameter_nodes(&mut self, name: &str, value: Vec<Node>) {
+        let mut param = Element::new("ac:parameter").with_attribute("ac:name", name);
+        param.children = value;
+        let children = &mut self.element.children;
+        let existing = children.iter().position(|c| {
+            c.as_element()
+                .map(|e| {
+                    e.name == "ac:parameter" && e.attribute("ac:name").unwrap_or_default() == name
+                })
+                .unwrap_or(false)
+        });
+        match existing {
+            Some(i) => children[i] = param.into(),
+            None => {
+                let last = children
+                    .iter()
+                    .rposition(|c| c.as_element().map(|e| e.name == "ac:parameter") == Some(true))
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                children.insert(last, param.into());
+            }
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: &str) -> Self {
+        self.set_parameter(name, value);
+        self
+    }
+
+    pub fn remove_parameter(&mut self, name: &str) -> Option<String> {
+        let children = &mut self.element.children;
+        let pos = children.iter().position(|c| {
+            c.as_element()
+                .map(|e| {
+                    e.name == "ac:parameter" && e.attribute("ac:name").unwrap_or_default() == name
+                })
+                .unwrap_or(false)
+        })?;
+        Some(children.remove(pos).text_content())
+    }
+
+    pub fn body(&self) -> Option<MacroBody<'_>> {
+        self.element
+            .child_elements()
+            .find_map(|e| match e.name.as_str() {
+                "ac:rich-text-body" => Some(MacroBody::Rich(&e.children)),
+                "ac:plain-text-body" => Some(MacroBody::Plain(e.text())),
+                _ => None,
+            })
+    }
+
+    /// Replaces the body with an `ac:rich-text-body` holding `nodes`.
+    pub fn set_rich_body(&mut self, nodes: Vec<Node>) {
+        let mut body = Element::new("ac:rich-text-body");
+        body.children = nodes;
+        self.replace_body(body);
+    }
+
+    /// Replaces the body with an `ac:plain-text-body` holding `text` as CDATA.
+    pub fn set_plain_body(&mut self, text: &str) {
+        let mut body = Element::new("ac:plain-text-body");
+        body.children = super::cdata(text);
+        self.replace_body(body);
+    }
+
+    fn replace_body(&mut self, body: Element) {
+        self.element.children.retain(|c| {
+            c.as_element()
+                .map(|e| e.name != "ac:rich-text-body" && e.name != "ac:plain-text-body")
+                .unwrap_or(true)
+        });
+        self.element.push(body);
+    }
+}
+
+impl Link {
+    /// The target of the link; `None` for links to an anchor on the same page.
+    pub fn resource(&self) -> Option<&Resource> {
+        typed_children!(self.element, Resource).next()
+    }
+
+    pub fn anchor(&self) -> Option<String> {
+        self.element.attribute("ac:anchor")
+    }
+
+    /// The link text, from either `ac:plain-text-link-body` or `ac:link-body`.
+    pub fn text(&self) -> Option<String> {
+        self.element
+            .child("ac:plain-text-link-body")
+            .or_else(|| self.element.child("ac:link-body"))
+            .map(|e| e.text())
+    }
+}
+
+impl Image {
+    /// An attached or external image source.
+    pub fn resource(&self) -> Option<&Resource> {
+        typed_children!(self.element, Resource).next()
+    }
+
+    pub fn alt(&self) -> Option<String> {
+        self.element.attribute("ac:alt")
+    }
+
+    pub fn width(&self) -> Option<String> {
+        self.element.attribute("ac:width")
+    }
+
+    pub fn height(&self) -> Option<String> {
+        self.element.attribute("ac:height")
+    }
+}
+
+/// What a `ri:*` element refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResourceIdentifier {
+    Page {
+        space_key: Option<String>,
+        content_title: String,
+    },
+    BlogPost {
+        space_key: Option<String>,
+        content_title: String,
+        posting_day: Option<String>,
+    },
+    Attachment {
+        filename: String,
+        /// The page or blog post holding the attachment, if not the current one.
+        container: Option<Box<ResourceIdentifier>>,
+    },
+    Url {
+        value: String,
+    },
+    User {
+        user_key: Option<String>,
+        account_id: Option<String>,
+        username: Option<String>,
+    },
+    Space {
+        space_key: String,
+    },
+    Content {
+        content_id: String,
+    },
+    Shortcut {
+        key: String,
+        parameter: String,
+    },
+    Other {
+        name: String,
+    },
+}
+
+impl Resource {
+    pub fn from_identifier(identifier: &ResourceIdentifier) -> Self {
+        fn with_opt(e: Element, name: &str, value: &Option<String>) -> Element {
+            match value {
+                Some(v) => e.with_attribute(name, v),
+                None => e,
+            }
+        }
+        let element = match identifier {
+            ResourceIdentifier::Page {
+                space_key,
+                content_title,
+            } => with_opt(Element::empty("ri:page"), "ri:space-key", space_key)
+                .with_attribute("ri:content-title", content_title),
+            ResourceIdentifier::BlogPost {
+                space_key,
+                content_title,
+                posting_day,
+            } => with_opt(
+                with_opt(Element::empty("ri:blog-post"), "ri:space-key", space_key)
+                    .with_attribute("ri:content-title", content_title),
+                "ri:posting-day",
+                posting_day,
+            ),
+            ResourceIdentifier::Attachment {
+                filename,
+                container,
+            } => {
+                let e = Element::empty("ri:attachment").with_attribute("ri:filename", filename);
+                match container {
+                    Some(c) => e.with_child(Resource::from_identifier(c)),
+                    None => e,
+                }
+            }
+            ResourceIdentifier::Url { value } => {
+                Element::empty("ri:url").with_attribute("ri:value", value)
+            }
+            ResourceIdentifier::User {
+                user_key,
+                account_id,
+                username,
+            } => with_opt(
+                with_opt(
+                    with_opt(Element::empty("ri:user"), "ri:userkey", user_key),
+                    "ri:account-id",
+                    account_id,
+                ),
+                "ri:username",
+                username,
+            ),
+            ResourceIdentifier::Space { space_key } => {
+                Element::empty("ri:space").with_attribute("ri:space-key", space_key)
+            }
+            ResourceIdentifier::Content { content_id } => {
+                Element::empty("ri:content-entity").with_attribute("ri:content-id", content_id)
+            }
+            ResourceIdentifier::Shortcut { key, parameter } => Element::empty("ri:shortcut")
+                .with_attribute("ri:key", key)
+                .with_attribute("ri:parameter", parameter),
+            ResourceIdentifier::Other { name } => Element::empty(name),
+        };
+        Self { element }
+    }
+
+    pub fn identifier(&self) -> ResourceIdentifier {
+        let e = &self.element;
+        let attr = |name: &str| e.attribute(name);
+        match e.name.as_str() {
+            "ri:page" => ResourceIdentifier::Page {
+                space_key: attr("ri:space-key"),
+                content_title: attr("ri:content-title").unwrap_or_default(),
+            },
+            "ri:blog-post" => ResourceIdentifier::BlogPost {
+                space_key: attr("ri:space-key"),
+                content_title: attr("ri:content-title").unwrap_or_default(),
+                posting_day: attr("ri:posting-day"),
+            },
+            "ri:attachment" => ResourceIdentifier::Attachment {
+                filename: attr("ri:filename").unwrap_or_default(),
+                container: typed_children!(e, Resource)
+                    .next()
+                    .map(|r| Box::new(r.identifier())),
+            },
+            "ri:url" => ResourceIdentifier::Url {
+                value: attr("ri:value").unwrap_or_default(),
+            },
+            "ri:user" => ResourceIdentifier::User {
+                user_key: attr("ri:userkey"),
+                account_id: attr("ri:account-id"),
+                username: attr("ri:username"),
+            },
+            "ri:space" => ResourceIdentifier::Space {
+                space_key: attr("ri:space-key").unwrap_or_default(),
+            },
+            "ri:content-entity" => ResourceIdentifier::Content {
+                content_id: attr("ri:content-id").unwrap_or_default(),
+            },
+            "ri:shortcut" => ResourceIdentifier::Shortcut {
+                key: attr("ri:key").unwrap_or_default(),
+                parameter: attr("ri:parameter").unwrap_or_default(),
+            },
+            name => ResourceIdentifier::Other {
+                name: name.to_string(),
+            },
+        }
+    }
+}
+
+impl Layout {
+    pub fn sections(&self) -> impl Iterator<Item = &LayoutSection> {
+        typed_children!(self.element, LayoutSection)
+    }
+}
+
+impl LayoutSection {
+    /// The section type, e.g. `two_equal` or `three_with_sidebars`.
+    pub fn section_type(&self) -> Option<String> {
+        self.element.attribute("ac:type")
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = &LayoutCell> {
+        typed_children!(self.element, LayoutCell)
+    }
+}
+
+impl LayoutCell {
+    pub fn content(&self) -> &[Node] {
+        &self.element.children
+    }
+}
+
+impl TaskList {
+    pub fn tasks(&self) -> impl Iterator<Item = &Task> {
+        typed_children!(self.element, Task)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskStatus {
+    Complete,
+    Incomplete,
+}
+
+impl Task {
+    pub fn id(&self) -> Option<String> {
+        self.element.child("ac:task-id").map(|e| e.text())
+    }
+
+    pub fn status(&self) -> TaskStatus {
+        match self.element.child("ac:task-status").map(|e| e.text()) {
+            Some(s) if s.trim() == "complete" => TaskStatus::Complete,
+            _ => TaskStatus::Incomplete,
+        }
+    }
+
+    pub fn set_status(&mut self, status: TaskStatus) {
+        let value = match status {
+            TaskStatus::Complete => "complete",
+            TaskStatus::Incomplete => "incomplete",
+        };
+        match self.element.child_mut("ac:task-status") {
+            Some(e) => e.children = vec![Node::text(value)],
+            None => self
+                .element
+                .push(Element::new("ac:task-status").with_text(value)),
+        }
+    }
+
+    pub fn body(&self) -> Option<&Element> {
+        self.element.child("ac:task-body")
+    }
+}
@@ -0,0 +1,310 @@
+use super::model::{Attribute, Document, Element, Node, TagEnd, VOID_ELEMENTS};
+use crate::client::Result;
+
+/// A small hand written XHTML reader. It is deliberately lenient about
+/// entities (HTML entities such as `&nbsp;` are not valid XML but common in
+/// storage format) and keeps enough formatting detail to write a document
+/// back unchanged.
+pub(crate) struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    pub(crate) fn parse(mut self) -> Result<Document> {
+        let nodes = self.parse_nodes()?;
+        if self.pos < self.input.len() {
+            let end = self.read_end_tag()?;
+            return Err(self.error(&format!("unexpected closing tag </{}>", end)));
+        }
+        Ok(Document::new(nodes))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error(&self, msg: &str) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        format!("storage format: {} at offset {}", msg, self.pos).into()
+    }
+
+    /// Consumes everything up to and including `terminator`, returning the
+    /// part before it.
+    fn take_until(&mut self, terminator: &str, what: &str) -> Result<&'a str> {
+        match self.rest().find(terminator) {
+            Some(i) => {
+                let taken = &self.rest()[..i];
+                self.pos += i + terminator.len();
+                Ok(taken)
+            }
+            None => Err(self.error(&format!("unterminated {}", what))),
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find(|c: char| !f(c)).unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    /// Parses sibling nodes until the end of input or a closing tag, which
+    /// is left for the caller.
+    fn parse_nodes(&mut self) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        while self.pos < self.input.len() {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                break;
+            } else if rest.starts_with("<!--") {
+                self.pos += 4;
+                let comment = self.take_until("-->", "comment")?;
+                nodes.push(Node::Comment(comment.to_string()));
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                let data = self.take_until("]]>", "CDATA section")?;
+                nodes.push(Node::CData(data.to_string()));
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                let start = self.pos;
+                self.take_until(">", "declaration")?;
+                nodes.push(Node::Declaration(self.input[start..self.pos].to_string()));
+            } else if rest.starts_with('<') {
+                let element = self.parse_element()?;
+                nodes.push(Node::from_element(element));
+            } else {
+                let text = self.take_while(|c| c != '<');
+                nodes.push(Node::Text(text.to_string()));
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn parse_element(&mut self) -> Result<Element> {
+        // skip '<'
+        self.pos += 1;
+        let name = self.take_while(is_name_char);
+        if name.is_empty() {
+            return Err(self.error("expected element name"));
+        }
+        let mut element = Element::new(name);
+        element.closing = TagEnd::Explicit;
+        element.trailing = String::new();
+
+        loop {
+            let ws = self.take_while(char::is_whitespace);
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                element.trailing = ws.to_string();
+                element.closing = TagEnd::SelfClosing;
+                return Ok(element);
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                element.trailing = ws.to_string();
+                break;
+            } else if rest.is_empty() {
+                return Err(self.error(&format!("unterminated tag <{}", name)));
+            } else if ws.is_empty() && !element.attributes.is_empty() {
+                return Err(self.error("expected whitespace between attributes"));
+            }
+            let attribute = self.parse_attribute(ws)?;
+            element.attributes.push(attribute);
+        }
+
+        if VOID_ELEMENTS.contains(&name) && !self.closes(name) {
+            element.closing = TagEnd::Void;
+            return Ok(element);
+        }
+
+        element.children = self.parse_nodes()?;
+        if self.pos >= self.input.len() {
+            return Err(self.error(&format!("missing closing tag </{}>", name)));
+        }
+        let end = self.read_end_tag()?;
+        if end != name {
+            return Err(self.error(&format!(
+                "expected closing tag </{}> but found </{}>",
+                name, end
+            )));
+        }
+        Ok(element)
+    }
+
+    /// Whether the next tag is the closing tag for `name`, as in `<br></br>`.
+    fn closes(&self, name: &str) -> bool {
+        self.rest()
+            .strip_prefix("</")
+            .and_then(|r| r.strip_prefix(name))
+            .map(|r| r.trim_start().starts_with('>'))
+            .unwrap_or(false)
+    }
+
+    fn read_end_tag(&mut self) -> Result<&'a str> {
+        // skip '</'
+        self.pos += 2;
+        let name = self.take_while(is_name_char);
+        self.take_while(char::is_whitespace);
+        if !self.rest().starts_with('>') {
+            return Err(self.error(&format!("malformed closing tag </{}", name)));
+        }
+        self.pos += 1;
+        Ok(name)
+    }
+
+    fn parse_attribute(&mut self, leading: &str) -> Result<Attribute> {
+        let name = self.take_while(is_name_char);
+        if name.is_empty() {
+            return Err(self.error("expected attribute name"));
+        }
+        self.take_while(char::is_whitespace);
+        if !self.rest().starts_with('=') {
+            return Err(self.error(&format!("expected '=' after attribute {}", name)));
+        }
+        self.pos += 1;
+        self.take_while(char::is_whitespace);
+        let quote = match self.rest().chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => return Err(self.error(&format!("expected quoted value for {}", name))),
+        };
+        self.pos += 1;
+        let raw = self.take_until(&quote.to_string(), "attribute value")?;
+        Ok(Attribute {
+            name: name.to_string(),
+            raw: raw.to_string(),
+            quote,
+            leading: leading.to_string(),
+        })
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == ':' || c == '-' || c == '_' || c == '.'
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::*;
+
+    const PAGE: &str = r#"<ac:layout><ac:layout-section ac:type="two_equal"><ac:layout-cell><h1>Title &amp; more&nbsp;text</h1><p>See <ac:link ac:anchor="top"><ri:page ri:space-key="ICF" ri:content-title="My Fancy Page" /><ac:plain-text-link-body><![CDATA[the page]]></ac:plain-text-link-body></ac:link>.<br /></p><ac:structured-macro ac:name="code" ac:schema-version="1" ac:macro-id="4b1c"><ac:parameter ac:name="language">rust</ac:parameter><ac:plain-text-body><![CDATA[fn main() { println!("<hi>"); }]]></ac:plain-text-body></ac:structured-macro></ac:layout-cell><ac:layout-cell><ac:task-list>
+<ac:task>
+<ac:task-id>1</ac:task-id>
+<ac:task-status>complete</ac:task-status>
+<ac:task-body>ship it</ac:task-body>
+</ac:task>
+<ac:task>
+<ac:task-id>2</ac:task-id>
+<ac:task-status>incomplete</ac:task-status>
+<ac:task-body>write <strong>docs</strong></ac:task-body>
+</ac:task>
+</ac:task-list><p><ac:image ac:height="250"><ri:attachment ri:filename="sequence.png"><ri:page ri:content-title="Other" /></ri:attachment></ac:image></p><!-- note --></ac:layout-cell></ac:layout-section></ac:layout>"#;
+
+    #[test]
+    fn round_trip_is_lossless() {
+        let doc = parse(PAGE).unwrap();
+        assert_eq!(doc.to_string(), PAGE);
+
+        let odd = "<p class='x'  id=\"a\" >a<br>b<hr/><img src=\"x\"></p><?xml version=\"1.0\"?>";
+        assert_eq!(parse(odd).unwrap().to_string(), odd);
+    }
+
+    #[test]
+    fn typed_nodes() {
+        let doc = parse(PAGE).unwrap();
+
+        let layout = match &doc.nodes[0] {
+            Node::Layout(l) => l,
+            n => panic!("expected layout, got {:?}", n),
+        };
+        let section = layout.sections().next().unwrap();
+        assert_eq!(section.section_type().as_deref(), Some("two_equal"));
+        assert_eq!(section.cells().count(), 2);
+
+        let macros = doc.macros();
+        assert_eq!(macros.len(), 1);
+        assert_eq!(macros[0].name().as_deref(), Some("code"));
+        assert_eq!(macros[0].parameter("language").as_deref(), Some("rust"));
+        assert_eq!(
+            macros[0].body(),
+            Some(MacroBody::Plain(
+                r#"fn main() { println!("<hi>"); }"#.to_string()
+            ))
+        );
+
+        let link = doc
+            .descendants()
+            .into_iter()
+            .find_map(|n| match n {
+                Node::Link(l) => Some(l),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(link.text().as_deref(), Some("the page"));
+        assert_eq!(link.anchor().as_deref(), Some("top"));
+        assert_eq!(
+            link.resource().unwrap().identifier(),
+            ResourceIdentifier::Page {
+                space_key: Some("ICF".to_string()),
+                content_title: "My Fancy Page".to_string(),
+            }
+        );
+
+        let tasks: Vec<_> = doc
+            .descendants()
+            .into_iter()
+            .filter_map(|n| match n {
+                Node::Task(t) => Some(t),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].status(), TaskStatus::Complete);
+        assert_eq!(tasks[1].body().unwrap().text(), "write docs");
+
+        let attachment = doc
+            .resources()
+            .into_iter()
+            .find(|r| r.as_element().name == "ri:attachment")
+            .unwrap();
+        assert_eq!(
+            attachment.identifier(),
+            ResourceIdentifier::Attachment {
+                filename: "sequence.png".to_string(),
+                container: Some(Box::new(ResourceIdentifier::Page {
+                    space_key: None,
+                    content_title: "Other".to_string(),
+                })),
+            }
+        );
+
+        let h1 = doc.descendants().into_iter().find_map(|n| match n {
+            Node::Element(e) if e.name == "h1" => Some(e),
+            _ => None,
+        });
+        assert_eq!(h1.unwrap().text(), "Title & more\u{a0}text");
+    }
+
+    #[test]
+    fn edits_are_escaped() {
+        let mut doc = parse(r#"<ac:structured-macro ac:name="info"><ac:rich-text-body><p>x</p></ac:rich-text-body></ac:structured-macro>"#).unwrap();
+        if let Node::Macro(m) = &mut doc.nodes[0] {
+            m.set_parameter("title", "A & B");
+        }
+        assert_eq!(
+            doc.to_string(),
+            r#"<ac:structured-macro ac:name="info"><ac:parameter ac:name="title">A &amp; B</ac:parameter><ac:rich-text-body><p>x</p></ac:rich-text-body></ac:structured-macro>"#
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(parse("<p><strong>x</p>").is_err());
+        assert!(parse("<p>x").is_err());
+        assert!(parse("x</p>").is_err());
+        assert!(parse("<p a=b>x</p>").is_err());
+        assert!(parse("<![CDATA[x").is_err());
+    }
+}
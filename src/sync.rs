@@ -0,0 +1,654 @@
+//! Publishing a directory of Markdown documents as a page tree.
+//!
+//! Every Markdown file becomes a page under the root page, and every
+//! directory a page whose children are the directory's documents. A
+//! directory's `index.md` or `README.md`, if any, is the body of its page.
+//! Titles are the documents' first level one heading, else their file name.
+//!
+//! Links between documents become page links, and local images and files
+//! are uploaded as attachments of the pages referring to them. A hash of
+//! each page's content is stored in its `docs-sync` property, so unchanged
+//! pages aren't updated again, and pages whose document was removed are
+//! found among the root page's descendants.
+//!
+//! ```no_run
+//! use confluence::client::Confluence;
+//! use confluence::content::model::Ancestor;
+//! use confluence::sync::{DeletedPages, DirectorySync};
+//!
+//! let confluence = Confluence::new("https://wiki.example.com/rest/api");
+//! let sync = DirectorySync::new(&confluence, "docs", "DOCS", Ancestor::new("123456"))
+//!     .with_deleted_pages(DeletedPages::Trash);
+//! let plan = sync.plan().unwrap();
+//! print!("{}", plan);
+//! sync.apply(&plan).unwrap();
+//! ```
+
+use crate::attachment::AttachmentSync;
+use crate::client::{Confluence, Executor, Result};
+use crate::content::model::Ancestor;
+use crate::deployment::{ContentApi, DeploymentClient, NewPage, Page};
+use crate::markdown::{to_storage, Asset, MarkdownConverter};
+use crate::model::{Representation, Storage};
+use crate::util::{check, encode_query, get_all, media_type};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The content property holding the source path and content hash of a
+/// published page.
+pub const SYNC_PROPERTY: &str = "docs-sync";
+
+/// What happens to published pages whose document was removed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeletedPages {
+    /// They are left alone.
+    Keep,
+    /// They are moved to the space's trash.
+    Trash,
+    /// They are moved under another page.
+    MoveUnder(Ancestor),
+}
+
+pub struct DirectorySync {
+    confluence: Confluence,
+    dir: PathBuf,
+    space_key: String,
+    root: Ancestor,
+    deleted: DeletedPages,
+}
+
+/// A change [`DirectorySync::apply`] makes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Create {
+        path: String,
+        title: String,
+    },
+    Update {
+        path: String,
+        title: String,
+        id: String,
+    },
+    Unchanged {
+        path: String,
+        title: String,
+        id: String,
+    },
+    /// A page whose document was removed, trashed or moved.
+    Archive {
+        path: String,
+        title: String,
+        id: String,
+    },
+}
+
+/// The changes syncing a directory makes, printed one per line by
+/// `Display`.
+#[derive(Debug)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+    documents: Vec<Document>,
+}
+
+impl Plan {
+    /// Whether applying the plan changes anything.
+    pub fn is_empty(&self) -> bool {
+        self.actions
+            .iter()
+            .all(|a| matches!(a, Action::Unchanged { .. }))
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for action in &self.actions {
+            match action {
+                Action::Create { path, title } => writeln!(f, "create    {} ({})", title, path)?,
+                Action::Update { path, title, id } => {
+                    writeln!(f, "update    {} ({}, page {})", title, path, id)?
+                }
+                Action::Unchanged { path, title, id } => {
+                    writeln!(f, "unchanged {} ({}, page {})", title, path, id)?
+                }
+                Action::Archive { path, title, id } => {
+                    writeln!(f, "archive   {} ({}, page {})", title, path, id)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A page to publish.
+#[derive(Debug)]
+struct Document {
+    /// The Markdown file relative to the root, or the directory if it has
+    /// no index.
+    path: String,
+    /// The directory links and images are resolved against.
+    dir: String,
+    /// Path of the parent document, none right under the root page.
+    parent: Option<String>,
+    title: String,
+    markdown: String,
+    storage: String,
+    assets: Vec<Asset>,
+    hash: String,
+    existing: Option<Page>,
+}
+
+impl DirectorySync {
+    /// Syncs the documents in `dir` to pages of the space `space_key` under
+    /// the page `root`.
+    pub fn new(
+        confluence: &Confluence,
+        dir: impl Into<PathBuf>,
+        space_key: &str,
+        root: Ancestor,
+    ) -> Self {
+        Self {
+            confluence: confluence.clone(),
+            dir: dir.into(),
+            space_key: space_key.to_string(),
+            root,
+            deleted: DeletedPages::Keep,
+        }
+    }
+
+    /// Pages of removed documents are kept by default.
+    pub fn with_deleted_pages(mut self, deleted: DeletedPages) -> Self {
+        self.deleted = deleted;
+        self
+    }
+
+    /// Works out the changes without making any.
+    pub fn plan(&self) -> Result<Plan> {
+        let api = DeploymentClient::detect(&self.confluence)?;
+        let mut documents = Vec::new();
+        self.walk("", None, None, &mut documents)?;
+
+        let mut titles: HashMap<String, String> = HashMap::new();
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for document in &documents {
+            if let Some(other) = seen.insert(&document.title, &document.path) {
+                return Err(format!(
+                    "Unable to sync: {} and {} both have the title {}",
+                    other, document.path, document.title
+                )
+                .into());
+            }
+            titles.insert(document.path.clone(), document.title.clone());
+        }
+
+        let mut ids: HashMap<String, String> = HashMap::new();
+        let mut actions = Vec::new();
+        for document in &mut documents {
+            let conversion = MarkdownConverter::new()
+                .with_document_dir(&document.dir)
+                .with_page_titles(titles.clone())
+                .convert(&document.markdown);
+            document.storage = conversion.storage;
+            document.assets = conversion.assets;
+            document.hash = self.hash(document)?;
+            document.existing = api.find_page(&self.space_key, &document.title)?;
+
+            let path = document.path.clone();
+            let title = document.title.clone();
+            let page = match &document.existing {
+                Some(page) => page,
+                None => {
+                    actions.push(Action::Create { path, title });
+                    continue;
+                }
+            };
+            let id = page.id.clone();
+            ids.insert(path.clone(), id.clone());
+            let parent_id = match &document.parent {
+                Some(parent) => ids.get(parent),
+                None => Some(&self.root.id),
+            };
+            let stored = property(&self.confluence, &id, SYNC_PROPERTY)?;
+            if !stored.as_ref().is_some_and(|p| p["path"] == path.as_str()) {
+                return Err(format!(
+                    "Unable to sync {}: page {} titled {} wasn't published from it",
+                    path, id, title
+                )
+                .into());
+            }
+            let unchanged = stored.is_some_and(|p| p["hash"] == document.hash.as_str())
+                && parent_id.is_some()
+                && parent_id == page.parent_id.as_ref();
+            if unchanged {
+                actions.push(Action::Unchanged { path, title, id });
+            } else {
+                actions.push(Action::Update { path, title, id });
+            }
+        }
+
+        if self.deleted != DeletedPages::Keep {
+            let published: HashSet<&str> = documents.iter().map(|d| d.path.as_str()).collect();
+            for (id, title) in self.descendants()? {
+                let stored = match property(&self.confluence, &id, SYNC_PROPERTY)? {
+                    Some(stored) => stored,
+                    None => continue,
+                };
+                let path = stored["path"].as_str().unwrap_or_default();
+                if !published.contains(path) {
+                    let path = path.to_string();
+                    actions.push(Action::Archive { path, title, id });
+                }
+            }
+        }
+        Ok(Plan { actions, documents })
+    }
+
+    /// Makes the changes of `plan`.
+    pub fn apply(&self, plan: &Plan) -> Result<()> {
+        let api = DeploymentClient::detect(&self.confluence)?;
+        let mut ids: HashMap<&str, String> = HashMap::new();
+        for (document, action) in plan.documents.iter().zip(&plan.actions) {
+            let parent_id = match &document.parent {
+                Some(parent) => ids
+                    .get(parent.as_str())
+                    .cloned()
+                    .ok_or_else(|| format!("Unable to find the page of {}", parent))?,
+                None => self.root.id.clone(),
+            };
+            let page = NewPage::new(
+                &self.space_key,
+                &document.title,
+                Storage::new(&document.storage, Representation::Storage),
+                Some(&parent_id),
+            );
+            let id = match action {
+                Action::Unchanged { id, .. } => {
+                    ids.insert(&document.path, id.clone());
+                    continue;
+                }
+                Action::Create { .. } => api.create_page(&page)?.id,
+                Action::Update { id, .. } => {
+                    let version = document.existing.as_ref().map_or(0, |p| p.version);
+                    api.update_page(id, &page, version + 1)?.id
+                }
+                Action::Archive { .. } => continue,
+            };
+            self.upload_assets(&id, &document.assets)?;
+            let value = json!({ "path": document.path, "hash": document.hash });
+            set_property(&self.confluence, &id, SYNC_PROPERTY, value)?;
+            ids.insert(&document.path, id);
+        }
+
+        for action in &plan.actions {
+            if let Action::Archive { id, .. } = action {
+                match &self.deleted {
+                    DeletedPages::Keep => {}
+                    DeletedPages::Trash => api.delete_page(id)?,
+                    DeletedPages::MoveUnder(parent) => {
+                        let current = api.get_page(id)?;
+                        let body = current.body.unwrap_or_default();
+                        let page = NewPage::new(
+                            &self.space_key,
+                            &current.title,
+                            Storage::new(&body, Representation::Storage),
+                            Some(&parent.id),
+                        );
+                        api.update_page(id, &page, current.version + 1)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Plans and applies the changes, returning what was done.
+    pub fn sync(&self) -> Result<Plan> {
+        let plan = self.plan()?;
+        self.apply(&plan)?;
+        Ok(plan)
+    }
+
+    /// Adds the documents in `dir`, relative to the root, depth first.
+    fn walk(
+        &self,
+        dir: &str,
+        parent: Option<&str>,
+        index: Option<&str>,
+        documents: &mut Vec<Document>,
+    ) -> Result<()> {
+        let full = self.dir.join(dir);
+        let mut entries = std::fs::read_dir(&full)
+            .map_err(|e| format!("Unable to read {}: {}", full.display(), e))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        // Documents before directories, each by name.
+        entries.sort_by_key(|e| (e.path().is_dir(), e.file_name()));
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || Some(name.as_str()) == index {
+                continue;
+            }
+            let path = join(dir, &name);
+            if entry.file_type()?.is_dir() {
+                if !contains_markdown(&entry.path())? {
+                    continue;
+                }
+                let index = find_index(&entry.path())?;
+                let document = match &index {
+                    Some(index) => self.document(&join(&path, index), &path, parent)?,
+                    None => Document::new(&path, &path, parent, &name, String::new()),
+                };
+                let document_path = document.path.clone();
+                documents.push(document);
+                self.walk(&path, Some(&document_path), index.as_deref(), documents)?;
+            } else if is_markdown(&name) {
+                documents.push(self.document(&path, dir, parent)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn document(&self, path: &str, dir: &str, parent: Option<&str>) -> Result<Document> {
+        let full = self.dir.join(path);
+        let markdown = std::fs::read_to_string(&full)
+            .map_err(|e| format!("Unable to read {}: {}", full.display(), e))?;
+        let title = match to_storage(&markdown).title {
+            Some(title) => title,
+            None => {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                name.rsplit_once('.')
+                    .map_or(name, |(stem, _)| stem)
+                    .to_string()
+            }
+        };
+        Ok(Document::new(path, dir, parent, &title, markdown))
+    }
+
+    /// Covers everything published for a document: its title, place in the
+    /// tree, body and attachments.
+    fn hash(&self, document: &Document) -> Result<String> {
+        let mut hasher = Sha256::new();
+        for part in [
+            &document.title,
+            document.parent.as_deref().unwrap_or(""),
+            &document.storage,
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        for asset in &document.assets {
+            hasher.update(asset.filename.as_bytes());
+            hasher.update([0]);
+            hasher.update(self.read_asset(asset)?);
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+
+    fn read_asset(&self, asset: &Asset) -> Result<Vec<u8>> {
+        let full = self.dir.join(&asset.path);
+        std::fs::read(&full).map_err(|e| format!("Unable to read {}: {}", full.display(), e).into())
+    }
+
+    fn upload_assets(&self, page_id: &str, assets: &[Asset]) -> Result<()> {
+        let attachments = AttachmentSync::new(&self.confluence, page_id);
+        for asset in assets {
+            let data = self.read_asset(asset)?;
+            attachments.ensure(&asset.filename, data, media_type(&asset.filename))?;
+        }
+        Ok(())
+    }
+
+    /// Ids and titles of the pages below the root page.
+    fn descendants(&self) -> Result<Vec<(String, String)>> {
+        let cql = encode_query(&format!("ancestor = {} and type = page", self.root.id));
+        let endpoint = format!("content/search?cql={}", cql);
+        let results = get_all(&self.confluence, &endpoint, "find published pages")?;
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                let id = result["id"].as_str().unwrap_or_default().to_string();
+                let title = result["title"].as_str().unwrap_or_default().to_string();
+                (id, title)
+            })
+            .collect())
+    }
+}
+
+impl Document {
+    fn new(path: &str, dir: &str, parent: Option<&str>, title: &str, markdown: String) -> Self {
+        Self {
+            path: path.to_string(),
+            dir: dir.to_string(),
+            parent: parent.map(str::to_string),
+            title: title.to_string(),
+            markdown,
+            storage: String::new(),
+            assets: Vec::new(),
+            hash: String::new(),
+            existing: None,
+        }
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn is_markdown(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.ends_with(".md") || name.ends_with(".markdown")
+}
+
+fn contains_markdown(dir: &Path) -> Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let found = if entry.file_type()?.is_dir() {
+            contains_markdown(&entry.path())?
+        } else {
+            is_markdown(&name)
+        };
+        if found {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The file holding the body of a directory's page.
+fn find_index(dir: &Path) -> Result<Option<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(["index.md", "readme.md"].iter().find_map(|index| {
+        names
+            .iter()
+            .find(|n| n.eq_ignore_ascii_case(index))
+            .cloned()
+    }))
+}
+
+/// A content property's value, if it is set.
+fn property(confluence: &Confluence, id: &str, key: &str) -> Result<Option<Value>> {
+    let (_, status, d) = confluence
+        .get()
+        .custom_endpoint(&format!("content/{}/property/{}", id, key))
+        .execute::<Value>()?;
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    check(status, "get property")?;
+    Ok(d.map(|d| d["value"].clone()))
+}
+
+fn set_property(confluence: &Confluence, id: &str, key: &str, value: Value) -> Result<()> {
+    let endpoint = format!("content/{}/property/{}", id, key);
+    let (_, status, current) = confluence
+        .get()
+        .custom_endpoint(&endpoint)
+        .execute::<Value>()?;
+    let (_, status, _) = match current {
+        Some(current) if status.is_success() => {
+            let version = current["version"]["number"].as_u64().unwrap_or(0) + 1;
+            confluence
+                .put(json!({ "key": key, "value": value, "version": { "number": version } }))
+                .custom_endpoint(&endpoint)
+                .execute::<Value>()?
+        }
+        _ => confluence
+            .post(json!({ "key": key, "value": value }))
+            .custom_endpoint(&format!("content/{}/property", id))
+            .execute::<Value>()?,
+    };
+    check(status, "set property")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::FakeConfluence;
+
+    fn docs(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sync-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("guide/img")).unwrap();
+        std::fs::write(
+            dir.join("index.md"),
+            "# Home\n\nSee the [guide](guide/README.md).",
+        )
+        .unwrap();
+        std::fs::write(dir.join("guide/README.md"), "# Guide\n\nStart here.").unwrap();
+        std::fs::write(
+            dir.join("guide/install.md"),
+            "# Installing\n\n![setup](img/setup.png)\n\nBack [home](../index.md).",
+        )
+        .unwrap();
+        std::fs::write(dir.join("guide/img/setup.png"), b"png").unwrap();
+        dir
+    }
+
+    fn root(server: &FakeConfluence) -> Ancestor {
+        let confluence = server.client();
+        let api = DeploymentClient::detect(&confluence).unwrap();
+        let storage = Storage::new("", Representation::Storage);
+        let root = api
+            .create_page(&NewPage::new("DOCS", "Docs", storage, None))
+            .unwrap();
+        Ancestor::new(&root.id)
+    }
+
+    #[test]
+    fn publishes_a_page_tree() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let root = root(&server);
+        let dir = docs("tree");
+        let sync = DirectorySync::new(&server.client(), &dir, "DOCS", root.clone());
+
+        let plan = sync.sync().unwrap();
+        assert_eq!(
+            plan.to_string(),
+            "create    Home (index.md)\n\
+             create    Guide (guide/README.md)\n\
+             create    Installing (guide/install.md)\n"
+        );
+        let home = server.find("DOCS", "Home").unwrap();
+        let guide = server.find("DOCS", "Guide").unwrap();
+        let install = server.find("DOCS", "Installing").unwrap();
+        assert_eq!(home.parent_id.as_deref(), Some(root.id.as_str()));
+        assert_eq!(install.parent_id.as_deref(), Some(guide.id.as_str()));
+        assert!(home.body.contains(r#"<ri:page ri:content-title="Guide""#));
+        assert!(install
+            .body
+            .contains(r#"<ri:attachment ri:filename="setup.png""#));
+        let attachment = |server: &FakeConfluence| {
+            server
+                .contents()
+                .into_iter()
+                .find(|c| c.title == "setup.png")
+                .unwrap()
+        };
+        assert_eq!(
+            attachment(&server).parent_id.as_deref(),
+            Some(install.id.as_str())
+        );
+
+        assert!(sync.plan().unwrap().is_empty());
+
+        std::fs::write(dir.join("guide/img/setup.png"), b"png2").unwrap();
+        let plan = sync.sync().unwrap();
+        assert_eq!(
+            plan.actions[2],
+            Action::Update {
+                path: "guide/install.md".to_string(),
+                title: "Installing".to_string(),
+                id: install.id.clone()
+            }
+        );
+        assert_eq!(attachment(&server).data, b"png2");
+        assert!(plan.actions[..2]
+            .iter()
+            .all(|a| matches!(a, Action::Unchanged { .. })));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_adopt_pages_it_did_not_publish() {
+        let server = FakeConfluence::start().unwrap().with_space("DOCS", "Docs");
+        let root = root(&server);
+        let guide = server.create_page("DOCS", "Guide", Some(&root.id));
+        let dir = docs("conflict");
+        let sync = DirectorySync::new(&server.client(), &dir, "DOCS", root);
+
+        let err = sync.plan().unwrap_err().to_string();
+        assert!(err.contains("guide/README.md"), "{}", err);
+        assert!(err.contains(&guide), "{}", err);
+        assert!(server.find("DOCS", "Home").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_documents_are_archived() {
+        // Descendants are listed one per page.
+        let server = FakeConfluence::start()
+            .unwrap()
+            .with_space("DOCS", "Docs")
+            .with_max_limit(1);
+        let root = root(&server);
+        let dir = docs("removed");
+        let sync = DirectorySync::new(&server.client(), &dir, "DOCS", root.clone());
+        sync.sync().unwrap();
+        let install = server.find("DOCS", "Installing").unwrap();
+
+        std::fs::remove_file(dir.join("guide/install.md")).unwrap();
+        assert_eq!(sync.plan().unwrap().actions.len(), 2);
+        let sync = sync.with_deleted_pages(DeletedPages::Trash);
+        let plan = sync.plan().unwrap();
+        assert_eq!(
+            plan.actions.last(),
+            Some(&Action::Archive {
+                path: "guide/install.md".to_string(),
+                title: "Installing".to_string(),
+                id: install.id.clone()
+            })
+        );
+        // A dry run changes nothing.
+        assert!(server.content(&install.id).is_some());
+        sync.apply(&plan).unwrap();
+        assert!(server.content(&install.id).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
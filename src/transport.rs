@@ -0,0 +1,294 @@
+//! The seam between the query builders and the network.
+//!
+//! Every request a [`Confluence`](crate::client::Confluence) client sends
+//! goes through its [`Transport`], after retries and rate limiting have been
+//! applied. [`ReqwestTransport`] is the default; wrapping it allows signing or
+//! logging requests, and [`MockTransport`] replaces the network in tests:
+//!
+//! ```
+//! use confluence::client::{Confluence, Executor, Method};
+//! use confluence::transport::{Expectation, MockTransport};
+//! use std::rc::Rc;
+//!
+//! let mock = Rc::new(MockTransport::new());
+//! mock.expect(
+//!     Expectation::new(Method::GET, "/rest/api/content")
+//!         .query("spaceKey", "DOC")
+//!         .query("title", "Home")
+//!         .respond_json(200, &serde_json::json!({ "results": [] })),
+//! );
+//! let confluence = Confluence::new("https://confluence.example.com/rest/api")
+//!     .with_transport(mock.clone());
+//! let (_, status, body) = confluence
+//!     .get()
+//!     .content()
+//!     .space_key("DOC")
+//!     .title("Home")
+//!     .expand("version")
+//!     .execute::<serde_json::Value>()
+//!     .unwrap();
+//! assert_eq!(status, 200);
+//! assert_eq!(body.unwrap()["results"], serde_json::json!([]));
+//! mock.assert_done();
+//! ```
+
+use crate::client::Result;
+use reqwest::blocking::{Client, Request, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Sends a request and returns the response.
+pub trait Transport {
+    fn send(&self, request: Request) -> Result<Response>;
+}
+
+/// Sends requests with a `reqwest` client.
+pub struct ReqwestTransport {
+    client: Rc<Client>,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Rc<Client>) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> Result<Response> {
+        Ok(self.client.execute(request)?)
+    }
+}
+
+/// A request as seen by a [`MockTransport`].
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub url: reqwest::Url,
+    pub headers: HeaderMap,
+    /// `None` for streamed bodies such as multipart uploads.
+    pub body: Option<Vec<u8>>,
+}
+
+/// An expected request and the response to answer it with.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    method: Method,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Option<serde_json::Value>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    response: Vec<u8>,
+}
+
+impl Expectation {
+    /// Expects a `method` request to `path`, answered with an empty 200
+    /// response unless set otherwise.
+    pub fn new(method: Method, path: &str) -> Self {
+        Self {
+            method,
+            path: path.to_string(),
+            query: Vec::new(),
+            body: None,
+            status: 200,
+            headers: Vec::new(),
+            response: Vec::new(),
+        }
+    }
+
+    /// Expects the query string to contain `name=value`.
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Expects a JSON request body equal to `body`.
+    pub fn json_body(mut self, body: &serde_json::Value) -> Self {
+        self.body = Some(body.clone());
+        self
+    }
+
+    pub fn respond(mut self, status: u16, body: &str) -> Self {
+        self.status = status;
+        self.response = body.as_bytes().to_vec();
+        self
+    }
+
+    pub fn respond_json(self, status: u16, body: &serde_json::Value) -> Self {
+        self.respond(status, &body.to_string())
+            .header(CONTENT_TYPE.as_str(), "application/json")
+    }
+
+    /// Adds a header to the response.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn check(&self, request: &RecordedRequest) -> std::result::Result<(), String> {
+        if request.method != self.method || request.url.path() != self.path {
+            return Err(format!(
+                "expected {} {}, got {} {}",
+                self.method,
+                self.path,
+                request.method,
+                request.url.path()
+            ));
+        }
+        let pairs: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+        if let Some((name, value)) = self.query.iter().find(|q| !pairs.contains(q)) {
+            return Err(format!(
+                "expected query parameter {}={} in {}",
+                name, value, request.url
+            ));
+        }
+        if let Some(expected) = &self.body {
+            let actual = request
+                .body
+                .as_ref()
+                .and_then(|b| serde_json::from_slice::<serde_json::Value>(b).ok());
+            if actual.as_ref() != Some(expected) {
+                return Err(format!(
+                    "expected body {} for {} {}, got {}",
+                    expected,
+                    self.method,
+                    self.path,
+                    actual.map(|a| a.to_string()).unwrap_or_default()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn response(&self) -> Result<Response> {
+        let mut builder = http::Response::builder().status(StatusCode::from_u16(self.status)?);
+        for (name, value) in &self.headers {
+            builder = builder.header(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        Ok(builder.body(self.response.clone())?.into())
+    }
+}
+
+/// Answers requests from a queue of [`Expectation`]s, in order, and panics
+/// on a request that doesn't match the next one.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    expectations: RefCell<VecDeque<Expectation>>,
+    requests: RefCell<Vec<RecordedRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect(&self, expectation: Expectation) -> &Self {
+        self.expectations.borrow_mut().push_back(expectation);
+        self
+    }
+
+    /// The requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.borrow().clone()
+    }
+
+    /// Panics unless every expected request has been received.
+    pub fn assert_done(&self) {
+        let remaining = self.expectations.borrow();
+        if let Some(next) = remaining.front() {
+            panic!(
+                "{} expected request(s) not received, next {} {}",
+                remaining.len(),
+                next.method,
+                next.path
+            );
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: Request) -> Result<Response> {
+        let recorded = RecordedRequest {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: request.headers().clone(),
+            body: request
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| b.to_vec()),
+        };
+        self.requests.borrow_mut().push(recorded.clone());
+        let expectation = match self.expectations.borrow_mut().pop_front() {
+            Some(e) => e,
+            None => panic!("unexpected request {} {}", recorded.method, recorded.url),
+        };
+        if let Err(message) = expectation.check(&recorded) {
+            panic!("{}", message);
+        }
+        expectation.response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Confluence, Executor};
+    use crate::model::{Body, CreatePageRequest, Representation, Space, Storage};
+    use serde_json::json;
+
+    #[test]
+    fn mock_checks_body_and_returns_canned_response() {
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(
+            Expectation::new(Method::POST, "/rest/api/content")
+                .json_body(&json!({
+                    "type": "page",
+                    "title": "Hello",
+                    "space": { "key": "DOC" },
+                    "body": { "storage": { "value": "<p>Hi</p>", "representation": "storage" } }
+                }))
+                .respond_json(200, &json!({ "id": "42" }))
+                .header("X-Request-Id", "abc"),
+        );
+        let confluence =
+            Confluence::new("https://confluence.example.com/rest/api").with_transport(mock.clone());
+        let request = CreatePageRequest::new(
+            "page",
+            "Hello",
+            Space::new("DOC"),
+            Body::new(Storage::new("<p>Hi</p>", Representation::Storage)),
+            None,
+        );
+        let (headers, status, d) = confluence
+            .post(request)
+            .content()
+            .execute::<serde_json::Value>()
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-request-id"], "abc");
+        assert_eq!(d.unwrap()["id"], "42");
+        assert_eq!(mock.requests().len(), 1);
+        mock.assert_done();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected GET /rest/api/content/1, got GET /rest/api/content/2")]
+    fn mock_panics_on_unexpected_request() {
+        let mock = Rc::new(MockTransport::new());
+        mock.expect(Expectation::new(Method::GET, "/rest/api/content/1"));
+        let confluence =
+            Confluence::new("https://confluence.example.com/rest/api").with_transport(mock);
+        let _ = confluence
+            .get()
+            .content()
+            .content_id("2")
+            .expand("version")
+            .execute::<serde_json::Value>();
+    }
+}
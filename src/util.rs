@@ -1,9 +1,9 @@
-use reqwest::{blocking::Response, header::HeaderMap, StatusCode, Url};
+use reqwest::{StatusCode, Url};
+use serde_json::Value;
 
-pub fn url_join(
-    url: &Url,
-    path: &str,
-) -> Result<Url, Box<dyn std::error::Error + Send + Sync + 'static>> {
+use crate::client::{Confluence, Executor, Result};
+
+pub fn url_join(url: &Url, path: &str) -> Result<Url> {
     let url_str = url.to_string();
     if url_str.ends_with("/") {
         return Ok(url.join(path)?);
@@ -12,3 +12,156 @@ pub fn url_join(
         return Ok(reqwest::Url::parse(&u)?);
     }
 }
+
+/// Percent-encodes a value for use in a query string, as the query builders
+/// append parameters verbatim.
+pub fn encode_query(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// The media type of a file by its extension, `application/octet-stream`
+/// when it is not known.
+pub fn media_type(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit('.')
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "html" | "htm" => "text/html",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The page size of paginated listings.
+pub(crate) const PAGE_SIZE: usize = 50;
+
+/// Year, month and day of a number of days since 1970-01-01, after Howard
+/// Hinnant's `civil_from_days`.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Days since 1970-01-01 of a date.
+pub(crate) fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = if m > 2 { m - 3 } else { m + 9 };
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Whether a header or parameter may carry credentials and must be kept out
+/// of logs and recordings.
+pub(crate) fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    matches!(
+        name.as_str(),
+        "authorization" | "proxy-authorization" | "cookie" | "set-cookie" | "x-atlassian-token"
+    ) || [
+        "token", "secret", "password", "api-key", "apikey", "session",
+    ]
+    .iter()
+    .any(|s| name.contains(s))
+}
+
+/// Fails with "Unable to {action}: {status}" unless the status is a success.
+pub fn check(status: StatusCode, action: &str) -> Result<()> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(format!("Unable to {}: {}", action, status).into())
+    }
+}
+
+/// The body of a successful response, failing on an error status or a body
+/// that did not deserialize.
+pub fn parse<T>(status: StatusCode, d: Option<T>, action: &str) -> Result<T> {
+    check(status, action)?;
+    d.ok_or_else(|| format!("Unable to {}: unexpected response", action).into())
+}
+
+/// The JSON body of a response, or `Null` when a success has none; errors
+/// carry the `message` Confluence puts in its error bodies.
+pub fn expect(status: StatusCode, d: Option<Value>, action: &str) -> Result<Value> {
+    if status.is_success() {
+        return Ok(d.unwrap_or(Value::Null));
+    }
+    match d.as_ref().and_then(|d| d["message"].as_str()) {
+        Some(message) => Err(format!("Unable to {}: {}: {}", action, status, message).into()),
+        None => Err(format!("Unable to {}: {}", action, status).into()),
+    }
+}
+
+/// The JSON body of a GET of `endpoint`, relative to the REST API root.
+pub fn get_json(confluence: &Confluence, endpoint: &str, action: &str) -> Result<Value> {
+    let (_, status, d) = confluence
+        .get()
+        .custom_endpoint(endpoint)
+        .execute::<Value>()?;
+    expect(status, d, action)
+}
+
+/// The results of every page of a v1 listing such as `content/{id}/label`
+/// or `content/search?cql=…`, requested with `start` and `limit` until the
+/// server has no next page. Fails rather than return a listing that may be
+/// cut short.
+pub fn get_all(confluence: &Confluence, endpoint: &str, action: &str) -> Result<Vec<Value>> {
+    let sep = if endpoint.contains('?') { '&' } else { '?' };
+    let mut all = Vec::new();
+    loop {
+        let page = format!("{}{}start={}&limit={}", endpoint, sep, all.len(), PAGE_SIZE);
+        let page = get_json(confluence, &page, action)?;
+        let more = has_next(&page)
+            .ok_or_else(|| format!("Unable to {}: the response has no paging links", action))?;
+        let results = page["results"].as_array().cloned().unwrap_or_default();
+        let empty = results.is_empty();
+        all.extend(results);
+        if !more || empty {
+            return Ok(all);
+        }
+    }
+}
+
+/// Whether more results follow a page of a listing, by its `_links.next`
+/// or, without links, by a page as full as the `limit` the server applied,
+/// which may be lower than the one requested.
+fn has_next(page: &Value) -> Option<bool> {
+    if page["_links"].is_object() {
+        return Some(page["_links"]["next"].is_string());
+    }
+    let limit = page["limit"].as_u64()?;
+    let size = page["results"].as_array()?.len() as u64;
+    Some(size >= limit)
+}
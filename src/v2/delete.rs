@@ -0,0 +1,43 @@
+imports!();
+
+use crate::client::DeleteQueryBuilder;
+
+new_type!(
+    Pages
+    PageId
+    BlogPosts
+    BlogPostId
+    Attachments
+    AttachmentId
+);
+
+from!(
+    @DeleteQueryBuilder
+        -> Pages = "pages"
+    @DeleteQueryBuilder
+        -> BlogPosts = "blogposts"
+    @DeleteQueryBuilder
+        -> Attachments = "attachments"
+    @Pages
+        => PageId
+    @BlogPosts
+        => BlogPostId
+    @Attachments
+        => AttachmentId
+);
+
+impl_macro!(
+    @Pages
+        |
+        |=> page_id -> PageId = page_id_str
+    @BlogPosts
+        |
+        |=> blogpost_id -> BlogPostId = blogpost_id_str
+    @Attachments
+        |
+        |=> attachment_id -> AttachmentId = attachment_id_str
+);
+
+exec!(PageId);
+exec!(BlogPostId);
+exec!(AttachmentId);
@@ -0,0 +1,176 @@
+imports!();
+
+use crate::client::GetQueryBuilder;
+
+new_type!(
+    Pages
+    PageId
+    PageChildren
+    PageAttachments
+    BlogPosts
+    BlogPostId
+    Attachments
+    AttachmentId
+    Spaces
+    SpaceId
+    SpacePages
+    SpaceIdFilter
+    SpaceKeys
+    Title
+    Filename
+    BodyFormat
+    Limit
+);
+
+from!(
+    @GetQueryBuilder
+        -> Pages = "pages"
+    @GetQueryBuilder
+        -> BlogPosts = "blogposts"
+    @GetQueryBuilder
+        -> Attachments = "attachments"
+    @GetQueryBuilder
+        -> Spaces = "spaces"
+    @Pages
+        ?> SpaceIdFilter = "space-id"
+        ?> Title = "title"
+        ?> BodyFormat = "body-format"
+        ?> Limit = "limit"
+    @Pages
+        => PageId
+    @PageId
+        ?> BodyFormat = "body-format"
+    @PageId
+        -> PageChildren = "children"
+    @PageId
+        -> PageAttachments = "attachments"
+    @PageChildren
+        ?> Limit = "limit"
+    @PageAttachments
+        ?> Filename = "filename"
+        ?> Limit = "limit"
+    @BlogPosts
+        ?> SpaceIdFilter = "space-id"
+        ?> Title = "title"
+        ?> BodyFormat = "body-format"
+        ?> Limit = "limit"
+    @BlogPosts
+        => BlogPostId
+    @BlogPostId
+        ?> BodyFormat = "body-format"
+    @Attachments
+        ?> Filename = "filename"
+        ?> Limit = "limit"
+    @Attachments
+        => AttachmentId
+    @Spaces
+        ?> SpaceKeys = "keys"
+        ?> Limit = "limit"
+    @Spaces
+        => SpaceId
+    @SpaceId
+        -> SpacePages = "pages"
+    @SpacePages
+        ?> Title = "title"
+        ?> BodyFormat = "body-format"
+        ?> Limit = "limit"
+    @SpaceIdFilter
+        ?> Title = "title"
+        ?> BodyFormat = "body-format"
+        ?> Limit = "limit"
+    @SpaceKeys
+        ?> Limit = "limit"
+    @Title
+        ?> BodyFormat = "body-format"
+        ?> Limit = "limit"
+    @Filename
+        ?> Limit = "limit"
+    @BodyFormat
+        ?> Limit = "limit"
+);
+
+impl_macro!(
+    @Pages
+        |
+        |=> page_id -> PageId = page_id_str
+        |?> space_id -> SpaceIdFilter = space_id_str
+        |?> title -> Title = title_str
+        |?> body_format -> BodyFormat = body_format_str
+        |?> limit -> Limit = limit_str
+    @PageId
+        |=> children -> PageChildren
+        |=> attachments -> PageAttachments
+        |
+        |?> body_format -> BodyFormat = body_format_str
+    @PageChildren
+        |
+        |?> limit -> Limit = limit_str
+    @PageAttachments
+        |
+        |?> filename -> Filename = filename_str
+        |?> limit -> Limit = limit_str
+    @BlogPosts
+        |
+        |=> blogpost_id -> BlogPostId = blogpost_id_str
+        |?> space_id -> SpaceIdFilter = space_id_str
+        |?> title -> Title = title_str
+        |?> body_format -> BodyFormat = body_format_str
+        |?> limit -> Limit = limit_str
+    @BlogPostId
+        |
+        |?> body_format -> BodyFormat = body_format_str
+    @Attachments
+        |
+        |=> attachment_id -> AttachmentId = attachment_id_str
+        |?> filename -> Filename = filename_str
+        |?> limit -> Limit = limit_str
+    @Spaces
+        |
+        |=> space_id -> SpaceId = space_id_str
+        |?> keys -> SpaceKeys = keys_str
+        |?> limit -> Limit = limit_str
+    @SpaceId
+        |=> pages -> SpacePages
+        |
+    @SpacePages
+        |
+        |?> title -> Title = title_str
+        |?> body_format -> BodyFormat = body_format_str
+        |?> limit -> Limit = limit_str
+    @SpaceIdFilter
+        |
+        |?> title -> Title = title_str
+        |?> body_format -> BodyFormat = body_format_str
+        |?> limit -> Limit = limit_str
+    @SpaceKeys
+        |
+        |?> limit -> Limit = limit_str
+    @Title
+        |
+        |?> body_format -> BodyFormat = body_format_str
+        |?> limit -> Limit = limit_str
+    @Filename
+        |
+        |?> limit -> Limit = limit_str
+    @BodyFormat
+        |
+        |?> limit -> Limit = limit_str
+);
+
+exec!(Pages);
+exec!(PageId);
+exec!(PageChildren);
+exec!(PageAttachments);
+exec!(BlogPosts);
+exec!(BlogPostId);
+exec!(Attachments);
+exec!(AttachmentId);
+exec!(Spaces);
+exec!(SpaceId);
+exec!(SpacePages);
+exec!(SpaceIdFilter);
+exec!(SpaceKeys);
+exec!(Title);
+exec!(Filename);
+exec!(BodyFormat);
+exec!(Limit);
@@ -0,0 +1,241 @@
+//! Confluence Cloud REST API v2 (`/wiki/api/v2`).
+//!
+//! The v2 API has separate `pages`, `blogposts`, `attachments` and `spaces`
+//! resources, numeric ids (sent as strings) and cursor based pagination. Its
+//! builders hang off the same query builders as v1 and are used with a client
+//! configured for it:
+//!
+//! ```no_run
+//! use confluence::client::{ApiVersion, Confluence};
+//! use confluence::v2::model::Page;
+//!
+//! let confluence = Confluence::new("https://example.atlassian.net/wiki/rest/api")
+//!     .with_api_version(ApiVersion::V2);
+//! let pages = confluence
+//!     .cursor::<Page, _>(confluence.get().spaces().space_id("42").pages().limit("100"))
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! ```
+
+pub mod delete;
+pub mod get;
+pub mod model;
+pub mod post;
+pub mod put;
+
+use crate::client::{Confluence, Executor, Result};
+use model::MultiEntityResult;
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, LINK};
+use reqwest::{Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+
+/// Iterates over every result of a v2 list endpoint, following the cursor
+/// of each page to fetch the next one.
+pub struct Cursor<'c, T, E> {
+    confluence: &'c Confluence,
+    first: Option<E>,
+    next: Option<Url>,
+    buffer: VecDeque<T>,
+}
+
+impl Confluence {
+    /// Walks all pages of results of `query`, e.g.
+    /// `confluence.get().pages().space_id("42")`.
+    pub fn cursor<T, E>(&self, query: E) -> Cursor<'_, T, E>
+    where
+        T: DeserializeOwned,
+        E: Executor,
+    {
+        Cursor {
+            confluence: self,
+            first: Some(query),
+            next: None,
+            buffer: VecDeque::new(),
+        }
+    }
+}
+
+impl<'c, T, E> Cursor<'c, T, E>
+where
+    T: DeserializeOwned,
+    E: Executor,
+{
+    fn fetch(&mut self) -> Result<Option<MultiEntityResult<T>>> {
+        let (headers, status, page) = match (self.first.take(), self.next.take()) {
+            (Some(query), _) => query.execute::<MultiEntityResult<T>>()?,
+            (None, Some(url)) => {
+                let mut request = Request::new(Method::GET, url);
+                *request.headers_mut() = self.confluence.default_headers().clone();
+                request
+                    .headers_mut()
+                    .entry(ACCEPT)
+                    .or_insert(HeaderValue::from_static("application/json"));
+                let res = self.confluence.send(request)?;
+                let headers = res.headers().clone();
+                let status = res.status();
+                (headers, status, res.json().ok())
+            }
+            (None, None) => return Ok(None),
+        };
+        if status != StatusCode::OK {
+            return Err(format!("Unable to fetch results: {}", status).into());
+        }
+        let page = page.ok_or("Unable to parse results")?;
+        let next = next_link(&headers).or_else(|| page.links.as_ref().and_then(|l| l.next.clone()));
+        if let Some(next) = next {
+            self.next = Some(Url::parse(&self.confluence.base_url())?.join(&next)?);
+        }
+        Ok(Some(page))
+    }
+}
+
+impl<'c, T, E> Iterator for Cursor<'c, T, E>
+where
+    T: DeserializeOwned,
+    E: Executor,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                return Some(Ok(item));
+            }
+            match self.fetch() {
+                Ok(Some(page)) => self.buffer.extend(page.results),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// The `rel="next"` target of a `Link` header, e.g.
+/// `</wiki/api/v2/pages?cursor=abc>; rel="next"`.
+pub fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find_map(|link| {
+            let mut parts = link.split(';');
+            let target = parts.next()?.trim();
+            let is_next = parts.any(|p| {
+                let p = p.trim().replace(' ', "");
+                p == "rel=\"next\"" || p == "rel=next"
+            });
+            if is_next {
+                Some(
+                    target
+                        .trim_start_matches('<')
+                        .trim_end_matches('>')
+                        .to_string(),
+                )
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::model::*;
+    use super::*;
+    use crate::client::ApiVersion;
+    use crate::transport::{Expectation, MockTransport};
+    use serde_json::json;
+
+    #[test]
+    fn base_url_follows_api_version() {
+        let confluence = Confluence::new("https://example.atlassian.net/wiki/rest/api");
+        assert_eq!(
+            confluence.base_url(),
+            "https://example.atlassian.net/wiki/rest/api"
+        );
+        let confluence = confluence.with_api_version(ApiVersion::V2);
+        assert_eq!(
+            confluence.base_url(),
+            "https://example.atlassian.net/wiki/api/v2"
+        );
+    }
+
+    #[test]
+    fn follow_up_pages_keep_the_default_headers() {
+        let mock = std::rc::Rc::new(MockTransport::new());
+        mock.expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/pages").respond_json(
+                200,
+                &json!({
+                    "results": [{ "id": "1" }],
+                    "_links": { "next": "/wiki/api/v2/pages?cursor=b" },
+                }),
+            ),
+        );
+        mock.expect(
+            Expectation::new(Method::GET, "/wiki/api/v2/pages")
+                .query("cursor", "b")
+                .respond_json(200, &json!({ "results": [{ "id": "2" }], "_links": {} })),
+        );
+        let confluence = Confluence::builder("https://example.atlassian.net/wiki/rest/api")
+            .with_bearer_auth("SECRET")
+            .build()
+            .unwrap()
+            .with_api_version(ApiVersion::V2)
+            .with_transport(mock.clone());
+        let ids: Vec<String> = confluence
+            .cursor::<serde_json::Value, _>(confluence.get().pages())
+            .map(|page| page.unwrap()["id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(ids, ["1", "2"]);
+        mock.assert_done();
+        for request in mock.requests() {
+            assert_eq!(request.headers["authorization"], "Bearer SECRET");
+        }
+    }
+
+    #[test]
+    fn next_link_from_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(next_link(&headers), None);
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                "</wiki/api/v2/pages?cursor=b>; rel=\"prev\", </wiki/api/v2/pages?cursor=c&limit=2>; rel=\"next\"",
+            ),
+        );
+        assert_eq!(
+            next_link(&headers).as_deref(),
+            Some("/wiki/api/v2/pages?cursor=c&limit=2")
+        );
+    }
+
+    #[test]
+    fn deserialize_pages() {
+        let json = r#"{"results":[{"id":"98305","status":"current","title":"Home","spaceId":"65539",
+            "parentId":null,"authorId":"5b10ac8d82e05b22cc7d4ef5","createdAt":"2024-01-02T10:00:00.000Z",
+            "version":{"number":3,"message":"","minorEdit":false,"authorId":"5b10ac8d82e05b22cc7d4ef5",
+            "createdAt":"2024-01-03T10:00:00.000Z"},"body":{"storage":{"representation":"storage",
+            "value":"<p>Hi</p>"}},"_links":{"webui":"/spaces/DOC/pages/98305/Home"}}],
+            "_links":{"next":"/wiki/api/v2/pages?cursor=abc","base":"https://example.atlassian.net/wiki"}}"#;
+        let page: MultiEntityResult<Page> = serde_json::from_str(json).unwrap();
+        assert_eq!(page.results[0].version.as_ref().unwrap().number, 3);
+        assert_eq!(
+            page.results[0]
+                .body
+                .as_ref()
+                .unwrap()
+                .storage
+                .as_ref()
+                .unwrap()
+                .value,
+            "<p>Hi</p>"
+        );
+        assert_eq!(
+            page.links.unwrap().next.as_deref(),
+            Some("/wiki/api/v2/pages?cursor=abc")
+        );
+    }
+}
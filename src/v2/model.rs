@@ -0,0 +1,216 @@
+use crate::model::Representation;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A page of results. `links.next` holds the relative URL of the next page,
+/// which is also sent in the `Link` response header.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MultiEntityResult<T> {
+    pub results: Vec<T>,
+    #[serde(rename = "_links", default)]
+    pub links: Option<MultiEntityLinks>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MultiEntityLinks {
+    pub next: Option<String>,
+    pub base: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Version {
+    pub number: u64,
+    pub message: Option<String>,
+    #[serde(rename = "minorEdit", default)]
+    pub minor_edit: bool,
+    #[serde(rename = "authorId")]
+    pub author_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BodyType {
+    pub representation: Representation,
+    pub value: String,
+}
+
+/// The body of a page or blog post, in the format asked for with
+/// `body_format`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BodyBulk {
+    pub storage: Option<BodyType>,
+    pub atlas_doc_format: Option<BodyType>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContentLinks {
+    pub webui: Option<String>,
+    pub editui: Option<String>,
+    pub tinyui: Option<String>,
+    pub download: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Page {
+    pub id: String,
+    pub status: String,
+    pub title: String,
+    #[serde(rename = "spaceId")]
+    pub space_id: String,
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<String>,
+    #[serde(rename = "parentType")]
+    pub parent_type: Option<String>,
+    #[serde(rename = "authorId")]
+    pub author_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    pub version: Option<Version>,
+    pub body: Option<BodyBulk>,
+    #[serde(rename = "_links")]
+    pub links: Option<ContentLinks>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlogPost {
+    pub id: String,
+    pub status: String,
+    pub title: String,
+    #[serde(rename = "spaceId")]
+    pub space_id: String,
+    #[serde(rename = "authorId")]
+    pub author_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    pub version: Option<Version>,
+    pub body: Option<BodyBulk>,
+    #[serde(rename = "_links")]
+    pub links: Option<ContentLinks>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Attachment {
+    pub id: String,
+    pub status: String,
+    pub title: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    #[serde(rename = "pageId")]
+    pub page_id: Option<String>,
+    #[serde(rename = "blogPostId")]
+    pub blog_post_id: Option<String>,
+    #[serde(rename = "mediaType")]
+    pub media_type: Option<String>,
+    #[serde(rename = "mediaTypeDescription")]
+    pub media_type_description: Option<String>,
+    pub comment: Option<String>,
+    #[serde(rename = "fileId")]
+    pub file_id: Option<String>,
+    #[serde(rename = "fileSize")]
+    pub file_size: Option<u64>,
+    #[serde(rename = "webuiLink")]
+    pub webui_link: Option<String>,
+    #[serde(rename = "downloadLink")]
+    pub download_link: Option<String>,
+    pub version: Option<Version>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Space {
+    pub id: String,
+    pub key: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub space_type: String,
+    pub status: String,
+    #[serde(rename = "homepageId")]
+    pub homepage_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BodyWrite {
+    pub representation: Representation,
+    pub value: String,
+}
+
+impl BodyWrite {
+    pub fn new(value: &str, representation: Representation) -> Self {
+        Self {
+            representation,
+            value: value.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VersionWrite {
+    pub number: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl VersionWrite {
+    pub fn new(number: u64) -> Self {
+        Self {
+            number,
+            message: None,
+        }
+    }
+}
+
+/// Request body for `POST /pages` and `POST /blogposts`; `parent_id` is
+/// ignored for blog posts.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePageRequest {
+    #[serde(rename = "spaceId")]
+    pub space_id: String,
+    pub status: String,
+    pub title: String,
+    #[serde(rename = "parentId", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub body: BodyWrite,
+}
+
+impl CreatePageRequest {
+    pub fn new(space_id: &str, title: &str, body: BodyWrite, parent_id: Option<&str>) -> Self {
+        Self {
+            space_id: space_id.to_string(),
+            status: "current".to_string(),
+            title: title.to_string(),
+            parent_id: parent_id.map(|p| p.to_string()),
+            body,
+        }
+    }
+}
+
+/// Request body for `PUT /pages/{id}` and `PUT /blogposts/{id}`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdatePageRequest {
+    pub id: String,
+    pub status: String,
+    pub title: String,
+    #[serde(rename = "parentId", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    pub body: BodyWrite,
+    pub version: VersionWrite,
+}
+
+impl UpdatePageRequest {
+    pub fn new(
+        id: &str,
+        title: &str,
+        body: BodyWrite,
+        parent_id: Option<&str>,
+        version: VersionWrite,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            status: "current".to_string(),
+            title: title.to_string(),
+            parent_id: parent_id.map(|p| p.to_string()),
+            body,
+            version,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+imports!();
+
+use crate::client::PostQueryBuilder;
+
+new_type!(
+    Pages
+    BlogPosts
+);
+
+from!(
+    @PostQueryBuilder
+        -> Pages = "pages"
+    @PostQueryBuilder
+        -> BlogPosts = "blogposts"
+);
+
+exec!(Pages);
+exec!(BlogPosts);
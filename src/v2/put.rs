@@ -0,0 +1,33 @@
+imports!();
+
+use crate::client::PutQueryBuilder;
+
+new_type!(
+    Pages
+    PageId
+    BlogPosts
+    BlogPostId
+);
+
+from!(
+    @PutQueryBuilder
+        -> Pages = "pages"
+    @PutQueryBuilder
+        -> BlogPosts = "blogposts"
+    @Pages
+        => PageId
+    @BlogPosts
+        => BlogPostId
+);
+
+impl_macro!(
+    @Pages
+        |
+        |=> page_id -> PageId = page_id_str
+    @BlogPosts
+        |
+        |=> blogpost_id -> BlogPostId = blogpost_id_str
+);
+
+exec!(PageId);
+exec!(BlogPostId);